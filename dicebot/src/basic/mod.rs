@@ -1,3 +1,4 @@
 pub mod dice;
 pub mod parser;
+pub mod probability;
 pub mod roll;
@@ -0,0 +1,188 @@
+use super::dice::{Element, ElementExpression, SignedElement};
+use super::roll::{Roll, Rolled};
+use crate::error::DiceRollingError;
+use crate::probability::{percent, simulated_rolls, Distribution, Odds};
+use crate::rng::DiceRng;
+
+/// Expressions that would take more steps than this to calculate
+/// exactly have their odds simulated instead.
+const MAX_EXACT_STEPS: f64 = 20_000_000.0;
+
+/// Expressions with more possible totals than this are too large to
+/// calculate odds for at all.
+const MAX_OUTCOME_RANGE: f64 = 1_000_000.0;
+
+fn dice_in(expression: &ElementExpression) -> impl Iterator<Item = (u32, u32)> + '_ {
+    expression.iter().filter_map(|element| match element {
+        SignedElement::Positive(Element::Dice(dice))
+        | SignedElement::Negative(Element::Dice(dice)) => Some((dice.count, dice.sides)),
+        _ => None,
+    })
+}
+
+/// Rough amount of steps needed to calculate the exact distribution,
+/// by adding up the dice one at a time.
+fn exact_steps(expression: &ElementExpression) -> f64 {
+    let mut steps = 0.0;
+    let mut width = 1.0;
+
+    for (count, sides) in dice_in(expression) {
+        let (count, sides) = (count as f64, sides as f64);
+        steps += sides * (count * width + (sides - 1.0) * count * (count - 1.0) / 2.0);
+        width += count * (sides - 1.0);
+    }
+
+    steps
+}
+
+fn exact_totals(expression: &ElementExpression) -> Distribution {
+    expression
+        .iter()
+        .fold(Distribution::constant(0), |total, element| {
+            let (element, negative) = match element {
+                SignedElement::Positive(element) => (element, false),
+                SignedElement::Negative(element) => (element, true),
+            };
+
+            let dist = match element {
+                Element::Bonus(bonus) => Distribution::constant(*bonus as i32),
+                Element::Dice(dice) => {
+                    let die = Distribution::uniform(1, dice.sides as i32);
+                    (0..dice.count).fold(Distribution::constant(0), |sum, _| sum.add(&die))
+                }
+            };
+
+            match negative {
+                true => total.add(&dist.negate()),
+                false => total.add(&dist),
+            }
+        })
+}
+
+/// Calculate the odds of a regular dice expression: the expected
+/// total, how likely each total is, and, if a target is given, the
+/// chance of rolling at least the target. Small expressions are
+/// calculated exactly; larger ones are estimated by rolling them a
+/// bounded number of times with the given generator.
+pub fn expression_odds(
+    expression: &ElementExpression,
+    target: Option<i32>,
+    rng: &DiceRng,
+) -> Result<Odds, DiceRollingError> {
+    if dice_in(expression).any(|(_, sides)| sides == 0) {
+        return Err(DiceRollingError::InvalidAmount);
+    }
+
+    let range: f64 = dice_in(expression)
+        .map(|(count, sides)| count as f64 * (sides as f64 - 1.0))
+        .sum();
+
+    if range > MAX_OUTCOME_RANGE {
+        return Err(DiceRollingError::ExpressionTooLarge);
+    }
+
+    let (totals, simulated) = if exact_steps(expression) <= MAX_EXACT_STEPS {
+        (exact_totals(expression), None)
+    } else {
+        let dice_per_roll: u64 = dice_in(expression).map(|(count, _)| count as u64).sum();
+        let rolls = simulated_rolls(dice_per_roll).ok_or(DiceRollingError::ExpressionTooLarge)?;
+        let totals = rng.with(|rng| {
+            let samples = (0..rolls).map(|_| expression.roll(rng).rolled_value());
+            Distribution::from_samples(samples)
+        });
+
+        (totals, Some(rolls))
+    };
+
+    let mut statistics = vec![];
+    if let Some(target) = target {
+        statistics.push((
+            "Success chance".to_string(),
+            percent(totals.at_least(target)),
+        ));
+    }
+
+    statistics.push((
+        "Expected total".to_string(),
+        format!("{:.2}", totals.expected_value()),
+    ));
+
+    let description = match target {
+        Some(target) => format!("{} (target: {} or higher)", expression, target),
+        None => expression.to_string(),
+    };
+
+    Ok(Odds {
+        description,
+        statistics,
+        histogram: totals.histogram_rows(),
+        simulated_rolls: simulated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basic::dice::Dice;
+
+    fn expression(elements: Vec<SignedElement>) -> ElementExpression {
+        ElementExpression(elements)
+    }
+
+    #[test]
+    fn exact_totals_with_bonus_test() {
+        let expr = expression(vec![
+            SignedElement::Positive(Element::Dice(Dice::new(2, 6))),
+            SignedElement::Negative(Element::Bonus(2)),
+        ]);
+
+        let totals = exact_totals(&expr);
+        assert_eq!(0, totals.min());
+        assert_eq!(10, totals.max());
+        assert!((totals.expected_value() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn success_chance_against_target_test() {
+        let expr = expression(vec![
+            SignedElement::Positive(Element::Dice(Dice::new(1, 20))),
+            SignedElement::Positive(Element::Bonus(5)),
+        ]);
+
+        let odds = expression_odds(&expr, Some(15), &DiceRng::seeded(1)).unwrap();
+        assert_eq!(
+            ("Success chance".to_string(), "55.00%".to_string()),
+            odds.statistics[0]
+        );
+        assert_eq!(None, odds.simulated_rolls);
+    }
+
+    #[test]
+    fn large_expressions_are_simulated_test() {
+        let expr = expression(vec![SignedElement::Positive(Element::Dice(Dice::new(
+            1000, 100,
+        )))]);
+
+        let odds = expression_odds(&expr, None, &DiceRng::seeded(1)).unwrap();
+        assert!(odds.simulated_rolls.is_some());
+    }
+
+    #[test]
+    fn huge_expressions_are_rejected_test() {
+        let expr = expression(vec![SignedElement::Positive(Element::Dice(Dice::new(
+            1_000_000, 1_000_000,
+        )))]);
+
+        let result = expression_odds(&expr, None, &DiceRng::seeded(1));
+        assert!(matches!(result, Err(DiceRollingError::ExpressionTooLarge)));
+    }
+
+    #[test]
+    fn zero_sided_dice_are_rejected_test() {
+        let expr = expression(vec![SignedElement::Positive(Element::Dice(Dice::new(
+            1, 0,
+        )))]);
+        let result = expression_odds(&expr, None, &DiceRng::seeded(1));
+        assert!(matches!(result, Err(DiceRollingError::InvalidAmount)));
+    }
+}
@@ -11,7 +11,7 @@ use std::ops::{Deref, DerefMut};
 pub trait Roll {
     type Output;
 
-    fn roll(&self) -> Self::Output;
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> Self::Output;
 }
 
 pub trait Rolled {
@@ -56,8 +56,7 @@ impl fmt::Display for DiceRoll {
 impl Roll for dice::Dice {
     type Output = DiceRoll;
 
-    fn roll(&self) -> DiceRoll {
-        let mut rng = rand::thread_rng();
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> DiceRoll {
         let rolls: Vec<_> = (0..self.count)
             .map(|_| rng.gen_range(1..=self.sides))
             .collect();
@@ -84,9 +83,9 @@ impl Rolled for ElementRoll {
 impl Roll for dice::Element {
     type Output = ElementRoll;
 
-    fn roll(&self) -> ElementRoll {
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> ElementRoll {
         match self {
-            dice::Element::Dice(d) => ElementRoll::Dice(d.roll()),
+            dice::Element::Dice(d) => ElementRoll::Dice(d.roll(rng)),
             dice::Element::Bonus(b) => ElementRoll::Bonus(*b),
         }
     }
@@ -119,10 +118,10 @@ impl Rolled for SignedElementRoll {
 impl Roll for dice::SignedElement {
     type Output = SignedElementRoll;
 
-    fn roll(&self) -> SignedElementRoll {
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> SignedElementRoll {
         match self {
-            dice::SignedElement::Positive(e) => SignedElementRoll::Positive(e.roll()),
-            dice::SignedElement::Negative(e) => SignedElementRoll::Negative(e.roll()),
+            dice::SignedElement::Positive(e) => SignedElementRoll::Positive(e.roll(rng)),
+            dice::SignedElement::Negative(e) => SignedElementRoll::Negative(e.roll(rng)),
         }
     }
 }
@@ -153,6 +152,20 @@ impl DerefMut for ElementExpressionRoll {
     }
 }
 
+impl ElementExpressionRoll {
+    /// Every individual die rolled in the expression, in order.
+    /// Bonuses are not included.
+    pub fn dice(&self) -> Vec<u32> {
+        self.iter()
+            .flat_map(|element| match element {
+                SignedElementRoll::Positive(ElementRoll::Dice(d))
+                | SignedElementRoll::Negative(ElementRoll::Dice(d)) => d.rolls().to_vec(),
+                _ => vec![],
+            })
+            .collect()
+    }
+}
+
 impl Rolled for ElementExpressionRoll {
     fn rolled_value(&self) -> i32 {
         self.iter().map(Rolled::rolled_value).sum()
@@ -162,8 +175,8 @@ impl Rolled for ElementExpressionRoll {
 impl Roll for dice::ElementExpression {
     type Output = ElementExpressionRoll;
 
-    fn roll(&self) -> ElementExpressionRoll {
-        ElementExpressionRoll(self.iter().map(Roll::roll).collect())
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> ElementExpressionRoll {
+        ElementExpressionRoll(self.iter().map(|element| element.roll(rng)).collect())
     }
 }
 
@@ -235,6 +248,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn element_expression_roll_dice_test() {
+        let roll = ElementExpressionRoll(vec![
+            SignedElementRoll::Positive(ElementRoll::Dice(DiceRoll(vec![1, 3, 4]))),
+            SignedElementRoll::Positive(ElementRoll::Bonus(4)),
+            SignedElementRoll::Negative(ElementRoll::Dice(DiceRoll(vec![2]))),
+        ]);
+
+        assert_eq!(roll.dice(), vec![1, 3, 4, 2]);
+    }
+
     #[test]
     fn element_expression_roll_display_test() {
         assert_eq!(
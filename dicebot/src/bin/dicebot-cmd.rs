@@ -1,51 +1,704 @@
-use matrix_sdk::identifiers::room_id;
+use matrix_sdk::identifiers::{room_id, RoomId, UserId};
 use matrix_sdk::Client;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::io::Write;
+use std::str::FromStr;
+use tenebrous_dicebot::bot::{
+    commands_in_message, inline_roll_command, inline_rolls_in_message, CommandTrigger,
+};
 use tenebrous_dicebot::commands;
-use tenebrous_dicebot::commands::ResponseExtractor;
+use tenebrous_dicebot::commands::{ExecutionResult, ResponseExtractor};
+use tenebrous_dicebot::config::DEFAULT_COMMAND_PREFIX;
 use tenebrous_dicebot::context::{Context, RoomContext};
 use tenebrous_dicebot::db::sqlite::Database;
 use tenebrous_dicebot::error::BotError;
-use tenebrous_dicebot::models::Account;
+use tenebrous_dicebot::fairness;
+use tenebrous_dicebot::logic;
+use tenebrous_dicebot::models::{GameSystem, RoomSetting, Verbosity};
+use tenebrous_dicebot::rng::DiceRng;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
 use url::Url;
 
+const USAGE: &str =
+    "usage: dicebot-cmd [--format plain|html|json] [--db <path>] [--seed <number>] <command>
+       dicebot-cmd [--format plain|html|json] [--db <path>] [--seed <number>] --repl
+       dicebot-cmd [--format plain|html|json] [--db <path>] verify --secret <secret> \
+           --commitment <hash> --event <event id> [--prefix <prefix>] \
+           [--system <game system>] <message>";
+
+const REPL_HELP: &str = "Messages are executed like the bot executes messages sent to a room:
+every line starting with the command prefix is a command. End a line
+with \\ to continue the message on the next line. A ``` code block is
+part of the message until it is closed.
+
+  :user <user id>   switch the user sending messages
+  :room <room id>   switch the room messages are sent to
+  :secure on|off    whether the room is an encrypted direct message
+  :admin on|off     whether the user is a bot admin
+  :history          show the messages sent so far
+  :redo <number>    send a message from the history again
+  :help             show this help
+  :quit             leave the REPL";
+
+/// The user ID of the simulated bot, which commands can be addressed
+/// to by name.
+const BOT_USER: &str = "@dicebot:example.com";
+
+/// How the result of the command is printed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Plain,
+    Html,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(OutputFormat::Plain),
+            "html" => Ok(OutputFormat::Html),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format: {}", s)),
+        }
+    }
+}
+
+/// A roll posted in fair mode, to be checked with the revealed secret.
+#[derive(Debug)]
+struct Verification {
+    secret: String,
+    commitment: String,
+    event_id: String,
+
+    /// The command prefix of the room the message was posted in, if
+    /// it has its own.
+    prefix: Option<String>,
+
+    /// The game system that !r rolls dice for in the room the message
+    /// was posted in, if it is not the default one.
+    system: Option<GameSystem>,
+}
+
+/// Read the options of the verify subcommand. Everything after them
+/// is the message that was rolled for.
+fn parse_verification(
+    args: &mut std::iter::Peekable<impl Iterator<Item = String>>,
+) -> Result<Verification, String> {
+    let (mut secret, mut commitment, mut event_id) = (None, None, None);
+    let (mut prefix, mut system) = (None, None);
+
+    while let Some(arg) = args.peek() {
+        let value = match arg.as_str() {
+            "--secret" => &mut secret,
+            "--commitment" => &mut commitment,
+            "--event" => &mut event_id,
+            "--prefix" => &mut prefix,
+            "--system" => &mut system,
+            _ => break,
+        };
+
+        let option = args.next().unwrap_or_default();
+        *value = Some(args.next().ok_or(format!("{} needs a value", option))?);
+    }
+
+    let system = system
+        .map(|key| GameSystem::from_key(&key).ok_or(format!("unknown game system: {}", key)))
+        .transpose()?;
+
+    Ok(Verification {
+        secret: secret.ok_or("verify needs --secret")?,
+        commitment: commitment.ok_or("verify needs --commitment")?,
+        event_id: event_id.ok_or("verify needs --event")?,
+        prefix,
+        system,
+    })
+}
+
+/// Options given on the command line, before the command itself.
+#[derive(Debug)]
+struct Options {
+    format: OutputFormat,
+
+    /// Database file kept between runs. A temporary database is used
+    /// if none is given.
+    db_path: Option<String>,
+
+    /// Seed for the dice, which makes rolls the same on every run.
+    seed: Option<u64>,
+
+    /// Read messages from standard input until it ends, instead of
+    /// executing one command.
+    repl: bool,
+
+    /// Check the rolls of a message posted in fair mode, instead of
+    /// executing a command. The input is the whole message.
+    verify: Option<Verification>,
+
+    input: String,
+}
+
+/// Read the options at the start of the arguments. Everything after
+/// them is the command, which may have flags of its own.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut args = args.peekable();
+    let mut format = OutputFormat::Plain;
+    let mut db_path = None;
+    let mut seed = None;
+    let mut repl = false;
+
+    while let Some(arg) = args.peek() {
+        match arg.as_str() {
+            "--format" => {
+                args.next();
+                let value = args.next().ok_or("--format needs a value")?;
+                format = value.parse()?;
+            }
+            "--db" => {
+                args.next();
+                db_path = Some(args.next().ok_or("--db needs a path")?);
+            }
+            "--seed" => {
+                args.next();
+                let value = args.next().ok_or("--seed needs a number")?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed: {}", value))?;
+                seed = Some(value);
+            }
+            "--repl" => {
+                args.next();
+                repl = true;
+            }
+            _ => break,
+        }
+    }
+
+    let verify = match args.peek().map(String::as_str) {
+        Some("verify") => {
+            args.next();
+            Some(parse_verification(&mut args)?)
+        }
+        _ => None,
+    };
+
+    if verify.is_some() && (repl || seed.is_some()) {
+        return Err("verify cannot be used with --repl or --seed".to_owned());
+    }
+
+    let input = args.collect::<Vec<_>>().join(" ");
+    match (input.trim(), repl) {
+        ("", false) => Err("no command given".to_owned()),
+        (_, true) if !input.is_empty() => Err("no command can be given with --repl".to_owned()),
+        _ => Ok(Options {
+            format,
+            db_path,
+            seed,
+            repl,
+            verify,
+            input,
+        }),
+    }
+}
+
+/// The message as plain text, like the bot sends it to Matrix.
+fn plain_text(html: &str) -> String {
+    html2text::from_read(html.as_bytes(), 80).trim().to_owned()
+}
+
+/// The result of a command as JSON, with the structured roll data if
+/// the command rolled dice. The command is missing if it could not be
+/// parsed.
+fn json_output(command: Option<&str>, result: &ExecutionResult) -> Value {
+    let html = result.message_html("fakeuser");
+    match result {
+        Ok(execution) => json!({
+            "command": command,
+            "success": true,
+            "plain": plain_text(&html),
+            "html": html,
+            "roll": execution.roll_result().map(|roll| json!({
+                "system": roll.system.key(),
+                "expression": roll.summary.expression,
+                "dice": roll.summary.dice,
+                "total": roll.summary.total,
+                "outcome": roll.summary.outcome,
+            })),
+            "error": null,
+        }),
+        Err(e) => json!({
+            "command": command,
+            "success": false,
+            "plain": null,
+            "html": null,
+            "roll": null,
+            "error": {
+                "kind": e.kind(),
+                "message": e.to_string(),
+            },
+        }),
+    }
+}
+
+fn print_result(format: OutputFormat, command: Option<&str>, result: &ExecutionResult) {
+    match format {
+        OutputFormat::Plain => println!("{}", plain_text(&result.message_html("fakeuser"))),
+        OutputFormat::Html => println!("{}", result.message_html("fakeuser")),
+        OutputFormat::Json => println!("{}", json_output(command, result)),
+    }
+}
+
+/// The results of the commands of a message, each with the name of
+/// the command, which is missing if the command could not be parsed.
+type CommandResults = Vec<(Option<&'static str>, ExecutionResult)>;
+
+fn print_results(format: OutputFormat, results: &CommandResults) {
+    for (command, result) in results {
+        print_result(format, *command, result);
+    }
+}
+
+/// The state of a REPL session. The database and Matrix client are
+/// kept for the whole session, while the user and room the messages
+/// come from can be switched.
+struct Session {
+    db: Database,
+    matrix_client: Client,
+    format: OutputFormat,
+    rng: DiceRng,
+    user: UserId,
+    room: RoomId,
+    secure: bool,
+    admin: bool,
+    history: Vec<String>,
+}
+
+impl Session {
+    fn new(db: Database, format: OutputFormat, rng: DiceRng) -> Result<Session, BotError> {
+        let homeserver = Url::parse("http://example.com")?;
+        let matrix_client = Client::new(homeserver).expect("Could not create matrix client");
+
+        Ok(Session {
+            db,
+            matrix_client,
+            format,
+            rng,
+            user: UserId::try_from("@localuser:example.com")?,
+            room: room_id!("!fakeroomid:example.com"),
+            secure: false,
+            admin: false,
+            history: vec![],
+        })
+    }
+
+    /// Give the room of the session the command prefix and game
+    /// system of another room, like the room a message was posted in.
+    async fn set_up_room(
+        &self,
+        prefix: Option<&str>,
+        system: Option<GameSystem>,
+    ) -> Result<(), BotError> {
+        let room_id = self.room.as_str();
+        let prefix_setting = RoomSetting::CommandPrefix;
+        let system_setting = RoomSetting::DefaultSystem;
+
+        if let Some(prefix) = prefix {
+            logic::set_room_setting(&self.db, room_id, prefix_setting, Some(prefix)).await?;
+        }
+
+        if let Some(system) = system {
+            logic::set_room_setting(&self.db, room_id, system_setting, Some(system.key())).await?;
+        }
+
+        Ok(())
+    }
+
+    /// How commands are recognized in the room of the session.
+    async fn command_trigger(&self) -> Result<CommandTrigger, BotError> {
+        let prefix = logic::room_command_prefix(&self.db, self.room.as_str())
+            .await?
+            .unwrap_or_else(|| DEFAULT_COMMAND_PREFIX.to_owned());
+
+        let names = vec![BOT_USER.to_owned(), "dicebot".to_owned()];
+        Ok(CommandTrigger::new(prefix, names))
+    }
+
+    /// Execute every command in a message, the same way the bot does
+    /// for messages sent to a room, and print the results.
+    async fn send_message(&mut self, message: &str) -> Result<(), BotError> {
+        self.history.push(message.to_owned());
+
+        let trigger = self.command_trigger().await?;
+        let results = match commands_in_message(message, &trigger) {
+            Ok(commands) => self.execute_commands(&commands).await?,
+            Err(e) => vec![(None, Err(e))],
+        };
+
+        print_results(self.format, &results);
+        Ok(())
+    }
+
+    /// Roll the dice of a message again, like the bot did when the
+    /// message was posted: its commands, or else its inline rolls.
+    async fn verify_message(&self, message: &str) -> Result<CommandResults, BotError> {
+        let trigger = self.command_trigger().await?;
+        let commands =
+            commands_in_message(message, &trigger).and_then(|commands| match commands.is_empty() {
+                true => Ok(inline_rolls_in_message(message)?
+                    .into_iter()
+                    .map(inline_roll_command)
+                    .collect()),
+                false => Ok(commands),
+            });
+
+        match commands {
+            Ok(commands) => self.execute_commands(&commands).await,
+            Err(e) => Ok(vec![(None, Err(e))]),
+        }
+    }
+
+    /// Execute commands one after the other, rolling with the dice of
+    /// the session.
+    async fn execute_commands(&self, commands: &[String]) -> Result<CommandResults, BotError> {
+        let username = self.user.as_str();
+        let room = RoomContext {
+            id: &self.room,
+            display_name: self.room.to_string(),
+            secure: self.secure,
+        };
+
+        let mut results = vec![];
+        for command in commands {
+            let ctx = Context {
+                db: self.db.clone(),
+                account: logic::get_account(&self.db, username).await?,
+                matrix_client: self.matrix_client.clone(),
+                origin_room: room.clone(),
+                active_room: room.clone(),
+                username,
+                message_body: command,
+                thread_id: None,
+                admin: self.admin,
+                verbosity: Verbosity::default(),
+                rng: self.rng.clone(),
+            };
+
+            let name = commands::command_name(&ctx).await;
+            let result = commands::execute_command(&ctx).await;
+            results.push((name, result));
+        }
+
+        Ok(results)
+    }
+
+    /// Execute a REPL command, like ":user @someone:example.com".
+    /// Returns false if the session should end.
+    async fn repl_command(&mut self, input: &str) -> Result<bool, String> {
+        let (command, arg) = input.split_once(' ').unwrap_or((input, ""));
+        let arg = arg.trim();
+        let on_off = |arg: &str| match arg {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(format!("expected on or off, not: {}", arg)),
+        };
+
+        match command {
+            ":user" => self.user = UserId::try_from(arg).map_err(|e| e.to_string())?,
+            ":room" => self.room = RoomId::try_from(arg).map_err(|e| e.to_string())?,
+            ":secure" => self.secure = on_off(arg)?,
+            ":admin" => self.admin = on_off(arg)?,
+            ":history" => {
+                for (number, message) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", number + 1, message.replace('\n', "\n      "));
+                }
+            }
+            ":redo" => {
+                let message = arg
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| self.history.get(number.wrapping_sub(1)))
+                    .cloned()
+                    .ok_or_else(|| format!("no message {} in the history", arg))?;
+
+                self.send_message(&message)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            ":help" => println!("{}", REPL_HELP),
+            ":quit" => return Ok(false),
+            _ => return Err(format!("unknown REPL command: {}", command)),
+        }
+
+        Ok(true)
+    }
+
+    fn prompt(&self) -> String {
+        let admin = if self.admin { " (admin)" } else { "" };
+        format!("{}{} in {}> ", self.user, admin, self.room)
+    }
+}
+
+/// Read one message. Lines ending with a backslash, and code blocks,
+/// continue the message on the next line. None is returned when the
+/// input ends.
+async fn read_message(
+    lines: &mut Lines<BufReader<Stdin>>,
+    prompt: &str,
+) -> std::io::Result<Option<String>> {
+    let mut message = String::new();
+    let mut in_code_block = false;
+
+    loop {
+        match message.is_empty() {
+            true => print!("{}", prompt),
+            false => print!("... "),
+        }
+
+        std::io::stdout().flush()?;
+
+        let line = match lines.next_line().await? {
+            Some(line) => line,
+            None if message.is_empty() => return Ok(None),
+            None => return Ok(Some(message)),
+        };
+
+        if !message.is_empty() {
+            message.push('\n');
+        }
+
+        let (line, continued) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line.as_str(), false),
+        };
+
+        message.push_str(line);
+
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+
+        if !continued && !in_code_block {
+            return Ok(Some(message));
+        }
+    }
+}
+
+async fn repl(mut session: Session) -> Result<(), BotError> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    eprintln!("Type :help for REPL commands, :quit to leave.");
+
+    while let Some(message) = read_message(&mut lines, &session.prompt()).await? {
+        let input = message.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        if input.starts_with(':') {
+            match session.repl_command(input).await {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => eprintln!("{}", e),
+            }
+        } else if let Err(e) = session.send_message(&message).await {
+            eprintln!("{}", e);
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), BotError> {
-    let input = std::env::args().skip(1).collect::<Vec<String>>().join(" ");
-    let command = match commands::parser::parse_command(&input) {
-        Ok(command) => command,
-        Err(e) => return Err(e),
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let input = &options.input;
+
+    let rng = match &options.verify {
+        Some(verification) => {
+            let Verification {
+                secret,
+                commitment,
+                event_id,
+                ..
+            } = verification;
+
+            if !fairness::matches_commitment(secret, commitment) {
+                eprintln!("The secret does not match the commitment {}", commitment);
+                std::process::exit(1);
+            }
+
+            eprintln!("The secret matches the commitment, rolls of {}:", event_id);
+            fairness::event_rng(secret, event_id)
+        }
+        None => options.seed.map(DiceRng::seeded).unwrap_or_default(),
     };
 
-    let homeserver = Url::parse("http://example.com")?;
-    let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
-    let db = Database::new(
-        db_path
+    // The temporary database is removed when the file is dropped.
+    let temp_db = match &options.db_path {
+        Some(_) => None,
+        None => Some(tempfile::NamedTempFile::new_in(".")?),
+    };
+
+    let db_path = match &temp_db {
+        Some(temp_db) => temp_db
             .path()
             .to_str()
             .expect("Could not get path to temporary db"),
-    )
-    .await?;
-
-    let context = Context {
-        db,
-        account: Account::default(),
-        matrix_client: Client::new(homeserver).expect("Could not create matrix client"),
-        origin_room: RoomContext {
-            id: &room_id!("!fakeroomid:example.com"),
-            display_name: "fake room".to_owned(),
-            secure: false,
-        },
-        active_room: RoomContext {
-            id: &room_id!("!fakeroomid:example.com"),
-            display_name: "fake room".to_owned(),
-            secure: false,
-        },
-        username: "@localuser:example.com",
-        message_body: &input,
+        None => options.db_path.as_deref().unwrap_or_default(),
     };
 
-    let message = command.execute(&context).await.message_html("fakeuser");
-    let message = html2text::from_read(message.as_bytes(), 80);
-    println!("{}", message.trim());
+    let db = Database::new(db_path).await?;
+    let session = Session::new(db, options.format, rng)?;
+
+    // The REPL reads messages, and verify takes a whole message, not
+    // a single command, read the way its room reads messages.
+    if options.repl {
+        return repl(session).await;
+    } else if let Some(verification) = &options.verify {
+        let prefix = verification.prefix.as_deref();
+        session.set_up_room(prefix, verification.system).await?;
+
+        let results = session.verify_message(input).await?;
+        print_results(session.format, &results);
+        return Ok(());
+    }
+
+    // A command that could not be parsed is a usage error. Exiting
+    // skips destructors, so the temporary database is removed first.
+    let results = session.execute_commands(&[input.to_owned()]).await?;
+    print_results(session.format, &results);
+
+    if results.iter().any(|(command, _)| command.is_none()) {
+        drop(session);
+        drop(temp_db);
+        std::process::exit(1);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tenebrous_dicebot::commands::Execution;
+    use tenebrous_dicebot::models::RollSummary;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        parse_args(args.split(' ').map(str::to_owned))
+    }
+
+    /// A session with a database of its own, which is removed when
+    /// the returned file is dropped.
+    async fn temp_session(rng: DiceRng) -> (tempfile::NamedTempFile, Session) {
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::new(db_file.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let session = Session::new(db, OutputFormat::Plain, rng).unwrap();
+        (db_file, session)
+    }
+
+    fn roll_summaries(results: &CommandResults) -> Vec<RollSummary> {
+        results
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok()?.roll_summary().cloned())
+            .collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let options = parse("--format json --seed 42 !r 1d20 --compact").unwrap();
+        assert_eq!(options.format, OutputFormat::Json);
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.db_path, None);
+        assert_eq!(options.input, "!r 1d20 --compact");
+
+        let options = parse("--db dice.db --repl").unwrap();
+        assert_eq!(options.db_path.as_deref(), Some("dice.db"));
+        assert!(options.repl);
+
+        let options = parse("verify --secret abc --commitment def --event $e !r 1d20").unwrap();
+        let verification = options.verify.unwrap();
+        assert_eq!(verification.secret, "abc");
+        assert_eq!(verification.event_id, "$e");
+        assert_eq!(options.input, "!r 1d20");
+
+        let options =
+            parse("verify --secret a --commitment b --event c --prefix . --system cofd .r 5")
+                .unwrap();
+        let verification = options.verify.unwrap();
+        assert_eq!(verification.prefix.as_deref(), Some("."));
+        assert_eq!(verification.system, Some(GameSystem::ChroniclesOfDarkness));
+        assert_eq!(options.input, ".r 5");
+
+        assert!(parse("--format xml !r 1d20").is_err());
+        assert!(parse("--seed lots !r 1d20").is_err());
+        assert!(parse("--repl !r 1d20").is_err());
+        assert!(parse("--seed 1 verify --secret a --commitment b --event c !r 1").is_err());
+        assert!(parse("verify --secret abc !r 1d20").is_err());
+        assert!(parse("verify --secret a --commitment b --event c --system d20 !r 1").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fair_roll_verifies_test() {
+        let event_id = "$roll:example.com";
+        let message = ".r 5\n.r r:8";
+        let system = Some(GameSystem::ChroniclesOfDarkness);
+
+        // The bot rolls in fair mode, in a room with its own prefix
+        // and game system.
+        let (_bot_db, bot) = temp_session(DiceRng::default()).await;
+        bot.set_up_room(Some("."), system).await.unwrap();
+
+        let fair = logic::restart_fair_mode(&bot.db, true).await.unwrap();
+        let fair = fair.expect("Fair mode did not start a session");
+        let rng = logic::event_rng(&bot.db, event_id).await.unwrap();
+        let bot = Session { rng, ..bot };
+
+        let trigger = bot.command_trigger().await.unwrap();
+        let commands = commands_in_message(message, &trigger).unwrap();
+        let rolled = bot.execute_commands(&commands).await.unwrap();
+
+        // Anyone can roll the same dice once the secret is revealed.
+        let rng = fairness::event_rng(&fair.secret, event_id);
+        let (_db, verifier) = temp_session(rng).await;
+        verifier.set_up_room(Some("."), system).await.unwrap();
+        let verified = verifier.verify_message(message).await.unwrap();
+
+        assert_eq!(roll_summaries(&rolled).len(), 2);
+        assert_eq!(roll_summaries(&rolled), roll_summaries(&verified));
+
+        let execution = rolled[0].1.as_ref().unwrap();
+        assert_eq!(execution.fair_session(), Some(fair.id));
+        assert!(execution.html().contains(&format!("session {}", fair.id)));
+    }
+
+    #[test]
+    fn json_output_test() {
+        let success = json_output(Some("help"), &Execution::success("<b>hi</b>".to_owned()));
+        assert_eq!(success["command"], "help");
+        assert_eq!(success["success"], true);
+        assert_eq!(success["plain"], "hi");
+        assert_eq!(success["html"], "<p><b>hi</b></p>");
+        assert_eq!(success["roll"], Value::Null);
+        assert_eq!(success["error"], Value::Null);
+
+        let failure = json_output(None, &Err(BotError::FairRollsOff));
+        assert_eq!(failure["command"], Value::Null);
+        assert_eq!(failure["success"], false);
+        assert_eq!(failure["error"]["kind"], "FairRollsOff");
+        assert_eq!(
+            failure["error"]["message"],
+            BotError::FairRollsOff.to_string()
+        );
+    }
+}
@@ -3,9 +3,10 @@ use crate::db::sqlite::Database;
 use crate::error::BotError;
 use crate::logic;
 use crate::matrix;
+use crate::rng::DiceRng;
 use crate::{
-    commands::{execute_command, ExecutionResult, ResponseExtractor},
-    models::Account,
+    commands::{escape_html, execute_command, ExecutionResult, ResponseExtractor},
+    models::{Account, ReplyType, Verbosity},
 };
 use futures::stream::{self, StreamExt};
 use matrix_sdk::{
@@ -17,6 +18,45 @@ use matrix_sdk::{
 use std::clone::Clone;
 use std::convert::TryFrom;
 
+/// How a response to a command message is sent.
+pub(super) struct ResponseOptions<'a> {
+    /// The thread the command was sent in, if any.
+    pub thread: Option<&'a EventId>,
+
+    /// The earlier reply to edit instead of sending a new one.
+    pub previous_reply: Option<EventId>,
+
+    /// Whether the response is a notice or an ordinary message.
+    pub reply_type: ReplyType,
+}
+
+/// Send a response to a command message, in the thread the command
+/// was sent in, or edit the earlier reply to it instead, if there is
+/// one. Returns the ID of the reply.
+async fn respond(
+    client: &Client,
+    room: &Joined,
+    message: (&str, &str),
+    reply_to: Option<EventId>,
+    options: ResponseOptions<'_>,
+) -> Option<EventId> {
+    let room_id = room.room_id();
+    let ResponseOptions {
+        thread,
+        previous_reply,
+        reply_type,
+    } = options;
+
+    match previous_reply {
+        Some(reply_id) => {
+            matrix::edit_message(client, room_id, reply_id.clone(), message, reply_type)
+                .await
+                .map(|_| reply_id)
+        }
+        None => matrix::send_message(client, room_id, message, reply_to, thread, reply_type).await,
+    }
+}
+
 /// Handle responding to a single command being executed. Wil print
 /// out the full result of that command.
 pub(super) async fn handle_single_result(
@@ -25,10 +65,69 @@ pub(super) async fn handle_single_result(
     respond_to: &str,
     room: &Joined,
     event_id: EventId,
-) {
+    options: ResponseOptions<'_>,
+) -> Option<EventId> {
     let html = cmd_result.message_html(respond_to);
     let plain = cmd_result.message_plain(respond_to);
-    matrix::send_message(client, room.room_id(), (&html, &plain), Some(event_id)).await;
+    respond(client, room, (&html, &plain), Some(event_id), options).await
+}
+
+/// Describe the result of one inline roll: the total and the dice
+/// rolled, and the fair mode session that seeded them, or the error.
+fn format_inline_result(expression: &str, result: &ExecutionResult) -> String {
+    let summary = result
+        .as_ref()
+        .map(|execution| (execution.roll_summary(), execution.fair_session()));
+
+    match summary {
+        Ok((Some(roll), fair_session)) => {
+            let dice: Vec<String> = roll.dice.iter().map(|die| die.to_string()).collect();
+            let fair = fair_session
+                .map(|session| format!(", fair session {}", session))
+                .unwrap_or_default();
+
+            format!(
+                "<strong>[[{}]]</strong> = {} ({}{})",
+                expression,
+                roll.total,
+                dice.join(" + "),
+                fair
+            )
+        }
+        Ok((None, _)) => format!("<strong>[[{}]]</strong> did not roll dice", expression),
+        Err(e) => format!("<strong>[[{}]]</strong>: {}", expression, e),
+    }
+}
+
+/// Handle responding to the inline rolls of a message. Every roll is
+/// listed in a single reply.
+pub(super) async fn handle_inline_results(
+    client: &Client,
+    results: &[(String, ExecutionResult)],
+    respond_to: &str,
+    room: &Joined,
+    event_id: EventId,
+    options: ResponseOptions<'_>,
+) -> Option<EventId> {
+    let user_pill = format!(
+        "<a href=\"https://matrix.to/#/{}\">{}</a>",
+        respond_to, respond_to
+    );
+
+    let rolls: Vec<String> = results
+        .iter()
+        .map(|(expression, result)| format_inline_result(&escape_html(expression), result))
+        .collect();
+
+    let rolls = rolls.join("<br/>");
+    let html = format!("<p>{}:<br/>{}</p>", user_pill, rolls);
+    let plain = format!(
+        "{}:\n{}",
+        respond_to,
+        html2text::from_read(rolls.as_bytes(), rolls.len())
+    );
+
+    respond(client, room, (&html, &plain), Some(event_id), options).await
 }
 
 /// Format failure messages nicely in either HTML or plain text. If
@@ -75,7 +174,8 @@ pub(super) async fn handle_multiple_results(
     results: &[(String, ExecutionResult)],
     respond_to: &str,
     room: &Joined,
-) {
+    options: ResponseOptions<'_>,
+) -> Option<EventId> {
     let user_pill = format!(
         "<a href=\"https://matrix.to/#/{}\">{}</a>",
         respond_to, respond_to
@@ -101,7 +201,7 @@ pub(super) async fn handle_multiple_results(
         )
     };
 
-    matrix::send_message(client, room.room_id(), (&message, &plain), None).await;
+    respond(client, room, (&message, &plain), None, options).await
 }
 
 /// Map an account's active room value to an actual matrix room, if
@@ -119,6 +219,21 @@ fn get_account_active_room(client: &Client, account: &Account) -> Result<Option<
     Ok(active_room)
 }
 
+/// Who commands are executed for, and where: the sender, and the room
+/// and thread the commands were sent in. Admin is true if the sender
+/// is a bot admin. The thread is given if the room keeps the state of
+/// each thread apart.
+pub(super) struct CommandSource<'a> {
+    pub room: &'a Joined,
+    pub sender: &'a str,
+    pub admin: bool,
+    pub thread_id: Option<&'a str>,
+
+    /// What the dice of the commands are rolled with, one after the
+    /// other.
+    pub rng: DiceRng,
+}
+
 /// Execute a single command in the list of commands. Can fail if the
 /// Account value cannot be created/fetched from the database, or if
 /// room display names cannot be calculated. Otherwise, the success or
@@ -127,9 +242,16 @@ async fn execute_single_command(
     command: &str,
     db: &Database,
     client: &Client,
-    origin_room: &Joined,
-    sender: &str,
+    source: &CommandSource<'_>,
 ) -> ExecutionResult {
+    let CommandSource {
+        room: origin_room,
+        sender,
+        admin,
+        thread_id,
+        rng,
+    } = source;
+
     let origin_ctx = RoomContext::new(origin_room, sender).await?;
     let account = logic::get_account(db, sender).await?;
     let active_room = get_account_active_room(client, &account)?;
@@ -149,27 +271,30 @@ async fn execute_single_command(
         db: db.clone(),
         matrix_client: client.clone(),
         origin_room: origin_ctx,
-        username: &sender,
+        username: sender,
         active_room: active_ctx,
-        message_body: &command,
+        message_body: command,
+        thread_id: *thread_id,
+        admin: *admin,
+        verbosity: Verbosity::default(),
+        rng: rng.clone(),
     };
 
     execute_command(&ctx).await
 }
 
 /// Attempt to execute all commands sent to the bot in a message. This
-/// asynchronously executes all commands given to it. A Vec of all
-/// commands and their execution results are returned.
+/// asynchronously executes all commands given to it, in order. A Vec
+/// of all commands and their execution results are returned.
 pub(super) async fn execute(
     commands: Vec<&str>,
     db: &Database,
     client: &Client,
-    room: &Joined,
-    sender: &str,
+    source: &CommandSource<'_>,
 ) -> Vec<(String, ExecutionResult)> {
     stream::iter(commands)
         .then(|command| async move {
-            let result = execute_single_command(command, db, client, room, sender).await;
+            let result = execute_single_command(command, db, client, source).await;
             (command.to_owned(), result)
         })
         .collect()
@@ -1,19 +1,27 @@
-use super::DiceBot;
+use super::{CommandMessage, DiceBot};
 use crate::db::sqlite::Database;
-use crate::db::Rooms;
+use crate::db::{Rooms, Users};
 use crate::error::BotError;
+use crate::models::AccountStatus;
 use async_trait::async_trait;
 use log::{debug, error, info, warn};
 use matrix_sdk::{
     self,
+    deserialized_responses::SyncResponse,
     events::{
-        room::member::MemberEventContent,
-        room::message::{MessageEventContent, MessageType, TextMessageEventContent},
-        StrippedStateEvent, SyncMessageEvent,
+        reaction::ReactionEventContent,
+        room::member::{MemberEventContent, MembershipState},
+        room::message::{MessageEventContent, MessageType, Relation, TextMessageEventContent},
+        AnySyncMessageEvent, AnySyncRoomEvent, StrippedStateEvent, SyncMessageEvent,
+        SyncStateEvent,
     },
-    room::Room,
-    EventHandler,
+    identifiers::EventId,
+    room::{Joined, Room},
+    EventHandler, LoopCtrl, Raw,
 };
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt::Debug;
 use std::ops::Sub;
 use std::time::{Duration, SystemTime};
 use std::{clone::Clone, time::UNIX_EPOCH};
@@ -22,10 +30,10 @@ use std::{clone::Clone, time::UNIX_EPOCH};
 /// message is within "oldest_message_age" seconds, this function
 /// returns true. If it's older than that, it returns false and logs a
 /// debug message.
-fn check_message_age(
-    event: &SyncMessageEvent<MessageEventContent>,
-    oldest_message_age: u64,
-) -> bool {
+fn check_message_age<C>(event: &SyncMessageEvent<C>, oldest_message_age: u64) -> bool
+where
+    C: matrix_sdk::events::MessageEventContent + Debug,
+{
     let sending_time = event
         .origin_server_ts
         .to_system_time()
@@ -46,6 +54,67 @@ fn check_message_age(
     }
 }
 
+/// The ID of the message that an event edits, if the event is an edit.
+fn edited_event_id(event: &SyncMessageEvent<MessageEventContent>) -> Option<EventId> {
+    match &event.content.relates_to {
+        Some(Relation::Replacement(replacement)) => Some(replacement.event_id.clone()),
+        _ => None,
+    }
+}
+
+/// What a reaction to a roll reply of the bot asks for.
+#[derive(Debug, PartialEq)]
+enum ReactionAction {
+    /// Roll the same command again (original sender only).
+    Reroll,
+
+    /// Roll the same command for the reacting user.
+    RollForSelf,
+
+    /// Remove the reply (original sender only).
+    Redact,
+}
+
+fn reaction_action(key: &str) -> Option<ReactionAction> {
+    // Some clients send emoji with a variation selector.
+    match key.trim_end_matches('\u{fe0f}') {
+        "🔁" => Some(ReactionAction::Reroll),
+        "🎲" => Some(ReactionAction::RollForSelf),
+        "❌" => Some(ReactionAction::Redact),
+        _ => None,
+    }
+}
+
+/// The root of the thread a message was sent in, if it was sent in a
+/// thread. The SDK does not know about threads yet, so the relation
+/// is read from the raw event.
+fn thread_root(event: &Raw<AnySyncRoomEvent>) -> Option<EventId> {
+    #[derive(Deserialize)]
+    struct ThreadEvent {
+        content: ThreadContent,
+    }
+
+    #[derive(Deserialize)]
+    struct ThreadContent {
+        #[serde(rename = "m.relates_to")]
+        relates_to: Option<ThreadRelation>,
+    }
+
+    #[derive(Deserialize)]
+    struct ThreadRelation {
+        rel_type: Option<String>,
+        event_id: Option<EventId>,
+    }
+
+    let event: ThreadEvent = event.deserialize_as().ok()?;
+    let relation = event.content.relates_to?;
+
+    match relation.rel_type.as_deref() {
+        Some("m.thread") => relation.event_id,
+        _ => None,
+    }
+}
+
 /// Determine whether or not to process a received message. This check
 /// is necessary in addition to the event processing check because we
 /// may receive message events when entering a room for the first
@@ -68,22 +137,18 @@ async fn should_process_message<'a>(
         return Err(BotError::ShouldNotProcessError);
     }
 
-    let (msg_body, sender_username) = if let SyncMessageEvent {
-        content:
-            MessageEventContent {
-                msgtype: MessageType::Text(TextMessageEventContent { body, .. }),
-                ..
-            },
-        sender,
-        ..
-    } = event
-    {
-        (
-            body.clone(),
-            format!("@{}:{}", sender.localpart(), sender.server_name()),
-        )
-    } else {
-        (String::new(), String::new())
+    let sender = &event.sender;
+    let sender_username = format!("@{}:{}", sender.localpart(), sender.server_name());
+
+    // An edit carries the whole edited message as its new content.
+    let msgtype = match &event.content.relates_to {
+        Some(Relation::Replacement(replacement)) => &replacement.new_content.msgtype,
+        _ => &event.content.msgtype,
+    };
+
+    let msg_body = match msgtype {
+        MessageType::Text(TextMessageEventContent { body, .. }) => body.clone(),
+        _ => String::new(),
     };
 
     Ok((msg_body, sender_username))
@@ -101,8 +166,10 @@ async fn should_process_event(db: &Database, room_id: &str, event_id: &str) -> b
         })
 }
 
-/// This event emitter listens for messages with dice rolling commands.
-/// Originally adapted from the matrix-rust-sdk examples.
+/// This event emitter listens for invites to rooms, and for members
+/// leaving rooms. Messages with dice rolling commands are handled from
+/// the sync response instead. Originally adapted from the
+/// matrix-rust-sdk examples.
 #[async_trait]
 impl EventHandler for DiceBot {
     async fn on_stripped_state_member(
@@ -120,22 +187,133 @@ impl EventHandler for DiceBot {
             return;
         }
 
-        info!(
-            "Autojoining room {}",
-            room.display_name().await.ok().unwrap_or_default()
-        );
+        let room_name = room.display_name().await.ok().unwrap_or_default();
+        let inviter = event.sender.as_str();
+
+        if let Some(reason) = self.invite_refusal(inviter).await {
+            info!(
+                "Declining invite to room {} from {}: {}",
+                room_name, inviter, reason
+            );
+
+            if let Err(e) = room.reject_invitation().await {
+                warn!("Could not decline invite: {}", e.to_string())
+            }
+
+            return;
+        }
+
+        info!("Autojoining room {} (invited by {})", room_name, inviter);
 
         if let Err(e) = self.client.join_room_by_id(&room.room_id()).await {
             warn!("Could not join room: {}", e.to_string())
         }
     }
 
-    async fn on_room_message(&self, room: Room, event: &SyncMessageEvent<MessageEventContent>) {
+    async fn on_room_member(&self, room: Room, event: &SyncStateEvent<MemberEventContent>) {
         let room = match room {
             Room::Joined(joined_room) => joined_room,
             _ => return,
         };
 
+        let left = matches!(
+            event.content.membership,
+            MembershipState::Leave | MembershipState::Ban
+        );
+
+        if !left || !self.config.leave_empty_rooms() {
+            return;
+        }
+
+        let alone = match room.joined_members().await {
+            Ok(members) => members.iter().all(|m| m.user_id() == room.own_user_id()),
+            Err(e) => {
+                warn!("Could not get the members of a room: {}", e.to_string());
+                false
+            }
+        };
+
+        if alone {
+            info!("Leaving room {}, no one else is left in it", room.room_id());
+
+            if let Err(e) = room.leave().await {
+                warn!("Could not leave room: {}", e.to_string())
+            }
+        }
+    }
+}
+
+impl DiceBot {
+    /// Why an invite from a user should be declined, if it should be:
+    /// the user is not one of the allowed inviters, or does not have
+    /// a registered account when the config requires one.
+    async fn invite_refusal(&self, inviter: &str) -> Option<&'static str> {
+        if !self.config.is_inviter_allowed(inviter) {
+            return Some("not an allowed inviter");
+        }
+
+        if self.config.require_registered_inviter() {
+            let registered = match self.db.get_user(inviter).await {
+                Ok(user) => user.map_or(false, |u| u.account_status == AccountStatus::Registered),
+                Err(e) => {
+                    error!("Could not look up inviter: {}", e.to_string());
+                    false
+                }
+            };
+
+            if !registered {
+                return Some("inviter is not registered");
+            }
+        }
+
+        None
+    }
+
+    /// Handle the room messages and reactions of a sync response. Messages are read
+    /// from the sync response rather than in the event handler,
+    /// because the thread a message was sent in is only in the raw
+    /// event.
+    pub(super) async fn handle_sync_response(&self, response: SyncResponse) -> LoopCtrl {
+        for (room_id, joined) in response.rooms.join {
+            let room = match self.client.get_joined_room(&room_id) {
+                Some(room) => room,
+                None => continue,
+            };
+
+            for event in joined.timeline.events {
+                let message = match event.event.deserialize() {
+                    Ok(AnySyncRoomEvent::Message(message)) => message,
+                    _ => continue,
+                };
+
+                match message {
+                    AnySyncMessageEvent::RoomMessage(message) => {
+                        let thread = thread_root(&event.event);
+                        self.on_room_message(&room, &message, thread).await;
+                    }
+                    AnySyncMessageEvent::Reaction(reaction) => {
+                        self.on_room_reaction(&room, &reaction).await;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        LoopCtrl::Continue
+    }
+
+    async fn on_room_message(
+        &self,
+        room: &Joined,
+        event: &SyncMessageEvent<MessageEventContent>,
+        thread: Option<EventId>,
+    ) {
+        // Replies of the bot are ordinary messages in rooms that want
+        // them, and could otherwise be taken for commands.
+        if &event.sender == room.own_user_id() {
+            return;
+        }
+
         let room_id = room.room_id().as_str();
         if !should_process_event(&self.db, room_id, event.event_id.as_str()).await {
             return;
@@ -148,11 +326,248 @@ impl EventHandler for DiceBot {
                 return;
             };
 
-        let results = self
-            .execute_commands(&room, &sender_username, &msg_body)
-            .await;
+        // Edited commands are executed again, and answered by editing
+        // the reply to the original message.
+        let edited = edited_event_id(event);
+        let event_id = edited.clone().unwrap_or_else(|| event.event_id.clone());
 
-        self.handle_results(&room, &sender_username, event.event_id.clone(), results)
+        let previous_reply = match edited {
+            Some(_) => self.previous_reply(room, &event_id).await,
+            None => None,
+        };
+
+        // Anyone can send an edit of any message, but only the sender
+        // of a command may change what the bot answered to it.
+        if let Some(reply) = &previous_reply {
+            if reply.sender.as_deref() != Some(sender_username.as_str()) {
+                debug!("Ignoring edit of another user's command: {:?}", event);
+                return;
+            }
+        }
+
+        // An edit does not say which thread the edited command was
+        // sent in, so it is answered in the thread of the command.
+        let thread = match &previous_reply {
+            Some(reply) => reply
+                .thread_id
+                .as_deref()
+                .and_then(|thread| EventId::try_from(thread).ok()),
+            None => thread,
+        };
+
+        let previous_reply =
+            previous_reply.and_then(|reply| EventId::try_from(reply.reply_event_id.as_str()).ok());
+
+        // In fair mode, the dice are seeded from the original message,
+        // so editing a command cannot roll new dice. No dice are rolled
+        // if it is unknown whether fair mode is on.
+        let rng = match self.event_rng(&event_id).await {
+            Ok(rng) => rng,
+            Err(e) => {
+                error!("Could not get fair mode session: {}", e);
+                return;
+            }
+        };
+
+        // Commands sent in a thread are answered in the thread, and may
+        // keep their state apart from the rest of the room.
+        let thread = thread.as_ref();
+        let reply = match &event.content.msgtype {
+            MessageType::File(file) => {
+                let thread_id = self.thread_scope(room, thread).await;
+                let results = self
+                    .import_attachment(room, &sender_username, file, thread_id, rng)
+                    .await;
+
+                self.handle_results(
+                    room,
+                    &sender_username,
+                    &event_id,
+                    thread,
+                    previous_reply,
+                    results,
+                )
+                .await
+            }
+            _ => {
+                let message = CommandMessage {
+                    sender: &sender_username,
+                    body: &msg_body,
+                    event_id: &event_id,
+                    thread,
+                };
+
+                self.respond_to_message(room, message, previous_reply, rng)
+                    .await
+            }
+        };
+
+        self.remember_reply(room, &event_id, &sender_username, thread, reply)
             .await;
     }
+
+    /// Act on a reaction to a roll reply of the bot: roll the same
+    /// command again, roll it for the reacting user, or remove the
+    /// reply.
+    async fn on_room_reaction(
+        &self,
+        room: &Joined,
+        event: &SyncMessageEvent<ReactionEventContent>,
+    ) {
+        let room_id = room.room_id().as_str();
+        if !should_process_event(&self.db, room_id, event.event_id.as_str()).await {
+            return;
+        }
+
+        let own_reaction = &event.sender == room.own_user_id();
+        if own_reaction || !check_message_age(event, self.config.oldest_message_age()) {
+            return;
+        }
+
+        let relation = &event.content.relates_to;
+        let action = match reaction_action(&relation.emoji) {
+            Some(action) => action,
+            None => return,
+        };
+
+        let reply_id = relation.event_id.as_str();
+        let command = match self.db.get_command_reply(room_id, reply_id).await {
+            Ok(Some(command)) => command,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Could not look up command of reply: {}", e);
+                return;
+            }
+        };
+
+        let sender = event.sender.as_str();
+        let from_command_sender = sender == command.sender;
+
+        let event_id = match EventId::try_from(command.event_id.as_str()) {
+            Ok(event_id) => event_id,
+            Err(e) => {
+                error!("Invalid event ID of command: {}", e);
+                return;
+            }
+        };
+
+        let thread = command
+            .thread_id
+            .as_deref()
+            .and_then(|thread| EventId::try_from(thread).ok());
+
+        let roll_as = match action {
+            ReactionAction::Reroll if from_command_sender => sender,
+            ReactionAction::RollForSelf => sender,
+            ReactionAction::Redact if from_command_sender => {
+                let reason = Some("Removed by the sender of the command");
+                if let Err(e) = room.redact(&relation.event_id, reason, None).await {
+                    warn!("Could not redact reply: {}", e);
+                }
+
+                return;
+            }
+            _ => return,
+        };
+
+        let message = CommandMessage {
+            sender: roll_as,
+            body: &command.message,
+            event_id: &event_id,
+            thread: thread.as_ref(),
+        };
+
+        // The roll is seeded from the reaction, not the command message,
+        // so that rolling again in fair mode rolls new dice.
+        let rng = match self.event_rng(&event.event_id).await {
+            Ok(rng) => rng,
+            Err(e) => {
+                error!("Could not get fair mode session: {}", e);
+                return;
+            }
+        };
+
+        self.roll_again(room, message, rng).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message_event(content: serde_json::Value) -> SyncMessageEvent<MessageEventContent> {
+        serde_json::from_value(json!({
+            "type": "m.room.message",
+            "event_id": "$edit:example.com",
+            "sender": "@user:example.com",
+            "origin_server_ts": 1,
+            "content": content,
+        }))
+        .expect("invalid event")
+    }
+
+    #[test]
+    fn edited_event_id_test() {
+        let edit = message_event(json!({
+            "msgtype": "m.text",
+            "body": "* !r 1d20",
+            "m.new_content": { "msgtype": "m.text", "body": "!r 1d20" },
+            "m.relates_to": { "rel_type": "m.replace", "event_id": "$original:example.com" },
+        }));
+
+        let message = message_event(json!({ "msgtype": "m.text", "body": "!r 1d20" }));
+
+        assert_eq!(
+            edited_event_id(&edit).map(|id| id.to_string()),
+            Some("$original:example.com".to_string())
+        );
+        assert_eq!(edited_event_id(&message), None);
+    }
+
+    #[test]
+    fn reaction_action_test() {
+        assert_eq!(reaction_action("🔁"), Some(ReactionAction::Reroll));
+        assert_eq!(reaction_action("🎲"), Some(ReactionAction::RollForSelf));
+        assert_eq!(reaction_action("❌\u{fe0f}"), Some(ReactionAction::Redact));
+        assert_eq!(reaction_action("👍"), None);
+    }
+
+    #[test]
+    fn thread_root_test() {
+        let raw = |content: serde_json::Value| -> Raw<AnySyncRoomEvent> {
+            serde_json::from_value(json!({
+                "type": "m.room.message",
+                "event_id": "$message:example.com",
+                "sender": "@user:example.com",
+                "origin_server_ts": 1,
+                "content": content,
+            }))
+            .expect("invalid event")
+        };
+
+        let threaded = raw(json!({
+            "msgtype": "m.text",
+            "body": "!r 1d20",
+            "m.relates_to": {
+                "rel_type": "m.thread",
+                "event_id": "$root:example.com",
+                "is_falling_back": true,
+                "m.in_reply_to": { "event_id": "$latest:example.com" },
+            },
+        }));
+
+        let reply = raw(json!({
+            "msgtype": "m.text",
+            "body": "!r 1d20",
+            "m.relates_to": { "m.in_reply_to": { "event_id": "$other:example.com" } },
+        }));
+
+        assert_eq!(
+            thread_root(&threaded).map(|id| id.to_string()),
+            Some("$root:example.com".to_string())
+        );
+        assert_eq!(thread_root(&reply), None);
+        assert_eq!(thread_root(&raw(json!({ "body": "hi" }))), None);
+    }
 }
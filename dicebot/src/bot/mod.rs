@@ -1,23 +1,213 @@
+use crate::commands::parser::is_roll_in_system;
 use crate::commands::ExecutionResult;
 use crate::config::*;
 use crate::db::sqlite::Database;
-use crate::db::DbState;
+use crate::db::{DbState, Rooms};
 use crate::error::BotError;
-use crate::state::DiceBotState;
-use log::info;
-use matrix_sdk::{self, identifiers::EventId, room::Joined, Client, SyncSettings};
+use crate::logic;
+use crate::models::{CommandReply, GameSystem, MessageReply, ReplyType, RoomSetting};
+use crate::rng::DiceRng;
+use crate::sheets::{SheetError, SheetFormat, MAX_SHEET_FILE_SIZE};
+use crate::state::{DiceBotState, STARTED};
+use command_execution::{CommandSource, ResponseOptions};
+use log::{error, info, warn};
+use matrix_sdk::{
+    self, events::room::message::FileMessageEventContent, identifiers::EventId, room::Joined,
+    Client, SyncSettings,
+};
+use once_cell::sync::Lazy;
+use rate_limit::{RateLimiter, Throttle};
 use std::clone::Clone;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 
 mod command_execution;
 pub mod event_handlers;
+mod rate_limit;
 
 /// How many commands can be in one message. If the amount is higher
 /// than this, we reject execution.
 const MAX_COMMANDS_PER_MESSAGE: usize = 50;
 
+/// How many inline rolls can be in one message. If the amount is
+/// higher than this, we reject execution.
+const MAX_INLINE_ROLLS_PER_MESSAGE: usize = 10;
+
+/// Find the inline rolls in a message: every expression between [[
+/// and ]], like "I swing at the orc [[1d20+5]]".
+fn find_inline_rolls(msg_body: &str) -> impl Iterator<Item = &str> + '_ {
+    msg_body
+        .split("[[")
+        .skip(1)
+        .filter_map(|part| part.split_once("]]"))
+        .map(|(expression, _)| expression.trim())
+        .filter(|expression| !expression.is_empty())
+}
+
+/// A message the bot responds to.
+#[derive(Clone, Copy)]
+pub(super) struct CommandMessage<'a> {
+    pub sender: &'a str,
+    pub body: &'a str,
+
+    /// The message event, which the reply answers.
+    pub event_id: &'a EventId,
+
+    /// The thread the message was sent in, if any.
+    pub thread: Option<&'a EventId>,
+}
+
+/// How commands are recognized in a room: lines starting with the
+/// room's command prefix, or addressed to the bot by name.
+pub struct CommandTrigger {
+    prefix: String,
+
+    /// Names the bot can be addressed by, like "DiceBot: roll 1d20".
+    names: Vec<String>,
+}
+
+impl CommandTrigger {
+    pub fn new(prefix: String, names: Vec<String>) -> CommandTrigger {
+        CommandTrigger { prefix, names }
+    }
+
+    /// The command on a line of a message, if there is one. Commands
+    /// are returned with the "!" prefix they are parsed with.
+    fn command(&self, line: &str) -> Option<String> {
+        if let Some(command) = line.strip_prefix(&self.prefix) {
+            return Some(format!("!{}", command));
+        }
+
+        let command = self
+            .names
+            .iter()
+            .find_map(|name| strip_mention(line, name))?;
+
+        let command = command.strip_prefix(&self.prefix).unwrap_or(command);
+
+        match command.is_empty() {
+            true => None,
+            false => Some(format!("!{}", command)),
+        }
+    }
+}
+
+/// Remove a mention like "DiceBot:" or "DiceBot," from the start of a
+/// line. Names are compared without regard to case.
+fn strip_mention<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let mentioned = line.get(..name.len())?;
+    let rest = line[name.len()..]
+        .strip_prefix(':')
+        .or_else(|| line[name.len()..].strip_prefix(','))?;
+
+    match mentioned.to_lowercase() == name.to_lowercase() {
+        true => Some(rest.trim_start()),
+        false => None,
+    }
+}
+
+/// Commands that take a code block on the line after them as input.
+const BODY_COMMANDS: [&str; 1] = ["!import"];
+
+/// Whether a command takes a code block as input.
+fn takes_body(command: &str) -> bool {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    BODY_COMMANDS
+        .iter()
+        .any(|body_command| name.eq_ignore_ascii_case(body_command))
+}
+
+/// Find the commands in a message: every line starting with the
+/// command prefix or a mention of the bot. A code block starting on
+/// the line after a command that takes a body is part of that
+/// command's input, which is how character sheets are sent.
+fn find_commands<'a>(
+    msg_body: &'a str,
+    trigger: &'a CommandTrigger,
+) -> impl Iterator<Item = String> + 'a {
+    let mut lines = msg_body.lines().peekable();
+
+    std::iter::from_fn(move || {
+        let mut command = lines.find_map(|line| trigger.command(line))?;
+        let code_block = |line: &&str| line.trim_start().starts_with("```");
+
+        if let Some(opening) = lines.next_if(|line| takes_body(&command) && code_block(line)) {
+            command.push('\n');
+            command.push_str(opening);
+
+            for line in &mut lines {
+                command.push('\n');
+                command.push_str(line);
+
+                if line.trim() == "```" {
+                    break;
+                }
+            }
+        }
+
+        Some(command)
+    })
+}
+
+/// Find the commands the bot executes for a message. Messages with
+/// too many commands are refused.
+pub fn commands_in_message(
+    msg_body: &str,
+    trigger: &CommandTrigger,
+) -> Result<Vec<String>, BotError> {
+    let commands: Vec<String> = find_commands(msg_body, trigger)
+        .take(MAX_COMMANDS_PER_MESSAGE + 1)
+        .collect();
+
+    //Up to 50 commands allowed, otherwise we send back an error.
+    match commands.len() < MAX_COMMANDS_PER_MESSAGE {
+        true => Ok(commands),
+        false => Err(BotError::MessageTooLarge),
+    }
+}
+
+/// Find the inline rolls of a message, which the bot rolls if inline
+/// rolls are on in the room. Messages with too many are refused.
+pub fn inline_rolls_in_message(msg_body: &str) -> Result<Vec<&str>, BotError> {
+    let expressions: Vec<&str> = find_inline_rolls(msg_body)
+        .take(MAX_INLINE_ROLLS_PER_MESSAGE + 1)
+        .collect();
+
+    match expressions.len() > MAX_INLINE_ROLLS_PER_MESSAGE {
+        true => Err(BotError::MessageTooLarge),
+        false => Ok(expressions),
+    }
+}
+
+/// The text of a downloaded character sheet. The size given with the
+/// upload may be missing or wrong, so the downloaded file is checked
+/// too.
+fn sheet_document(contents: Vec<u8>, format: SheetFormat) -> Result<String, BotError> {
+    if contents.len() as u64 > MAX_SHEET_FILE_SIZE {
+        return Err(SheetError::FileTooLarge.into());
+    }
+
+    String::from_utf8(contents).map_err(|_| {
+        SheetError::InvalidDocument(format, "file is not UTF-8 text".to_string()).into()
+    })
+}
+
+/// The commands of the results that rolled dice.
+fn rolled_commands(results: &[(String, ExecutionResult)]) -> impl Iterator<Item = &str> {
+    results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(execution) if execution.roll_summary().is_some()))
+        .map(|(command, _)| command.as_str())
+}
+
+/// The !roll command that an inline roll is rolled with.
+pub fn inline_roll_command(expression: &str) -> String {
+    format!("!roll {}", expression)
+}
+
 /// The DiceBot struct represents an active dice bot. The bot is not
 /// connected to Matrix until its run() function is called.
+#[derive(Clone)]
 pub struct DiceBot {
     /// A reference to the configuration read in on application start.
     config: Arc<Config>,
@@ -30,6 +220,9 @@ pub struct DiceBot {
 
     /// Active database layer
     db: Database,
+
+    /// Limits how many commands users and rooms can execute.
+    rate_limiter: Arc<Mutex<RateLimiter>>,
 }
 
 impl DiceBot {
@@ -48,6 +241,10 @@ impl DiceBot {
             config: config.clone(),
             state: state.clone(),
             db: db.clone(),
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::new(
+                config.user_rate_limit(),
+                config.room_rate_limit(),
+            ))),
         })
     }
 
@@ -85,64 +282,634 @@ impl DiceBot {
     pub async fn run(self) -> Result<(), BotError> {
         let client = self.client.clone();
         self.login(&client).await?;
+        Lazy::force(&STARTED);
+
+        match logic::restart_fair_mode(&self.db, self.config.fair_rolls()).await? {
+            Some(session) => info!(
+                "Fair rolls are on, session {} commits to {}",
+                session.id, session.commitment
+            ),
+            None => info!("Fair rolls are off"),
+        }
 
-        client.set_event_handler(Box::new(self)).await;
+        client.set_event_handler(Box::new(self.clone())).await;
         info!("Listening for commands");
 
-        // TODO replace with sync_with_callback for cleaner shutdown
-        // process.
-        client.sync(SyncSettings::default()).await;
+        client
+            .sync_with_callback(SyncSettings::default(), |response| {
+                self.handle_sync_response(response)
+            })
+            .await;
+
         Ok(())
     }
 
+    /// Figure out how commands are recognized in a room: the room's
+    /// own prefix or the configured one, and the names of the bot.
+    async fn command_trigger(&self, room: &Joined) -> CommandTrigger {
+        let room_prefix = logic::room_command_prefix(&self.db, room.room_id().as_str())
+            .await
+            .unwrap_or_else(|e| {
+                error!("Could not get command prefix of room: {}", e);
+                None
+            });
+
+        let own_user_id = room.own_user_id();
+        let mut names = vec![own_user_id.to_string(), own_user_id.localpart().to_owned()];
+
+        if let Ok(Some(member)) = room.get_member(own_user_id).await {
+            names.extend(member.display_name().map(str::to_owned));
+        }
+
+        // Longest names first, so that a name is not cut short by
+        // another name it starts with.
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+        CommandTrigger {
+            prefix: room_prefix.unwrap_or_else(|| self.config.command_prefix().to_owned()),
+            names,
+        }
+    }
+
+    /// The thread that commands sent in a thread are scoped to, if the
+    /// room keeps the state of each thread apart.
+    async fn thread_scope<'a>(
+        &self,
+        room: &Joined,
+        thread: Option<&'a EventId>,
+    ) -> Option<&'a str> {
+        let thread = thread?;
+        let room_id = room.room_id().as_str();
+
+        match logic::room_setting_enabled(&self.db, room_id, RoomSetting::ThreadScope).await {
+            Ok(true) => Some(thread.as_str()),
+            Ok(false) => None,
+            Err(e) => {
+                error!("Could not check if threads are kept apart: {}", e);
+                None
+            }
+        }
+    }
+
+    /// How to respond to a command message in a room: in the given
+    /// thread, editing the earlier reply if there is one, and with
+    /// notices or ordinary messages as the room wants.
+    async fn response_options<'a>(
+        &self,
+        room: &Joined,
+        thread: Option<&'a EventId>,
+        previous_reply: Option<EventId>,
+    ) -> ResponseOptions<'a> {
+        let room_id = room.room_id().as_str();
+        let reply_type = logic::room_reply_type(&self.db, room_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Could not get reply type of room: {}", e);
+                ReplyType::default()
+            });
+
+        ResponseOptions {
+            thread,
+            previous_reply,
+            reply_type,
+        }
+    }
+
+    /// Execute commands sent by a user in a room. Bot admins are
+    /// allowed to execute every command, and are not rate limited.
+    /// Throttled commands are not executed; the user gets one notice
+    /// about it, and no reply to later throttled attempts.
+    async fn execute(
+        &self,
+        commands: Vec<&str>,
+        room: &Joined,
+        sender: &str,
+        thread_id: Option<&str>,
+        rng: DiceRng,
+    ) -> Vec<(String, ExecutionResult)> {
+        let admin = self.config.is_admin(sender);
+        let room_id = room.room_id().as_str();
+
+        let throttle = match admin {
+            true => Throttle::Allowed,
+            false => self.rate_limiter.lock().unwrap().check(
+                sender,
+                room_id,
+                commands.len(),
+                Instant::now(),
+            ),
+        };
+
+        if let Throttle::Throttled { notify } = throttle {
+            warn!(
+                "Throttled {} commands from {} in {}",
+                commands.len(),
+                sender,
+                room_id
+            );
+
+            return match notify {
+                true => vec![("".to_owned(), Err(BotError::RateLimited))],
+                false => vec![],
+            };
+        }
+
+        let source = CommandSource {
+            room,
+            sender,
+            admin,
+            thread_id,
+            rng,
+        };
+
+        command_execution::execute(commands, &self.db, &self.client, &source).await
+    }
+
     async fn execute_commands(
         &self,
         room: &Joined,
         sender: &str,
         msg_body: &str,
+        thread_id: Option<&str>,
+        rng: DiceRng,
     ) -> Vec<(String, ExecutionResult)> {
-        let commands: Vec<&str> = msg_body
-            .lines()
-            .filter(|line| line.starts_with("!"))
-            .take(MAX_COMMANDS_PER_MESSAGE + 1)
+        let trigger = self.command_trigger(room).await;
+        match commands_in_message(msg_body, &trigger) {
+            Ok(commands) => {
+                let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+                self.execute(commands, room, sender, thread_id, rng).await
+            }
+            Err(e) => vec![("".to_owned(), Err(e))],
+        }
+    }
+
+    /// Roll the inline expressions of an ordinary message, if inline
+    /// rolls are turned on in the room. Each expression is rolled as
+    /// if it had been sent with !roll.
+    async fn execute_inline_rolls(
+        &self,
+        room: &Joined,
+        sender: &str,
+        msg_body: &str,
+        thread_id: Option<&str>,
+        rng: DiceRng,
+    ) -> Vec<(String, ExecutionResult)> {
+        let expressions = match inline_rolls_in_message(msg_body) {
+            Ok(expressions) if expressions.is_empty() => return vec![],
+            expressions => expressions,
+        };
+
+        let room_id = room.room_id().as_str();
+        match logic::room_setting_enabled(&self.db, room_id, RoomSetting::InlineRolls).await {
+            Ok(true) => (),
+            Ok(false) => return vec![],
+            Err(e) => {
+                error!("Could not check if inline rolls are on: {}", e);
+                return vec![];
+            }
+        }
+
+        let expressions = match expressions {
+            Ok(expressions) => expressions,
+            Err(e) => return vec![("".to_owned(), Err(e))],
+        };
+
+        let commands: Vec<String> = expressions
+            .iter()
+            .map(|expression| inline_roll_command(expression))
             .collect();
 
-        //Up to 50 commands allowed, otherwise we send back an error.
-        let results: Vec<(String, ExecutionResult)> = if commands.len() < MAX_COMMANDS_PER_MESSAGE {
-            command_execution::execute(commands, &self.db, &self.client, room, sender).await
-        } else {
-            vec![("".to_owned(), Err(BotError::MessageTooLarge))]
+        let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+        let results = self.execute(commands, room, sender, thread_id, rng).await;
+
+        expressions
+            .into_iter()
+            .map(str::to_owned)
+            .zip(results.into_iter().map(|(_, result)| result))
+            .collect()
+    }
+
+    /// Import an uploaded JSON or TOML file as a character sheet, as
+    /// if it had been sent with !import. Other files are ignored.
+    async fn import_attachment(
+        &self,
+        room: &Joined,
+        sender: &str,
+        file: &FileMessageEventContent,
+        thread_id: Option<&str>,
+        rng: DiceRng,
+    ) -> Vec<(String, ExecutionResult)> {
+        let file_name = file.filename.as_deref().unwrap_or(&file.body);
+        let format = match file_name.rsplit_once('.') {
+            Some((_, extension)) => SheetFormat::from_name(extension),
+            None => None,
         };
 
-        results
+        let format = match format {
+            Some(format) => format,
+            None => return vec![],
+        };
+
+        let room_id = room.room_id().as_str();
+        match logic::room_setting_enabled(&self.db, room_id, RoomSetting::SheetUploads).await {
+            Ok(true) => (),
+            Ok(false) => return vec![],
+            Err(e) => {
+                error!("Could not check if sheet uploads are on: {}", e);
+                return vec![];
+            }
+        }
+
+        let import = match self.download_sheet(file, format).await {
+            Ok(document) => format!("!import\n```{}\n{}\n```", format, document),
+            Err(e) => return vec![(file_name.to_owned(), Err(e))],
+        };
+
+        let commands = vec![import.as_str()];
+        self.execute(commands, room, sender, thread_id, rng).await
     }
 
-    pub async fn handle_results(
+    async fn download_sheet(
+        &self,
+        file: &FileMessageEventContent,
+        format: SheetFormat,
+    ) -> Result<String, BotError> {
+        let too_large = |size: u64| size > MAX_SHEET_FILE_SIZE;
+        let size = file.info.as_ref().and_then(|info| info.size);
+
+        if size.map(u64::from).map_or(false, too_large) {
+            return Err(SheetError::FileTooLarge.into());
+        }
+
+        let contents = self.client.get_file(file.clone(), false).await?;
+        sheet_document(contents.unwrap_or_default(), format)
+    }
+
+    /// The reply sent earlier to a command message that was edited.
+    pub(super) async fn previous_reply(
         &self,
         room: &Joined,
-        sender_username: &str,
-        event_id: EventId,
-        results: Vec<(String, ExecutionResult)>,
+        event_id: &EventId,
+    ) -> Option<MessageReply> {
+        let room_id = room.room_id().as_str();
+        self.db
+            .get_reply(room_id, event_id.as_str())
+            .await
+            .unwrap_or_else(|e| {
+                error!("Could not look up previous reply: {}", e);
+                None
+            })
+    }
+
+    /// Remember the reply to a command message, so that it can be
+    /// edited if the sender edits the command message.
+    pub(super) async fn remember_reply(
+        &self,
+        room: &Joined,
+        event_id: &EventId,
+        sender: &str,
+        thread: Option<&EventId>,
+        reply: Option<EventId>,
     ) {
-        if results.len() >= 1 {
-            if results.len() == 1 {
-                command_execution::handle_single_result(
-                    &self.client,
-                    &results[0].1,
-                    sender_username,
-                    &room,
-                    event_id,
-                )
+        if let Some(reply) = reply {
+            let room_id = room.room_id().as_str();
+            let reply = MessageReply {
+                reply_event_id: reply.to_string(),
+                sender: Some(sender.to_owned()),
+                thread_id: thread.map(EventId::to_string),
+            };
+
+            let result = self
+                .db
+                .record_reply(room_id, event_id.as_str(), &reply)
                 .await;
-            } else if results.len() > 1 {
-                command_execution::handle_multiple_results(
-                    &self.client,
-                    &results,
-                    sender_username,
-                    &room,
-                )
+
+            if let Err(e) = result {
+                error!("Could not record reply: {}", e);
+            }
+        }
+    }
+
+    /// Remember the command message a roll reply answered, so that
+    /// reacting to the reply can roll again.
+    async fn remember_command_reply(
+        &self,
+        room: &Joined,
+        reply: Option<&EventId>,
+        command: CommandReply,
+    ) {
+        if let Some(reply) = reply {
+            let room_id = room.room_id().as_str();
+            let result = self
+                .db
+                .record_command_reply(room_id, reply.as_str(), &command)
                 .await;
+
+            if let Err(e) = result {
+                error!("Could not record command of reply: {}", e);
             }
         }
     }
+
+    /// The generator the dice are rolled with for an event: seeded
+    /// from the event in fair mode, and random otherwise. Fails if
+    /// the fair mode session cannot be looked up, rather than rolling
+    /// dice that cannot be verified.
+    pub(super) async fn event_rng(&self, event_id: &EventId) -> Result<DiceRng, BotError> {
+        logic::event_rng(&self.db, event_id.as_str()).await
+    }
+
+    /// Execute the commands of a message, or its inline rolls if it
+    /// has no commands, and respond to them in the thread the message
+    /// was sent in. The earlier reply is edited instead, if one is
+    /// given. Replies to rolls are remembered, so that reacting to
+    /// them can roll again. Returns the ID of the reply.
+    pub(super) async fn respond_to_message(
+        &self,
+        room: &Joined,
+        message: CommandMessage<'_>,
+        previous_reply: Option<EventId>,
+        rng: DiceRng,
+    ) -> Option<EventId> {
+        let CommandMessage {
+            sender,
+            body: msg_body,
+            event_id,
+            thread,
+        } = message;
+
+        let thread_id = self.thread_scope(room, thread).await;
+        let results = self
+            .execute_commands(room, sender, msg_body, thread_id, rng.clone())
+            .await;
+
+        // A message without commands may still have inline rolls.
+        let (reply, rolled) = if results.is_empty() {
+            let results = self
+                .execute_inline_rolls(room, sender, msg_body, thread_id, rng)
+                .await;
+
+            let rolled = rolled_commands(&results).map(inline_roll_command).collect();
+            let reply = self
+                .handle_inline_results(room, sender, event_id, thread, previous_reply, results)
+                .await;
+
+            (reply, rolled)
+        } else {
+            let rolled = rolled_commands(&results).map(str::to_owned).collect();
+            let reply = self
+                .handle_results(room, sender, event_id, thread, previous_reply, results)
+                .await;
+
+            (reply, rolled)
+        };
+
+        let message = CommandMessage {
+            sender,
+            body: msg_body,
+            event_id,
+            thread,
+        };
+
+        self.remember_rolls(room, reply.as_ref(), message, rolled)
+            .await;
+
+        reply
+    }
+
+    /// Roll the commands of a roll reply again for a sender, and reply
+    /// in the thread of the commands. Anyone may ask for a roll of
+    /// their own by reacting to a reply, so nothing is executed unless
+    /// every command does nothing but roll dice. Returns the ID of the
+    /// reply.
+    pub(super) async fn roll_again(
+        &self,
+        room: &Joined,
+        message: CommandMessage<'_>,
+        rng: DiceRng,
+    ) -> Option<EventId> {
+        let CommandMessage {
+            sender,
+            body,
+            event_id,
+            thread,
+        } = message;
+
+        let commands: Vec<String> = body.lines().map(str::to_owned).collect();
+        let rolls = self.roll_commands(room, commands.clone()).await;
+
+        if rolls.is_empty() || rolls != commands {
+            warn!("Not rolling again commands that are not rolls: {:?}", body);
+            return None;
+        }
+
+        let thread_id = self.thread_scope(room, thread).await;
+        let commands: Vec<&str> = rolls.iter().map(String::as_str).collect();
+        let results = self.execute(commands, room, sender, thread_id, rng).await;
+
+        let rolled = rolled_commands(&results).map(str::to_owned).collect();
+        let reply = self
+            .handle_results(room, sender, event_id, thread, None, results)
+            .await;
+
+        self.remember_rolls(room, reply.as_ref(), message, rolled)
+            .await;
+
+        reply
+    }
+
+    /// The commands that do nothing but roll dice in the game system
+    /// of a room, out of the given commands.
+    async fn roll_commands(&self, room: &Joined, commands: Vec<String>) -> Vec<String> {
+        let room_id = room.room_id().as_str();
+        let system = logic::room_game_system(&self.db, room_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Could not get game system of room: {}", e);
+                GameSystem::default()
+            });
+
+        commands
+            .into_iter()
+            .filter(|command| is_roll_in_system(command, system))
+            .collect()
+    }
+
+    /// Remember the roll commands that a reply answered, so that
+    /// reacting to the reply can roll them again. Commands that did
+    /// more than roll dice are left out.
+    async fn remember_rolls(
+        &self,
+        room: &Joined,
+        reply: Option<&EventId>,
+        message: CommandMessage<'_>,
+        rolled: Vec<String>,
+    ) {
+        let rolls = self.roll_commands(room, rolled).await;
+        if rolls.is_empty() {
+            return;
+        }
+
+        let command = CommandReply {
+            event_id: message.event_id.to_string(),
+            sender: message.sender.to_owned(),
+            message: rolls.join("\n"),
+            thread_id: message.thread.map(EventId::to_string),
+        };
+
+        self.remember_command_reply(room, reply, command).await;
+    }
+
+    /// Reply with the results of every inline roll in one message, in
+    /// the thread the message was sent in, or edit the earlier reply
+    /// if one is given. Returns the ID of the reply.
+    pub async fn handle_inline_results(
+        &self,
+        room: &Joined,
+        sender_username: &str,
+        event_id: &EventId,
+        thread: Option<&EventId>,
+        previous_reply: Option<EventId>,
+        results: Vec<(String, ExecutionResult)>,
+    ) -> Option<EventId> {
+        if results.is_empty() {
+            return None;
+        }
+
+        command_execution::handle_inline_results(
+            &self.client,
+            &results,
+            sender_username,
+            room,
+            event_id.clone(),
+            self.response_options(room, thread, previous_reply).await,
+        )
+        .await
+    }
+
+    /// Respond to the commands of a message, in the thread the message
+    /// was sent in, or edit the earlier reply if one is given. Returns
+    /// the ID of the reply.
+    pub async fn handle_results(
+        &self,
+        room: &Joined,
+        sender_username: &str,
+        event_id: &EventId,
+        thread: Option<&EventId>,
+        previous_reply: Option<EventId>,
+        results: Vec<(String, ExecutionResult)>,
+    ) -> Option<EventId> {
+        if results.is_empty() {
+            return None;
+        }
+
+        let options = self.response_options(room, thread, previous_reply).await;
+        if results.len() == 1 {
+            command_execution::handle_single_result(
+                &self.client,
+                &results[0].1,
+                sender_username,
+                room,
+                event_id.clone(),
+                options,
+            )
+            .await
+        } else {
+            command_execution::handle_multiple_results(
+                &self.client,
+                &results,
+                sender_username,
+                room,
+                options,
+            )
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(prefix: &str) -> CommandTrigger {
+        CommandTrigger {
+            prefix: prefix.to_owned(),
+            names: vec!["@dicebot:example.com".to_owned(), "DiceBot".to_owned()],
+        }
+    }
+
+    #[test]
+    fn find_commands_on_each_line_test() {
+        let trigger = trigger("!");
+        let commands: Vec<String> = find_commands("!r 1d4\nchatter\n!r 1d6", &trigger).collect();
+        assert_eq!(commands, vec!["!r 1d4", "!r 1d6"]);
+    }
+
+    #[test]
+    fn find_commands_with_room_prefix_test() {
+        let trigger = trigger(".");
+        let commands: Vec<String> = find_commands("!r 1d4\n.r 1d6", &trigger).collect();
+        assert_eq!(commands, vec!["!r 1d6"]);
+    }
+
+    #[test]
+    fn find_commands_addressed_to_bot_test() {
+        let trigger = trigger(".");
+        let message = "dicebot: r 1d4\n@dicebot:example.com, .r 1d6\nDiceBot:\nDiceBotty: r 1d8";
+        let commands: Vec<String> = find_commands(message, &trigger).collect();
+        assert_eq!(commands, vec!["!r 1d4", "!r 1d6"]);
+    }
+
+    #[test]
+    fn find_inline_rolls_test() {
+        let message = "I swing at the orc [[1d20+5]] for [[ 1d8+3 ]] damage [[]] [[2d6";
+        let rolls: Vec<&str> = find_inline_rolls(message).collect();
+        assert_eq!(rolls, vec!["1d20+5", "1d8+3"]);
+    }
+
+    #[test]
+    fn find_commands_includes_code_block_test() {
+        let trigger = trigger("!");
+        let message = "!import\n```toml\n!strength = 3\n```\n!export";
+        let commands: Vec<String> = find_commands(message, &trigger).collect();
+        assert_eq!(
+            commands,
+            vec!["!import\n```toml\n!strength = 3\n```", "!export"]
+        );
+    }
+
+    #[test]
+    fn find_commands_leaves_code_block_of_other_commands_test() {
+        let trigger = trigger("!");
+        let message = "!r 1d4\n```\n!r 1d6\n```";
+        let commands: Vec<String> = find_commands(message, &trigger).collect();
+        assert_eq!(commands, vec!["!r 1d4", "!r 1d6"]);
+    }
+
+    #[test]
+    fn sheet_document_checks_downloaded_size_test() {
+        let sheet = sheet_document(b"strength = 3".to_vec(), SheetFormat::Toml);
+        assert_eq!(sheet.unwrap(), "strength = 3");
+
+        let too_large = vec![b' '; MAX_SHEET_FILE_SIZE as usize + 1];
+        let result = sheet_document(too_large, SheetFormat::Toml);
+        assert!(matches!(
+            result,
+            Err(BotError::SheetError(SheetError::FileTooLarge))
+        ));
+    }
+
+    #[test]
+    fn commands_in_message_refuses_too_many_test() {
+        let trigger = trigger("!");
+        let message = "!r 1d4\n".repeat(MAX_COMMANDS_PER_MESSAGE);
+        let commands = commands_in_message(&message[7..], &trigger);
+        assert_eq!(
+            commands.map(|c| c.len()).ok(),
+            Some(MAX_COMMANDS_PER_MESSAGE - 1)
+        );
+
+        let commands = commands_in_message(&message, &trigger);
+        assert!(matches!(commands, Err(BotError::MessageTooLarge)));
+    }
 }
@@ -0,0 +1,223 @@
+use crate::config::RateLimit;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often the buckets that refilled are dropped. A full bucket is
+/// the same as a new one, so only the buckets in use are kept.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A bucket of tokens that refills at a steady rate. Each command
+/// executed takes one token.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+
+    /// Whether the user was told they are being throttled since the
+    /// bucket last allowed commands.
+    notified: bool,
+}
+
+impl TokenBucket {
+    /// Add the tokens that came back since the last update.
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let refill = elapsed * f64::from(limit.per_minute) / 60.0;
+        self.tokens = (self.tokens + refill).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+
+    fn is_full(&self, limit: &RateLimit) -> bool {
+        self.tokens >= f64::from(limit.burst)
+    }
+}
+
+/// Whether commands may be executed right now.
+#[derive(Debug, PartialEq)]
+pub(super) enum Throttle {
+    Allowed,
+
+    /// The commands are throttled. Notify is true only for the first
+    /// throttled attempt, so that one notice is sent, not one per
+    /// attempt.
+    Throttled {
+        notify: bool,
+    },
+}
+
+/// Token bucket rate limiter, with one bucket per key.
+#[derive(Debug)]
+struct Limiter {
+    limit: RateLimit,
+    buckets: HashMap<String, TokenBucket>,
+    swept: Instant,
+}
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Limiter {
+        Limiter {
+            limit,
+            buckets: HashMap::new(),
+            swept: Instant::now(),
+        }
+    }
+
+    /// Drop the buckets that refilled, at most once per sweep interval.
+    fn sweep(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.swept) < SWEEP_INTERVAL {
+            return;
+        }
+
+        let limit = &self.limit;
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(limit, now);
+            !bucket.is_full(limit)
+        });
+
+        self.swept = now;
+    }
+
+    /// The bucket for a key, refilled up to the current time.
+    fn bucket(&mut self, key: &str, now: Instant) -> &mut TokenBucket {
+        let limit = &self.limit;
+        let bucket = self
+            .buckets
+            .entry(key.to_owned())
+            .or_insert_with(|| TokenBucket {
+                tokens: f64::from(limit.burst),
+                updated: now,
+                notified: false,
+            });
+
+        bucket.refill(limit, now);
+        bucket
+    }
+
+    fn has_tokens(&mut self, key: &str, cost: usize, now: Instant) -> bool {
+        self.bucket(key, now).tokens >= cost as f64
+    }
+}
+
+/// Limits how many commands each user, and each room, can execute.
+/// Commands are only executed if both the user and the room have
+/// tokens left for all of them.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+    users: Limiter,
+    rooms: Limiter,
+}
+
+impl RateLimiter {
+    pub fn new(user_limit: RateLimit, room_limit: RateLimit) -> RateLimiter {
+        RateLimiter {
+            users: Limiter::new(user_limit),
+            rooms: Limiter::new(room_limit),
+        }
+    }
+
+    /// Take tokens for a number of commands sent by a user in a room,
+    /// if both have enough of them.
+    pub fn check(&mut self, user: &str, room: &str, cost: usize, now: Instant) -> Throttle {
+        self.users.sweep(now);
+        self.rooms.sweep(now);
+
+        let user_allowed = self.users.has_tokens(user, cost, now);
+        let room_allowed = self.rooms.has_tokens(room, cost, now);
+
+        // The user is told once, whichever limit they ran into.
+        let user_bucket = self.users.bucket(user, now);
+
+        if user_allowed && room_allowed {
+            user_bucket.tokens -= cost as f64;
+            user_bucket.notified = false;
+            self.rooms.bucket(room, now).tokens -= cost as f64;
+            Throttle::Allowed
+        } else {
+            let notify = !user_bucket.notified;
+            user_bucket.notified = true;
+            Throttle::Throttled { notify }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        let user = RateLimit {
+            burst: 3,
+            per_minute: 60,
+        };
+
+        let room = RateLimit {
+            burst: 5,
+            per_minute: 60,
+        };
+
+        RateLimiter::new(user, room)
+    }
+
+    #[test]
+    fn throttles_user_after_burst_test() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check("user", "room", 3, now), Throttle::Allowed);
+        assert_eq!(
+            limiter.check("user", "room", 1, now),
+            Throttle::Throttled { notify: true }
+        );
+        assert_eq!(
+            limiter.check("user", "room", 1, now),
+            Throttle::Throttled { notify: false }
+        );
+
+        // One token per second comes back.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check("user", "room", 1, later), Throttle::Allowed);
+    }
+
+    #[test]
+    fn throttles_room_for_every_user_test() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check("user1", "room", 3, now), Throttle::Allowed);
+        assert_eq!(limiter.check("user2", "room", 2, now), Throttle::Allowed);
+        assert_eq!(
+            limiter.check("user3", "room", 1, now),
+            Throttle::Throttled { notify: true }
+        );
+        assert_eq!(limiter.check("user3", "other", 1, now), Throttle::Allowed);
+    }
+
+    #[test]
+    fn throttled_commands_take_no_tokens_test() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.check("user", "room", 4, now),
+            Throttle::Throttled { notify: true }
+        );
+        assert_eq!(limiter.check("user", "room", 3, now), Throttle::Allowed);
+    }
+
+    #[test]
+    fn refilled_buckets_are_dropped_test() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check("user1", "room1", 3, now), Throttle::Allowed);
+        assert_eq!(limiter.users.buckets.len(), 1);
+        assert_eq!(limiter.rooms.buckets.len(), 1);
+
+        // By the next sweep, the buckets of the first user and room
+        // are full again.
+        let later = now + SWEEP_INTERVAL;
+        assert_eq!(limiter.check("user2", "room2", 3, later), Throttle::Allowed);
+        assert_eq!(limiter.users.buckets.keys().collect::<Vec<_>>(), ["user2"]);
+        assert_eq!(limiter.rooms.buckets.keys().collect::<Vec<_>>(), ["room2"]);
+    }
+}
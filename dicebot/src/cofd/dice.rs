@@ -1,7 +1,6 @@
 use crate::context::Context;
 use crate::error::{BotError, DiceRollingError};
 use crate::parser::dice::{Amount, Element, Operator};
-use itertools::Itertools;
 use std::convert::TryFrom;
 use std::fmt;
 
@@ -124,32 +123,36 @@ pub struct RolledDicePool {
     pub(crate) num_dice: i32,
     pub(crate) roll: DicePoolRoll,
     pub(crate) modifiers: DicePoolModifiers,
+
+    /// The dice rolled for each die of the pool: the die itself,
+    /// followed by the dice it exploded into or the re-roll of a rote
+    /// action.
+    pub(crate) chains: Vec<Vec<i32>>,
 }
 
 impl RolledDicePool {
-    fn from(pool: &DicePool, num_dice: i32, rolls: Vec<i32>) -> RolledDicePool {
+    pub(super) fn from(pool: &DicePool, num_dice: i32, rolls: Vec<i32>) -> RolledDicePool {
+        let chains = rolls.iter().map(|&roll| vec![roll]).collect();
+        RolledDicePool::from_chains(pool, num_dice, chains)
+    }
+
+    pub(crate) fn from_chains(
+        pool: &DicePool,
+        num_dice: i32,
+        chains: Vec<Vec<i32>>,
+    ) -> RolledDicePool {
         RolledDicePool {
             modifiers: pool.modifiers,
             num_dice: num_dice,
             roll: DicePoolRoll {
-                rolls: rolls,
+                rolls: chains.iter().flatten().copied().collect(),
                 modifiers: pool.modifiers,
             },
+            chains,
         }
     }
 }
 
-impl fmt::Display for RolledDicePool {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let dice_plural = if self.num_dice == 1 { "die" } else { "dice" };
-        write!(
-            f,
-            "{} {} ({}, exceptional on {} successes)",
-            self.num_dice, dice_plural, self.modifiers.quality, self.modifiers.exceptional_on
-        )
-    }
-}
-
 ///Store all rolls of the dice pool dice into one struct.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DicePoolRoll {
@@ -158,22 +161,9 @@ pub struct DicePoolRoll {
 }
 
 /// Amount of dice to display before cutting off and showing "and X
-/// more", so we don't spam the room with huge messages.
-const MAX_DISPLAYED_ROLLS: usize = 15;
-
-fn fmt_rolls(pool: &DicePoolRoll) -> String {
-    let rolls = pool.rolls();
-    if rolls.len() > MAX_DISPLAYED_ROLLS {
-        let shown_amount = rolls.into_iter().take(MAX_DISPLAYED_ROLLS).join(", ");
-        format!(
-            "{}, and {} more",
-            shown_amount,
-            rolls.len() - MAX_DISPLAYED_ROLLS
-        )
-    } else {
-        rolls.into_iter().join(", ")
-    }
-}
+/// more", so we don't spam the room with huge messages. Rooms can
+/// change this with !config.
+pub const DEFAULT_MAX_DISPLAYED_ROLLS: usize = 15;
 
 fn fmt_for_failure(pool: &DicePoolRoll) -> String {
     match pool.modifiers.quality {
@@ -203,36 +193,27 @@ impl DicePoolRoll {
     pub fn is_exceptional(&self) -> bool {
         self.successes() >= self.modifiers.exceptional_on
     }
-}
-
-/// Attach a Context to a dice pool. Needed for database access.
-pub struct DicePoolWithContext<'a>(pub &'a DicePool, pub &'a Context<'a>);
 
-impl fmt::Display for DicePoolRoll {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let successes = self.successes();
-        if successes > 0 {
-            let success_msg = if self.is_exceptional() {
-                format!("{} successes (exceptional!)", successes)
-            } else {
-                format!("{} successes", successes)
-            };
-
-            write!(f, "{} ({})", success_msg, fmt_rolls(&self))?;
-        } else {
-            write!(f, "{} ({})", fmt_for_failure(&self), fmt_rolls(&self))?;
+    /// Short description of the outcome of the roll, without the
+    /// dice.
+    pub fn outcome(&self) -> String {
+        match self.successes() {
+            0 => fmt_for_failure(self),
+            _ if self.is_exceptional() => String::from("exceptional success!"),
+            _ => String::from("success!"),
         }
-
-        Ok(())
     }
 }
 
-trait DieRoller {
+/// Attach a Context to a dice pool. Needed for database access.
+pub struct DicePoolWithContext<'a>(pub &'a DicePool, pub &'a Context<'a>);
+
+pub(super) trait DieRoller {
     fn roll_number(&mut self, sides: i32) -> i32;
 }
 
 ///A version of DieRoller that uses a rand::Rng to roll numbers.
-struct RngDieRoller<R: rand::Rng>(R);
+pub(super) struct RngDieRoller<R: rand::Rng>(pub(super) R);
 
 impl<R: rand::Rng> DieRoller for RngDieRoller<R> {
     fn roll_number(&mut self, sides: i32) -> i32 {
@@ -277,7 +258,7 @@ fn roll_rote_die<R: DieRoller>(roller: &mut R, sides: i32, success_on: i32) -> V
 ///there are multiple 10s). Nine- and eight-again will explode similarly if the result is
 ///at least that number. Rote quality will re-roll a failure once, while also exploding
 ///on 10. The function returns a Vec of all rolled dice (usually 1).
-fn roll_die<R: DieRoller>(roller: &mut R, pool: &DicePool) -> Vec<i32> {
+pub(super) fn roll_die<R: DieRoller>(roller: &mut R, pool: &DicePool) -> Vec<i32> {
     let mut results = vec![];
     let sides = pool.sides;
     let success_on = pool.modifiers.success_on;
@@ -295,12 +276,18 @@ fn roll_die<R: DieRoller>(roller: &mut R, pool: &DicePool) -> Vec<i32> {
     results
 }
 
-fn roll_dice<'a, R: DieRoller>(pool: &DicePool, num_dice: i32, roller: &mut R) -> Vec<i32> {
+pub(super) fn roll_dice<R: DieRoller>(pool: &DicePool, num_dice: i32, roller: &mut R) -> Vec<i32> {
     (0..num_dice)
         .flat_map(|_| roll_die(roller, &pool))
         .collect()
 }
 
+/// Roll the dice of a pool, keeping the dice rolled for each die of
+/// the pool together.
+fn roll_dice_chains<R: DieRoller>(pool: &DicePool, num_dice: i32, roller: &mut R) -> Vec<Vec<i32>> {
+    (0..num_dice).map(|_| roll_die(roller, pool)).collect()
+}
+
 ///Roll the dice in a dice pool, according to behavior documented in the various rolling
 ///methods.
 pub async fn roll_pool(pool: &DicePoolWithContext<'_>) -> Result<RolledDicePool, BotError> {
@@ -309,16 +296,15 @@ pub async fn roll_pool(pool: &DicePoolWithContext<'_>) -> Result<RolledDicePool,
     }
 
     let num_dice = crate::logic::calculate_dice_amount(&pool.0.amounts, &pool.1).await?;
-    let mut roller = RngDieRoller(rand::thread_rng());
+    let rng = &pool.1.rng;
 
     if num_dice > 0 {
-        let rolls = roll_dice(&pool.0, num_dice, &mut roller);
-        Ok(RolledDicePool::from(&pool.0, num_dice, rolls))
+        let chains = rng.with(|rng| roll_dice_chains(pool.0, num_dice, &mut RngDieRoller(rng)));
+        Ok(RolledDicePool::from_chains(pool.0, num_dice, chains))
     } else {
         let chance_die = DicePool::chance_die();
-        let pool = DicePoolWithContext(&chance_die, &pool.1);
-        let rolls = roll_dice(&pool.0, 1, &mut roller);
-        Ok(RolledDicePool::from(&pool.0, 1, rolls))
+        let chains = rng.with(|rng| roll_dice_chains(&chance_die, 1, &mut RngDieRoller(rng)));
+        Ok(RolledDicePool::from_chains(&chance_die, 1, chains))
     }
 }
 
@@ -326,7 +312,9 @@ pub async fn roll_pool(pool: &DicePoolWithContext<'_>) -> Result<RolledDicePool,
 mod tests {
     use super::*;
     use crate::db::sqlite::Database;
-    use crate::db::Variables;
+    use crate::db::{RoomVariables, Variables};
+    use crate::models::Verbosity;
+    use crate::rng::DiceRng;
     use url::Url;
 
     macro_rules! dummy_room {
@@ -490,6 +478,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let mut amounts = vec![];
@@ -532,6 +524,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let mut amounts = vec![];
@@ -571,6 +567,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         db.set_user_variable(
@@ -597,6 +597,97 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn can_resolve_room_variables_test() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+        let ctx = Context {
+            account: crate::models::Account::default(),
+            db: db.clone(),
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: dummy_room!(),
+            active_room: dummy_room!(),
+            username: "username",
+            message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        db.set_room_variable(ctx.origin_room.id.as_str(), "momentum", 3)
+            .await
+            .expect("could not set momentum to 3");
+
+        let amounts = vec![
+            Amount {
+                operator: Operator::Plus,
+                element: Element::Number(5),
+            },
+            Amount {
+                operator: Operator::Plus,
+                element: Element::RoomVariable("momentum".to_owned()),
+            },
+        ];
+
+        let pool = DicePool::new(amounts, DicePoolModifiers::default());
+
+        assert_eq!(
+            crate::logic::calculate_dice_amount(&pool.amounts, &ctx)
+                .await
+                .unwrap(),
+            8
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn variable_names_are_case_insensitive_test() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+        let ctx = Context {
+            account: crate::models::Account::default(),
+            db: db.clone(),
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: dummy_room!(),
+            active_room: dummy_room!(),
+            username: "username",
+            message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        db.set_user_variable(ctx.username, ctx.origin_room.id.as_str(), "Stärke", 3)
+            .await
+            .expect("could not set Stärke to 3");
+
+        let pool = crate::cofd::parser::parse_dice_pool("STÄRKE + 2").unwrap();
+
+        assert_eq!(
+            crate::logic::calculate_dice_amount(&pool.amounts, &ctx)
+                .await
+                .unwrap(),
+            5
+        );
+    }
+
     //DicePool tests
     #[test]
     fn easy_pool_chance_die_test() {
@@ -688,30 +779,6 @@ mod tests {
     }
 
     //Format tests
-    #[test]
-    fn formats_rolled_dice_pool_single_die() {
-        let pool = DicePool::easy_pool(5, DicePoolQuality::TenAgain);
-        let rolled_pool = RolledDicePool::from(&pool, 1, vec![1]);
-        let message = format!("{}", rolled_pool);
-        assert!(message.starts_with("1 die"));
-    }
-
-    #[test]
-    fn formats_rolled_dice_pool_multiple_dice() {
-        let pool = DicePool::easy_pool(5, DicePoolQuality::TenAgain);
-        let rolled_pool = RolledDicePool::from(&pool, 2, vec![1, 2]);
-        let message = format!("{}", rolled_pool);
-        assert!(message.starts_with("2 dice"));
-    }
-
-    #[test]
-    fn formats_rolled_dice_pool_zero_dice() {
-        let pool = DicePool::easy_pool(5, DicePoolQuality::TenAgain);
-        let rolled_pool = RolledDicePool::from(&pool, 0, vec![]);
-        let message = format!("{}", rolled_pool);
-        assert!(message.starts_with("0 dice"));
-    }
-
     #[test]
     fn formats_dramatic_failure_test() {
         let result = DicePoolRoll {
@@ -737,43 +804,14 @@ mod tests {
     }
 
     #[test]
-    fn formats_lots_of_dice_test() {
-        let result = DicePoolRoll {
+    fn outcome_test() {
+        let roll = |rolls: Vec<i32>| DicePoolRoll {
             modifiers: DicePoolModifiers::default(),
-            rolls: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            rolls,
         };
 
-        assert_eq!(
-            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, and 4 more",
-            fmt_rolls(&result)
-        );
-    }
-
-    #[test]
-    fn shows_more_than_10_dice_test() {
-        //Make sure we display more than 10 dice when below the display limit (15).
-        let result = DicePoolRoll {
-            modifiers: DicePoolModifiers::default(),
-            rolls: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
-        };
-
-        assert_eq!(
-            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14",
-            fmt_rolls(&result)
-        );
-    }
-
-    #[test]
-    fn shows_exactly_15_dice_test() {
-        //If we are at format limit (15), make sure all are shown
-        let result = DicePoolRoll {
-            modifiers: DicePoolModifiers::default(),
-            rolls: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
-        };
-
-        assert_eq!(
-            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15",
-            fmt_rolls(&result)
-        );
+        assert_eq!("failure!", roll(vec![1, 2]).outcome());
+        assert_eq!("success!", roll(vec![8, 2]).outcome());
+        assert_eq!("exceptional success!", roll(vec![8, 8, 9, 10, 8]).outcome());
     }
 }
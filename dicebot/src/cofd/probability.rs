@@ -0,0 +1,214 @@
+use super::dice::{
+    roll_dice, roll_die, DicePool, DicePoolQuality, DicePoolWithContext, DieRoller, RngDieRoller,
+    RolledDicePool,
+};
+use crate::error::{BotError, DiceRollingError};
+use crate::formatting;
+use crate::probability::{
+    enumerate_rolls, percent, simulated_rolls, Distribution, Odds, RollEnumerator,
+};
+use crate::rng::DiceRng;
+use std::convert::TryFrom;
+
+/// Pools with more dice than this have their odds simulated, because
+/// adding up the exact odds of every die becomes too slow.
+const MAX_EXACT_POOL_SIZE: i32 = 300;
+
+impl DieRoller for RollEnumerator {
+    fn roll_number(&mut self, sides: i32) -> i32 {
+        self.next_die(sides)
+    }
+}
+
+fn count_successes(pool: &DicePool, rolls: &[i32]) -> i32 {
+    let successes = rolls
+        .iter()
+        .filter(|&roll| *roll >= pool.modifiers.success_on)
+        .count();
+
+    i32::try_from(successes).unwrap_or(0)
+}
+
+/// Exact distribution of successes for the whole pool, built up by
+/// enumerating every way a single die (and its re-rolls) can land.
+fn exact_successes(pool: &DicePool, num_dice: i32) -> Distribution {
+    let die = Distribution::from_outcomes(enumerate_rolls(|roller| {
+        count_successes(pool, &roll_die(roller, pool))
+    }))
+    .trimmed();
+
+    (0..num_dice).fold(Distribution::constant(0), |total, _| {
+        total.add(&die).trimmed()
+    })
+}
+
+/// Estimated distribution of successes for the whole pool, from a
+/// bounded number of simulated rolls.
+fn simulated_successes(
+    pool: &DicePool,
+    num_dice: i32,
+    rng: &DiceRng,
+) -> Result<(Distribution, u64), BotError> {
+    let rolls = simulated_rolls(num_dice as u64).ok_or(DiceRollingError::ExpressionTooLarge)?;
+
+    // The simulation takes a while, so it rolls with a generator of
+    // its own rather than keeping the shared one locked.
+    let mut roller = RngDieRoller(rng.split());
+    let samples =
+        (0..rolls).map(|_| count_successes(pool, &roll_dice(pool, num_dice, &mut roller)));
+    let successes = Distribution::from_samples(samples);
+
+    Ok((successes, rolls))
+}
+
+/// Chance of rolling a 1 on a chance die.
+fn dramatic_failure_chance(pool: &DicePool) -> f64 {
+    enumerate_rolls(|roller| roll_die(roller, pool).first() == Some(&1))
+        .into_iter()
+        .filter(|&(dramatic, _)| dramatic)
+        .map(|(_, probability)| probability)
+        .sum()
+}
+
+fn pool_odds_for(pool: &DicePool, num_dice: i32, rng: &DiceRng) -> Result<Odds, BotError> {
+    let (successes, simulated) = if num_dice <= MAX_EXACT_POOL_SIZE {
+        (exact_successes(pool, num_dice), None)
+    } else {
+        let (successes, rolls) = simulated_successes(pool, num_dice, rng)?;
+        (successes, Some(rolls))
+    };
+
+    let exceptional_on = pool.modifiers.exceptional_on;
+    let mut statistics = vec![
+        ("Success chance".to_string(), percent(successes.at_least(1))),
+        (
+            "Exceptional success chance".to_string(),
+            percent(successes.at_least(exceptional_on)),
+        ),
+    ];
+
+    if pool.modifiers.quality == DicePoolQuality::ChanceDie {
+        statistics.push((
+            "Dramatic failure chance".to_string(),
+            percent(dramatic_failure_chance(pool)),
+        ));
+    }
+
+    statistics.push((
+        "Expected successes".to_string(),
+        format!("{:.2}", successes.expected_value()),
+    ));
+
+    Ok(Odds {
+        description: formatting::pool_description(&RolledDicePool::from(pool, num_dice, vec![])),
+        statistics,
+        histogram: successes.histogram_rows(),
+        simulated_rolls: simulated,
+    })
+}
+
+/// Calculate the odds of a dice pool: the chance of success and
+/// exceptional success, expected successes, and how likely each
+/// amount of successes is. Like rolling the pool, a pool of zero or
+/// fewer dice becomes a chance die.
+pub async fn pool_odds(pool: &DicePoolWithContext<'_>) -> Result<Odds, BotError> {
+    if pool.0.amounts.len() > 100 {
+        return Err(DiceRollingError::ExpressionTooLarge.into());
+    }
+
+    let num_dice = crate::logic::calculate_dice_amount(&pool.0.amounts, pool.1).await?;
+    let rng = pool.1.rng.clone();
+    let (pool, num_dice) = match num_dice {
+        num_dice if num_dice > 0 => {
+            let pool = DicePool::easy_with_modifiers(num_dice, pool.0.modifiers);
+            (pool, num_dice)
+        }
+        _ => (DicePool::chance_die(), 1),
+    };
+
+    // Adding up the odds of a big pool takes a while, so it must not
+    // hold up the commands of other rooms.
+    tokio::task::spawn_blocking(move || pool_odds_for(&pool, num_dice, &rng))
+        .await
+        .expect("Calculating the odds of a dice pool panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-6,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn ten_again_single_die_test() {
+        let pool = DicePool::easy_pool(1, DicePoolQuality::TenAgain);
+        let successes = exact_successes(&pool, 1);
+
+        assert_close(0.7, successes.probability(0));
+        assert_close(0.2 + 0.1 * 0.7, successes.probability(1));
+        assert_close(0.1 * 0.2 + 0.01 * 0.7, successes.probability(2));
+        assert_close(0.3 / 0.9, successes.expected_value());
+    }
+
+    #[test]
+    fn rote_single_die_test() {
+        let pool = DicePool::easy_pool(1, DicePoolQuality::Rote);
+        let successes = exact_successes(&pool, 1);
+
+        // A failure is re-rolled once, so both rolls must fail.
+        assert_close(0.49, successes.probability(0));
+    }
+
+    #[test]
+    fn no_explode_pool_test() {
+        let pool = DicePool::easy_pool(2, DicePoolQuality::NoExplode);
+        let successes = exact_successes(&pool, 2);
+
+        assert_eq!(2, successes.max());
+        assert_close(0.09, successes.probability(2));
+        assert_close(1.0 - 0.49, successes.at_least(1));
+    }
+
+    #[test]
+    fn chance_die_odds_test() {
+        let pool = DicePool::chance_die();
+        let odds = pool_odds_for(&pool, 1, &DiceRng::seeded(1)).unwrap();
+
+        let stat = |name: &str| {
+            odds.statistics
+                .iter()
+                .find(|(stat_name, _)| stat_name == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        assert_eq!(Some("10.00%".to_string()), stat("Success chance"));
+        assert_eq!(Some("10.00%".to_string()), stat("Dramatic failure chance"));
+        assert_eq!(None, odds.simulated_rolls);
+    }
+
+    #[test]
+    fn large_pools_are_simulated_test() {
+        let pool = DicePool::easy_pool(1000, DicePoolQuality::TenAgain);
+        let odds = pool_odds_for(&pool, 1000, &DiceRng::seeded(1)).unwrap();
+        assert!(odds.simulated_rolls.is_some());
+    }
+
+    #[test]
+    fn huge_pools_are_rejected_test() {
+        let pool = DicePool::easy_pool(1_000_000, DicePoolQuality::TenAgain);
+        let result = pool_odds_for(&pool, 1_000_000, &DiceRng::seeded(1));
+        assert!(matches!(
+            result,
+            Err(BotError::DiceRollingError(
+                DiceRollingError::ExpressionTooLarge
+            ))
+        ));
+    }
+}
@@ -0,0 +1,290 @@
+use super::{escape_html, Command, CommandError, Execution, ExecutionResult, Permission};
+use crate::context::Context;
+use crate::db::{DbState, Users, Variables};
+use crate::error::BotError;
+use crate::matrix;
+use crate::models::{AccountStatus, ReplyType::Notice};
+use crate::state::STARTED;
+use async_trait::async_trait;
+use matrix_sdk::identifiers::{RoomId, UserId};
+use std::convert::TryFrom;
+
+/// Commands for the operators of the bot. Only bot admins can execute
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    /// List the rooms the bot is in.
+    Rooms,
+
+    /// Make the bot leave a room.
+    Leave(String),
+
+    /// List the user accounts.
+    Users,
+
+    /// Delete the variables of a user, in one room or in every room.
+    ResetVariables(String, Option<String>),
+
+    /// Show the uptime and version of the bot, and the database size.
+    Status,
+
+    /// Send a notice to every room the bot is in.
+    Broadcast(String),
+}
+
+fn invalid(message: impl Into<String>) -> BotError {
+    CommandError::InvalidCommand(message.into()).into()
+}
+
+impl TryFrom<String> for AdminCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        use AdminCommand::*;
+        let input = input.trim();
+        let (subcommand, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let rest = rest.trim();
+
+        let command = match (subcommand.to_lowercase().as_ref(), rest) {
+            ("rooms", "") => Rooms,
+            ("leave", room) if !room.is_empty() => Leave(RoomId::try_from(room)?.to_string()),
+            ("users", "") => Users,
+            ("resetvars", args) => {
+                let mut args = args.split_whitespace();
+                let user = args
+                    .next()
+                    .ok_or_else(|| invalid("resetvars needs a user"))?;
+                let room = args.next().map(RoomId::try_from).transpose()?;
+
+                if args.next().is_some() {
+                    return Err(invalid(input));
+                }
+
+                ResetVariables(
+                    UserId::try_from(user)?.to_string(),
+                    room.map(|room| room.to_string()),
+                )
+            }
+            ("status", "") => Status,
+            ("broadcast", message) if !message.is_empty() => Broadcast(message.to_owned()),
+            _ => return Err(invalid(input)),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Format a duration in seconds like "2d 3h 14m".
+fn format_uptime(seconds: u64) -> String {
+    let days = seconds / (24 * 60 * 60);
+    let hours = seconds / (60 * 60) % 24;
+    let minutes = seconds / 60 % 60;
+
+    match days {
+        0 => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// Format a size in bytes, in the largest unit that fits.
+fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = "bytes";
+
+    for next_unit in UNITS.iter() {
+        if size < 1024.0 {
+            break;
+        }
+
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    match unit {
+        "bytes" => format!("{} bytes", bytes),
+        _ => format!("{:.1} {}", size, unit),
+    }
+}
+
+fn account_status(status: AccountStatus) -> &'static str {
+    match status {
+        AccountStatus::NotRegistered => "not registered",
+        AccountStatus::Registered => "registered",
+        AccountStatus::AwaitingActivation => "awaiting activation",
+    }
+}
+
+#[async_trait]
+impl Command for AdminCommand {
+    fn name(&self) -> &'static str {
+        "bot administration"
+    }
+
+    fn is_secure(&self) -> bool {
+        true
+    }
+
+    fn permission(&self) -> Permission {
+        Permission::Admin
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        use AdminCommand::*;
+        let client = &ctx.matrix_client;
+
+        let html = match self {
+            Rooms => {
+                let mut rooms = vec![];
+                for room in client.joined_rooms() {
+                    let name = room.display_name().await.unwrap_or_default();
+                    rooms.push(format!("  {}  |  {}", room.room_id(), escape_html(&name)));
+                }
+
+                format!(
+                    "<strong>Joined rooms ({}):</strong><pre>{}</pre>",
+                    rooms.len(),
+                    rooms.join("\n")
+                )
+            }
+            Leave(room_id) => {
+                let room = RoomId::try_from(room_id.as_str())?;
+                let room = client
+                    .get_joined_room(&room)
+                    .ok_or(BotError::RoomDoesNotExist)?;
+
+                room.leave().await?;
+                format!("<strong>Left room:</strong> {}", room_id)
+            }
+            Users => {
+                let users: Vec<String> = ctx
+                    .db
+                    .get_users()
+                    .await?
+                    .into_iter()
+                    .map(|user| {
+                        format!(
+                            "  {}  |  {}  |  {}  |  active room: {}",
+                            user.username,
+                            account_status(user.account_status),
+                            if user.password.is_some() {
+                                "linked"
+                            } else {
+                                "not linked"
+                            },
+                            user.active_room.as_deref().unwrap_or("none")
+                        )
+                    })
+                    .collect();
+
+                format!(
+                    "<strong>User accounts ({}):</strong><pre>{}</pre>",
+                    users.len(),
+                    users.join("\n")
+                )
+            }
+            ResetVariables(user, room_id) => {
+                let deleted = ctx
+                    .db
+                    .delete_all_user_variables(user, room_id.as_deref())
+                    .await?;
+
+                let room = room_id.as_deref().unwrap_or("every room");
+                format!(
+                    "<strong>Reset Variables:</strong> deleted {} variables of {} in {}",
+                    deleted, user, room
+                )
+            }
+            Status => {
+                let uptime = format_uptime(STARTED.elapsed().as_secs());
+                let size = format_size(ctx.db.database_size().await?);
+
+                format!(
+                    "<strong>Version:</strong> {}<br/>\
+                     <strong>Uptime:</strong> {}<br/>\
+                     <strong>Joined rooms:</strong> {}<br/>\
+                     <strong>Database size:</strong> {}",
+                    env!("CARGO_PKG_VERSION"),
+                    uptime,
+                    client.joined_rooms().len(),
+                    size
+                )
+            }
+            Broadcast(message) => {
+                let html = format!(
+                    "<strong>Notice from the bot admins:</strong> {}",
+                    escape_html(message)
+                );
+
+                let plain = format!("Notice from the bot admins: {}", message);
+                let mut sent = 0;
+
+                for room in client.joined_rooms() {
+                    let notice = (html.as_str(), plain.as_str());
+                    if matrix::send_message(client, room.room_id(), notice, None, None, Notice)
+                        .await
+                        .is_some()
+                    {
+                        sent += 1;
+                    }
+                }
+
+                format!("<strong>Broadcast:</strong> sent to {} rooms", sent)
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_admin_commands_test() {
+        use AdminCommand::*;
+        let parse = |input: &str| AdminCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse("rooms"), Some(Rooms));
+        assert_eq!(
+            parse("leave !abc:example.com"),
+            Some(Leave("!abc:example.com".to_string()))
+        );
+        assert_eq!(
+            parse("resetvars @user:example.com"),
+            Some(ResetVariables("@user:example.com".to_string(), None))
+        );
+        assert_eq!(
+            parse("resetvars @user:example.com !abc:example.com"),
+            Some(ResetVariables(
+                "@user:example.com".to_string(),
+                Some("!abc:example.com".to_string())
+            ))
+        );
+        assert_eq!(
+            parse("broadcast Restarting in 5 minutes"),
+            Some(Broadcast("Restarting in 5 minutes".to_string()))
+        );
+
+        assert_eq!(parse("leave"), None);
+        assert_eq!(parse("leave notaroom"), None);
+        assert_eq!(parse("resetvars user"), None);
+        assert_eq!(parse("broadcast"), None);
+        assert_eq!(parse("shutdown"), None);
+    }
+
+    #[test]
+    fn format_uptime_test() {
+        assert_eq!(format_uptime(59), "0h 0m");
+        assert_eq!(format_uptime(2 * 60 * 60 + 5 * 60), "2h 5m");
+        assert_eq!(format_uptime(3 * 24 * 60 * 60 + 60 * 60), "3d 1h 0m");
+    }
+
+    #[test]
+    fn format_size_test() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(2048), "2.0 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 + 512 * 1024), "3.5 MiB");
+    }
+}
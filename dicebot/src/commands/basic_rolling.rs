@@ -1,9 +1,15 @@
-use super::{Command, Execution, ExecutionResult};
+use super::{Command, CommandError, Execution, ExecutionResult};
 use crate::basic::dice::ElementExpression;
 use crate::basic::parser::parse_element_expression;
-use crate::basic::roll::Roll;
+use crate::basic::roll::{Roll, Rolled};
 use crate::context::Context;
-use crate::error::BotError;
+use crate::db::errors::DataError;
+use crate::db::Variables;
+use crate::error::{BotError, DiceRollingError};
+use crate::formatting;
+use crate::logic;
+use crate::models::{GameSystem, RollSummary, VariableValue};
+use crate::parser::variables::{parse_expression, parse_variable_name};
 use async_trait::async_trait;
 use nom::Err as NomErr;
 use std::convert::TryFrom;
@@ -36,13 +42,82 @@ impl Command for RollCommand {
         false
     }
 
-    async fn execute(&self, _ctx: &Context<'_>) -> ExecutionResult {
-        let roll = self.0.roll();
-        let html = format!(
-            "<strong>Dice:</strong> {}</p><p><strong>Result</strong>: {}",
-            self.0, roll
-        );
+    fn is_roll(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        roll_expression(&self.0, self.0.to_string(), ctx)
+    }
+}
+
+/// Roll a regular dice expression. The description is shown in place
+/// of the expression.
+fn roll_expression(
+    expression: &ElementExpression,
+    description: String,
+    ctx: &Context<'_>,
+) -> ExecutionResult {
+    let roll = ctx.rng.with(|rng| expression.roll(rng));
+    let html = formatting::basic_roll(&description, &roll, ctx.verbosity);
+
+    let summary = RollSummary {
+        expression: description,
+        dice: roll.dice().into_iter().map(|die| die as i32).collect(),
+        total: roll.rolled_value(),
+        outcome: None,
+    };
+
+    Execution::roll(html, GameSystem::Basic, summary)
+}
+
+/// Roll a dice expression stored in a variable, like "!r sword".
+pub struct RollVariableCommand(pub String);
+
+impl TryFrom<String> for RollVariableCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        Ok(RollVariableCommand(parse_variable_name(&input)?))
+    }
+}
+
+#[async_trait]
+impl Command for RollVariableCommand {
+    fn name(&self) -> &'static str {
+        "roll stored dice expression"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn is_roll(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let name = &self.0;
+        let value = ctx
+            .db
+            .get_variable_value(ctx.username, ctx.active_room_id().as_str(), name)
+            .await;
+
+        let stored = match value {
+            Ok(VariableValue::Dice(expression)) => expression,
+            Ok(_) => {
+                let message = format!("{} is not a dice expression", name);
+                return Err(CommandError::InvalidCommand(message).into());
+            }
+            Err(DataError::KeyDoesNotExist(_)) => {
+                return Err(DiceRollingError::VariableNotFound(name.clone()).into())
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-        Execution::success(html)
+        let terms = parse_expression(&stored)?;
+        let expression = logic::resolve_expression(&terms, ctx).await?;
+        let description = format!("{} ({})", name, expression);
+        roll_expression(&expression, description, ctx)
     }
 }
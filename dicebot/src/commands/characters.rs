@@ -0,0 +1,165 @@
+use super::{escape_html, Command, CommandError, Execution, ExecutionResult};
+use crate::context::Context;
+use crate::db::errors::DataError;
+use crate::db::Characters;
+use crate::error::BotError;
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// Longest name a character can have.
+pub const MAX_CHARACTER_NAME_LENGTH: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CharacterCommand {
+    /// List the user's characters in the room.
+    List,
+
+    /// Create a new character and make it active.
+    Create(String),
+
+    /// Make an existing character active.
+    Switch(String),
+
+    /// Go back to the user's own variables.
+    SwitchOff,
+
+    /// Delete a character and its variables.
+    Delete(String),
+}
+
+fn character_name(name: &str) -> Result<String, CommandError> {
+    match name.trim() {
+        "" => Err(CommandError::InvalidCommand(
+            "character name is missing".to_string(),
+        )),
+        name if name.chars().count() > MAX_CHARACTER_NAME_LENGTH => {
+            Err(CommandError::InvalidCommand(format!(
+                "character name can be at most {} characters",
+                MAX_CHARACTER_NAME_LENGTH
+            )))
+        }
+        name => Ok(name.to_owned()),
+    }
+}
+
+impl TryFrom<String> for CharacterCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        use CharacterCommand::*;
+        let input = input.trim();
+        let (subcommand, name) = input.split_once(' ').unwrap_or((input, ""));
+
+        let command = match subcommand.to_lowercase().as_ref() {
+            "" | "list" => List,
+            "new" | "create" => Create(character_name(name)?),
+            "switch" | "use" => Switch(character_name(name)?),
+            "off" => SwitchOff,
+            "delete" | "del" => Delete(character_name(name)?),
+            _ => return Err(CommandError::InvalidCommand(input.to_owned()).into()),
+        };
+
+        Ok(command)
+    }
+}
+
+#[async_trait]
+impl Command for CharacterCommand {
+    fn name(&self) -> &'static str {
+        "manage characters"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        use CharacterCommand::*;
+        let user = ctx.username;
+        let room_id = ctx.active_room_id().as_str();
+
+        // Character names are sent by users, so they are escaped in
+        // every reply.
+        let html = match self {
+            List => {
+                let characters = ctx.db.get_characters(user, room_id).await?;
+                let list: Vec<String> = characters
+                    .into_iter()
+                    .map(|character| {
+                        let name = escape_html(&character.name);
+                        match character.active {
+                            true => format!(" - {} (active)", name),
+                            false => format!(" - {}", name),
+                        }
+                    })
+                    .collect();
+
+                match list.is_empty() {
+                    true => "<strong>Characters:</strong> none".to_string(),
+                    false => format!("<strong>Characters:</strong><br/>{}", list.join("<br/>")),
+                }
+            }
+            Create(name) => match ctx.db.create_character(user, room_id, name).await {
+                Ok(()) => format!(
+                    "<strong>Created character:</strong> {} (now active)",
+                    escape_html(name)
+                ),
+                Err(DataError::KeyAlreadyExists(_)) => {
+                    format!("{} already exists", escape_html(name))
+                }
+                Err(e) => return Err(e.into()),
+            },
+            Switch(name) => match ctx.db.set_active_character(user, room_id, Some(name)).await {
+                Ok(()) => format!("<strong>Active character:</strong> {}", escape_html(name)),
+                Err(DataError::KeyDoesNotExist(_)) => {
+                    format!("{} does not exist", escape_html(name))
+                }
+                Err(e) => return Err(e.into()),
+            },
+            SwitchOff => {
+                ctx.db.set_active_character(user, room_id, None).await?;
+                "<strong>Active character:</strong> none (using your own variables)".to_string()
+            }
+            Delete(name) => match ctx.db.delete_character(user, room_id, name).await {
+                Ok(()) => format!("<strong>Deleted character:</strong> {}", escape_html(name)),
+                Err(DataError::KeyDoesNotExist(_)) => {
+                    format!("{} does not exist", escape_html(name))
+                }
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_character_commands_test() {
+        use CharacterCommand::*;
+        let parse = |input: &str| CharacterCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse(""), Some(List));
+        assert_eq!(parse("list"), Some(List));
+        assert_eq!(
+            parse("new Old Man Henderson"),
+            Some(Create("Old Man Henderson".to_string()))
+        );
+        assert_eq!(parse("switch Alice"), Some(Switch("Alice".to_string())));
+        assert_eq!(parse("off"), Some(SwitchOff));
+        assert_eq!(parse("delete Alice"), Some(Delete("Alice".to_string())));
+    }
+
+    #[test]
+    fn parse_invalid_character_commands_test() {
+        assert!(CharacterCommand::try_from("new".to_string()).is_err());
+        assert!(CharacterCommand::try_from("switch   ".to_string()).is_err());
+        assert!(CharacterCommand::try_from("dance Alice".to_string()).is_err());
+
+        let long_name = format!("new {}", "a".repeat(MAX_CHARACTER_NAME_LENGTH + 1));
+        assert!(CharacterCommand::try_from(long_name).is_err());
+    }
+}
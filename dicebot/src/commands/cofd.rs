@@ -3,6 +3,9 @@ use crate::cofd::dice::{roll_pool, DicePool, DicePoolWithContext};
 use crate::cofd::parser::{create_chance_die, parse_dice_pool};
 use crate::context::Context;
 use crate::error::BotError;
+use crate::formatting;
+use crate::logic;
+use crate::models::{GameSystem, RollSummary};
 use async_trait::async_trait;
 use std::convert::TryFrom;
 
@@ -34,15 +37,26 @@ impl Command for PoolRollCommand {
         false
     }
 
+    fn is_roll(&self) -> bool {
+        true
+    }
+
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let pool_with_ctx = DicePoolWithContext(&self.0, ctx);
         let rolled_pool = roll_pool(&pool_with_ctx).await?;
 
-        let html = format!(
-            "<strong>Pool:</strong> {}</p><p><strong>Result</strong>: {}",
-            rolled_pool, rolled_pool.roll
-        );
+        let room_id = ctx.active_room_id().as_str();
+        let max_displayed = logic::room_max_displayed_dice(&ctx.db, room_id).await?;
+
+        let html = formatting::pool_roll(&rolled_pool, ctx.verbosity, max_displayed);
+
+        let summary = RollSummary {
+            expression: formatting::pool_description(&rolled_pool),
+            dice: rolled_pool.roll.rolls().to_vec(),
+            total: rolled_pool.roll.successes(),
+            outcome: Some(rolled_pool.roll.outcome()),
+        };
 
-        Execution::success(html)
+        Execution::roll(html, GameSystem::ChroniclesOfDarkness, summary)
     }
 }
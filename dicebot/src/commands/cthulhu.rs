@@ -6,6 +6,8 @@ use crate::cthulhu::dice::{
 };
 use crate::cthulhu::parser::{parse_advancement_roll, parse_regular_roll};
 use crate::error::BotError;
+use crate::formatting;
+use crate::models::{GameSystem, RollSummary};
 use async_trait::async_trait;
 use std::convert::TryFrom;
 
@@ -30,16 +32,24 @@ impl Command for CthRoll {
         false
     }
 
+    fn is_roll(&self) -> bool {
+        true
+    }
+
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let roll_with_ctx = DiceRollWithContext(&self.0, ctx);
         let executed_roll = regular_roll(&roll_with_ctx).await?;
+        let html = formatting::cthulhu_roll(&executed_roll, ctx.verbosity);
 
-        let html = format!(
-            "<strong>Roll:</strong> {}</p><p><strong>Result</strong>: {}",
-            executed_roll, executed_roll.roll
-        );
+        let num_rolled = executed_roll.roll.num_rolled() as i32;
+        let summary = RollSummary {
+            expression: formatting::cthulhu_target(&executed_roll),
+            dice: vec![num_rolled],
+            total: num_rolled,
+            outcome: Some(executed_roll.roll.result().to_string()),
+        };
 
-        Execution::success(html)
+        Execution::roll(html, GameSystem::CallOfCthulhu, summary)
     }
 }
 
@@ -67,11 +77,22 @@ impl Command for CthAdvanceRoll {
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let roll_with_ctx = AdvancementRollWithContext(&self.0, ctx);
         let executed_roll = advancement_roll(&roll_with_ctx).await?;
-        let html = format!(
-            "<strong>Roll:</strong> {}</p><p><strong>Result</strong>: {}",
-            executed_roll, executed_roll.roll
-        );
+        let html = formatting::advancement_roll(&executed_roll, ctx.verbosity);
+
+        let roll = &executed_roll.roll;
+        let outcome = match roll.successful() {
+            true => format!("success! new skill is {}", roll.new_skill_amount()),
+            false => String::from("failure!"),
+        };
+
+        let num_rolled = roll.num_rolled() as i32;
+        let summary = RollSummary {
+            expression: formatting::advancement_target(&executed_roll),
+            dice: vec![num_rolled],
+            total: num_rolled,
+            outcome: Some(outcome),
+        };
 
-        Execution::success(html)
+        Execution::roll(html, GameSystem::CallOfCthulhu, summary)
     }
 }
@@ -0,0 +1,123 @@
+use super::{Command, CommandError, Execution, ExecutionResult, Permission};
+use crate::context::Context;
+use crate::db::FairnessSessions;
+use crate::error::BotError;
+use crate::logic;
+use crate::models::FairnessSession;
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// How many revealed sessions are listed.
+const REVEALED_SESSIONS: u32 = 5;
+
+/// Show the commitment of the current fair mode session and the
+/// secrets of the last revealed sessions, or reveal the current
+/// session and start a new one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FairnessCommand {
+    Show,
+    Reveal,
+}
+
+impl TryFrom<String> for FairnessCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        match input.trim().to_lowercase().as_ref() {
+            "" => Ok(FairnessCommand::Show),
+            "reveal" => Ok(FairnessCommand::Reveal),
+            _ => Err(CommandError::InvalidCommand(input).into()),
+        }
+    }
+}
+
+fn fmt_current(session: &FairnessSession) -> String {
+    format!(
+        "<strong>Fair rolls:</strong> on, session {} commits to <code>{}</code>",
+        session.id, session.commitment
+    )
+}
+
+fn fmt_revealed(sessions: &[FairnessSession]) -> String {
+    let sessions: Vec<String> = sessions
+        .iter()
+        .map(|session| {
+            format!(
+                "  {}  |  secret: {}  |  commitment: {}",
+                session.id, session.secret, session.commitment
+            )
+        })
+        .collect();
+
+    format!(
+        "<strong>Revealed sessions:</strong><pre>{}</pre>",
+        sessions.join("\n")
+    )
+}
+
+#[async_trait]
+impl Command for FairnessCommand {
+    fn name(&self) -> &'static str {
+        "fair rolls"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        match self {
+            FairnessCommand::Show => Permission::Anyone,
+            FairnessCommand::Reveal => Permission::Admin,
+        }
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let html = match self {
+            FairnessCommand::Show => {
+                let current = match ctx.db.current_fairness_session().await? {
+                    Some(session) => fmt_current(&session),
+                    None => "<strong>Fair rolls:</strong> off".to_owned(),
+                };
+
+                let revealed = ctx.db.revealed_fairness_sessions(REVEALED_SESSIONS).await?;
+
+                match revealed.is_empty() {
+                    true => current,
+                    false => format!("{}<br/>{}", current, fmt_revealed(&revealed)),
+                }
+            }
+            FairnessCommand::Reveal => {
+                let previous = ctx
+                    .db
+                    .current_fairness_session()
+                    .await?
+                    .ok_or(BotError::FairRollsOff)?;
+
+                let current = logic::start_fairness_session(&ctx.db).await?;
+                format!(
+                    "{}<br/><strong>Revealed session {}:</strong> secret <code>{}</code>",
+                    fmt_current(&current),
+                    previous.id,
+                    previous.secret
+                )
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fairness_command_test() {
+        let parse = |input: &str| FairnessCommand::try_from(input.to_owned()).ok();
+
+        assert_eq!(parse(""), Some(FairnessCommand::Show));
+        assert_eq!(parse(" Reveal "), Some(FairnessCommand::Reveal));
+        assert_eq!(parse("reveal now"), None);
+    }
+}
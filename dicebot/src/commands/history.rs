@@ -0,0 +1,371 @@
+use super::{escape_html, Command, CommandError, Execution, ExecutionResult};
+use crate::context::Context;
+use crate::db::RollHistory;
+use crate::error::BotError;
+use crate::models::RollRecord;
+use async_trait::async_trait;
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Amount of rolls shown when no amount is given.
+const DEFAULT_HISTORY_ROLLS: u32 = 10;
+
+/// Maximum amount of rolls shown at once, so we don't spam the room.
+const MAX_HISTORY_ROLLS: u32 = 50;
+
+/// How far back, in hours, an export goes when no amount of hours is
+/// given. Roughly one play session.
+const DEFAULT_SESSION_HOURS: u32 = 6;
+
+/// How far back, in hours, an export can go at most: one week.
+const MAX_EXPORT_HOURS: u32 = 7 * 24;
+
+/// Maximum amount of rolls in one export.
+const MAX_EXPORT_ROLLS: usize = 100;
+
+/// Maximum size of the exported rolls, in bytes. The reply holds them
+/// as HTML and as plain text, and must stay under the 64 KiB size
+/// limit of Matrix events.
+const MAX_EXPORT_BYTES: usize = 24 * 1024;
+
+/// Whose rolls to show from the history.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryScope {
+    /// The rolls of the user executing the command.
+    Own,
+
+    /// The rolls of another user in the room.
+    User(String),
+
+    /// The rolls of everyone in the room.
+    Room,
+}
+
+/// Format of a roll history export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryCommand {
+    /// Show the last N rolls of the given scope.
+    Show(HistoryScope, u32),
+
+    /// Export every roll in the room from the last N hours.
+    Export(ExportFormat, u32),
+}
+
+fn parse_amount(input: &str) -> Result<u32, CommandError> {
+    input
+        .parse::<u32>()
+        .map_err(|_| CommandError::InvalidCommand(format!("not a valid amount: {}", input)))
+}
+
+fn parse_export_format(input: &str) -> Result<ExportFormat, CommandError> {
+    match input.to_lowercase().as_ref() {
+        "csv" => Ok(ExportFormat::Csv),
+        "json" => Ok(ExportFormat::Json),
+        _ => Err(CommandError::InvalidCommand(format!(
+            "unknown export format: {}",
+            input
+        ))),
+    }
+}
+
+impl TryFrom<String> for HistoryCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        use HistoryCommand::*;
+        let args: Vec<&str> = input.split_whitespace().collect();
+
+        let command = match args[..] {
+            [] => Show(HistoryScope::Own, DEFAULT_HISTORY_ROLLS),
+            ["export", format] => Export(parse_export_format(format)?, DEFAULT_SESSION_HOURS),
+            ["export", format, hours] => Export(parse_export_format(format)?, parse_amount(hours)?),
+            ["room"] => Show(HistoryScope::Room, DEFAULT_HISTORY_ROLLS),
+            ["room", amount] => Show(HistoryScope::Room, parse_amount(amount)?),
+            [user] if user.starts_with('@') => {
+                Show(HistoryScope::User(user.to_owned()), DEFAULT_HISTORY_ROLLS)
+            }
+            [user, amount] if user.starts_with('@') => {
+                Show(HistoryScope::User(user.to_owned()), parse_amount(amount)?)
+            }
+            [amount] => Show(HistoryScope::Own, parse_amount(amount)?),
+            _ => return Err(CommandError::InvalidCommand(input).into()),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Result of a roll, e.g. "14", "3 (success!)" or, for a roll made
+/// in fair mode, "14 (fair session 2)".
+fn fmt_result(record: &RollRecord) -> String {
+    let result = match &record.result.outcome {
+        Some(outcome) => format!("{} ({})", record.result.total, outcome),
+        None => record.result.total.to_string(),
+    };
+
+    match record.fair_session {
+        Some(session) => format!("{} (fair session {})", result, session),
+        None => result,
+    }
+}
+
+/// The rolls as HTML. Commands and user IDs are sent by users, so
+/// everything is escaped.
+fn fmt_show(records: &[RollRecord], scope: &HistoryScope) -> String {
+    let rolls: Vec<String> = records
+        .iter()
+        .map(|record| {
+            let command = escape_html(&record.command);
+            let result = escape_html(&fmt_result(record));
+            match scope {
+                HistoryScope::Own => format!(" - {}: {}", command, result),
+                _ => format!(
+                    " - {} rolled {}: {}",
+                    escape_html(&record.user_id),
+                    command,
+                    result
+                ),
+            }
+        })
+        .collect();
+
+    if rolls.is_empty() {
+        "<strong>Roll history:</strong> no rolls found".to_string()
+    } else {
+        format!("<strong>Roll history:</strong><br/>{}", rolls.join("<br/>"))
+    }
+}
+
+/// Quote a CSV field if it contains characters that would otherwise
+/// break the row.
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn export_csv(records: &[RollRecord]) -> String {
+    let mut lines =
+        vec!["timestamp,user_id,command,expression,dice,total,outcome,fair_session".to_string()];
+
+    for record in records {
+        let dice: Vec<String> = record.result.dice.iter().map(i32::to_string).collect();
+        let fields = [
+            record.timestamp.to_string(),
+            csv_field(&record.user_id),
+            csv_field(&record.command),
+            csv_field(&record.result.expression),
+            csv_field(&dice.join(" ")),
+            record.result.total.to_string(),
+            csv_field(record.result.outcome.as_deref().unwrap_or_default()),
+            record
+                .fair_session
+                .map(|session| session.to_string())
+                .unwrap_or_default(),
+        ];
+
+        lines.push(fields.join(","));
+    }
+
+    lines.join("\n")
+}
+
+fn export_records(records: &[RollRecord], format: ExportFormat) -> Result<String, BotError> {
+    let exported = match format {
+        ExportFormat::Csv => export_csv(records),
+        ExportFormat::Json => serde_json::to_string_pretty(records)?,
+    };
+
+    Ok(escape_html(&exported))
+}
+
+/// Export the most recent rolls that fit in one message. Older rolls
+/// are left out, and the reply says so, if there are more than
+/// MAX_EXPORT_ROLLS rolls or they are larger than MAX_EXPORT_BYTES.
+fn export(records: &[RollRecord], format: ExportFormat) -> Result<String, BotError> {
+    let mut start = records.len().saturating_sub(MAX_EXPORT_ROLLS);
+    let mut exported = export_records(&records[start..], format)?;
+
+    while exported.len() > MAX_EXPORT_BYTES && start < records.len() {
+        start += 1;
+        exported = export_records(&records[start..], format)?;
+    }
+
+    let exported_rolls = records.len() - start;
+    let title = match start {
+        0 => format!("Roll history export ({} rolls):", exported_rolls),
+        _ => format!(
+            "Roll history export (the last {} rolls, older rolls do not fit in one message):",
+            exported_rolls
+        ),
+    };
+
+    Ok(format!(
+        "<strong>{}</strong><pre><code>{}</code></pre>",
+        title, exported
+    ))
+}
+
+#[async_trait]
+impl Command for HistoryCommand {
+    fn name(&self) -> &'static str {
+        "roll history"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let room_id = ctx.room_id().as_str();
+
+        let html = match self {
+            HistoryCommand::Show(scope, amount) => {
+                let amount = (*amount).min(MAX_HISTORY_ROLLS);
+                let thread_id = ctx.thread_id;
+                let records = match scope {
+                    HistoryScope::Own => {
+                        ctx.db
+                            .get_user_rolls(room_id, thread_id, ctx.username, amount)
+                    }
+                    HistoryScope::User(user) => {
+                        ctx.db.get_user_rolls(room_id, thread_id, user, amount)
+                    }
+                    HistoryScope::Room => ctx.db.get_room_rolls(room_id, thread_id, amount),
+                }
+                .await?;
+
+                fmt_show(&records, scope)
+            }
+            HistoryCommand::Export(format, hours) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Clock has gone backwards")
+                    .as_secs();
+
+                // One roll more than can be exported is fetched, so the
+                // export knows that older rolls were left out.
+                let hours = (*hours).min(MAX_EXPORT_HOURS);
+                let since = i64::try_from(now)? - i64::from(hours) * 60 * 60;
+                let limit = u32::try_from(MAX_EXPORT_ROLLS + 1)?;
+                let records = ctx.db.get_room_rolls_since(room_id, since, limit).await?;
+                export(&records, *format)?
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RollSummary;
+
+    fn record(command: &str, outcome: Option<&str>) -> RollRecord {
+        RollRecord {
+            room_id: "myroom".to_string(),
+            user_id: "@user:example.com".to_string(),
+            command: command.to_string(),
+            result: RollSummary {
+                expression: "2d6".to_string(),
+                dice: vec![3, 4],
+                total: 7,
+                outcome: outcome.map(str::to_owned),
+            },
+            timestamp: 100,
+            fair_session: None,
+        }
+    }
+
+    #[test]
+    fn parse_history_arguments_test() {
+        use HistoryCommand::*;
+        let parse = |input: &str| HistoryCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse(""), Some(Show(HistoryScope::Own, 10)));
+        assert_eq!(parse("5"), Some(Show(HistoryScope::Own, 5)));
+        assert_eq!(parse("room 3"), Some(Show(HistoryScope::Room, 3)));
+        assert_eq!(
+            parse("@other:example.com"),
+            Some(Show(
+                HistoryScope::User("@other:example.com".to_string()),
+                10
+            ))
+        );
+        assert_eq!(parse("export csv"), Some(Export(ExportFormat::Csv, 6)));
+        assert_eq!(parse("export JSON 2"), Some(Export(ExportFormat::Json, 2)));
+    }
+
+    #[test]
+    fn parse_invalid_history_arguments_test() {
+        assert!(HistoryCommand::try_from("export xml".to_string()).is_err());
+        assert!(HistoryCommand::try_from("abc".to_string()).is_err());
+        assert!(HistoryCommand::try_from("room 1 2".to_string()).is_err());
+    }
+
+    #[test]
+    fn csv_export_quotes_fields_test() {
+        let csv = export_csv(&[record("!roll 1d4, 2d6", Some("said \"hi\""))]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"100,@user:example.com,"!roll 1d4, 2d6",2d6,3 4,7,"said ""hi""","#
+        );
+    }
+
+    #[test]
+    fn json_export_round_trips_test() {
+        let records = vec![record("!roll 2d6", None)];
+        let json = serde_json::to_string_pretty(&records).unwrap();
+        let parsed: Vec<RollRecord> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, records);
+    }
+
+    #[test]
+    fn history_escapes_user_text_test() {
+        let records = [record("!r 1d20 </code><a href=x>", None)];
+        let html = fmt_show(&records, &HistoryScope::Room);
+        assert!(html.contains("!r 1d20 &lt;/code&gt;&lt;a href=x&gt;: 7"));
+
+        let exported = export(&records, ExportFormat::Csv).unwrap();
+        assert!(exported.contains("!r 1d20 &lt;/code&gt;&lt;a href=x&gt;"));
+        assert!(!exported.contains("<a href"));
+    }
+
+    #[test]
+    fn export_is_cut_off_to_fit_test() {
+        let records = vec![record("!roll 2d6", None); MAX_EXPORT_ROLLS + 1];
+        let exported = export(&records, ExportFormat::Csv).unwrap();
+        assert!(exported.contains("the last 100 rolls, older rolls do not fit"));
+
+        let long_command = format!("!roll 2d6 {}", "x".repeat(1000));
+        let records = vec![record(&long_command, None); 50];
+        let exported = export(&records, ExportFormat::Json).unwrap();
+        assert!(exported.len() < MAX_EXPORT_BYTES + 200);
+        assert!(exported.contains("older rolls do not fit"));
+
+        let exported = export(&records[..2], ExportFormat::Json).unwrap();
+        assert!(exported.contains("Roll history export (2 rolls):"));
+    }
+
+    #[test]
+    fn history_names_fair_session_test() {
+        let mut fair = record("!roll 2d6", None);
+        fair.fair_session = Some(2);
+
+        let html = fmt_show(&[fair.clone()], &HistoryScope::Own);
+        assert!(html.contains("!roll 2d6: 7 (fair session 2)"));
+        assert!(export_csv(&[fair]).ends_with(",7,,2"));
+    }
+}
@@ -0,0 +1,336 @@
+use super::parser::split_command;
+use super::{
+    escape_html, execution_allowed, parse_in_room, Command, CommandError, Execution,
+    ExecutionResult, Permission,
+};
+use crate::context::Context;
+use crate::db::errors::DataError;
+use crate::db::Macros;
+use crate::error::BotError;
+use crate::parser::variables::{normalize_variable_name, parse_variable_name};
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// Longest macro body that can be stored.
+pub const MAX_MACRO_LENGTH: usize = 500;
+
+/// Longest command a macro may expand to, including its arguments.
+pub const MAX_EXPANDED_LENGTH: usize = 2000;
+
+/// Most macros a user (or a room) can have in a room.
+pub const MAX_MACROS: i64 = 50;
+
+/// How many macros may run each other before giving up.
+pub const MAX_MACRO_DEPTH: usize = 5;
+
+/// Name of the command that runs macros.
+const RUN_MACRO: &str = "m";
+
+fn invalid(message: impl Into<String>) -> BotError {
+    CommandError::InvalidCommand(message.into()).into()
+}
+
+/// Fill in the arguments of a macro body. $1 to $9 are replaced by
+/// the positional arguments, and $* by all of them.
+pub fn expand_macro(body: &str, args: &[String]) -> Result<String, BotError> {
+    let mut expanded = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('$', Some('*')) => {
+                chars.next();
+                expanded.push_str(&args.join(" "));
+            }
+            ('$', Some(digit @ '1'..='9')) => {
+                let position = digit.to_digit(10).unwrap_or_default() as usize;
+                chars.next();
+
+                match args.get(position - 1) {
+                    Some(arg) => expanded.push_str(arg),
+                    None => return Err(invalid(format!("macro needs argument ${}", position))),
+                }
+            }
+            _ => expanded.push(c),
+        }
+    }
+
+    match expanded.len() {
+        len if len > MAX_EXPANDED_LENGTH => Err(invalid("macro expands to a command too long")),
+        _ => Ok(expanded),
+    }
+}
+
+/// Store, list or delete macros.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroCommand {
+    List,
+    Set(String, String),
+    Delete(String),
+    SetRoom(String, String),
+    DeleteRoom(String),
+}
+
+fn macro_definition(input: &str) -> Result<(String, String), BotError> {
+    let (name, body) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let name = parse_variable_name(name)?;
+    let body = body.trim();
+
+    if !body.starts_with('!') {
+        Err(invalid("macro must be a command starting with !"))
+    } else if body.len() > MAX_MACRO_LENGTH {
+        Err(invalid(format!(
+            "macro is longer than {} characters",
+            MAX_MACRO_LENGTH
+        )))
+    } else {
+        Ok((name, body.to_owned()))
+    }
+}
+
+impl TryFrom<String> for MacroCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        use MacroCommand::*;
+        let input = input.trim();
+        let (subcommand, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let rest = rest.trim();
+
+        let command = match subcommand.to_lowercase().as_ref() {
+            "" | "list" => List,
+            "set" => macro_definition(rest).map(|(name, body)| Set(name, body))?,
+            "del" | "delete" => Delete(parse_variable_name(rest)?),
+            "roomset" => macro_definition(rest).map(|(name, body)| SetRoom(name, body))?,
+            "roomdel" => DeleteRoom(parse_variable_name(rest)?),
+            _ => return Err(invalid(input)),
+        };
+
+        Ok(command)
+    }
+}
+
+#[async_trait]
+impl Command for MacroCommand {
+    fn name(&self) -> &'static str {
+        "manage macros"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        match self {
+            MacroCommand::SetRoom(..) | MacroCommand::DeleteRoom(_) => Permission::Moderator,
+            _ => Permission::Anyone,
+        }
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        use MacroCommand::*;
+        let room_id = ctx.active_room_id().as_str();
+
+        // Room macros are stored without a user.
+        let user = match self {
+            SetRoom(..) | DeleteRoom(_) => None,
+            _ => Some(ctx.username),
+        };
+
+        let html = match self {
+            List => {
+                let macros = ctx.db.get_macros(ctx.username, room_id).await?;
+                let list: Vec<String> = macros
+                    .into_iter()
+                    .map(|m| {
+                        let scope = if m.room { " (room)" } else { "" };
+                        format!(" - {}{}: {}", m.name, scope, escape_html(&m.body))
+                    })
+                    .collect();
+
+                match list.is_empty() {
+                    true => "<strong>Macros:</strong> none".to_string(),
+                    false => format!("<strong>Macros:</strong><br/>{}", list.join("<br/>")),
+                }
+            }
+            Set(name, body) | SetRoom(name, body) => {
+                let exists = ctx
+                    .db
+                    .get_macros(ctx.username, room_id)
+                    .await?
+                    .iter()
+                    .any(|m| m.room == user.is_none() && m.name == normalize_variable_name(name));
+
+                if !exists && ctx.db.count_macros(user, room_id).await? >= MAX_MACROS {
+                    return Err(DataError::TooManyEntries.into());
+                }
+
+                ctx.db.set_macro(user, room_id, name, body).await?;
+                format!(
+                    "<strong>Set Macro:</strong> {}: {}",
+                    name,
+                    escape_html(body)
+                )
+            }
+            Delete(name) | DeleteRoom(name) => {
+                match ctx.db.delete_macro(user, room_id, name).await {
+                    Ok(()) => format!("<strong>Deleted Macro:</strong> {}", name),
+                    Err(DataError::KeyDoesNotExist(_)) => format!("{} does not exist", name),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+/// Run a macro by name, with positional arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMacroCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl TryFrom<String> for RunMacroCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let mut words = input.split_whitespace();
+        let name = parse_variable_name(words.next().unwrap_or_default())?;
+        let args = words.map(|word| word.to_owned()).collect();
+        Ok(RunMacroCommand { name, args })
+    }
+}
+
+#[async_trait]
+impl Command for RunMacroCommand {
+    fn name(&self) -> &'static str {
+        "run macro"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let room_id = ctx.active_room_id().as_str();
+        let mut run = self.clone();
+
+        // A macro that runs another macro is expanded in place, rather
+        // than executed recursively.
+        for _ in 0..MAX_MACRO_DEPTH {
+            let found = match ctx.db.get_macro(ctx.username, room_id, &run.name).await {
+                Ok(found) => found,
+                Err(DataError::KeyDoesNotExist(_)) => {
+                    return Execution::success(format!("macro {} does not exist", run.name))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let expanded = expand_macro(&found.body, &run.args)?;
+
+            match split_command(&expanded) {
+                Ok((command, input)) if command == RUN_MACRO => {
+                    run = RunMacroCommand::try_from(input)?;
+                }
+                _ => {
+                    let cmd = parse_in_room(&expanded, ctx).await?;
+                    let ctx = Context {
+                        message_body: &expanded,
+                        ..ctx.clone()
+                    };
+
+                    execution_allowed(cmd.as_ref(), &ctx).await?;
+                    return cmd.execute(&ctx).await;
+                }
+            }
+        }
+
+        Err(invalid(format!(
+            "macros can run at most {} levels deep",
+            MAX_MACRO_DEPTH
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &str) -> Vec<String> {
+        input.split_whitespace().map(|s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn expand_positional_arguments_test() {
+        let expanded = expand_macro("!pool dexterity + firearms + $1", &args("2"));
+        assert_eq!(expanded.unwrap(), "!pool dexterity + firearms + 2");
+
+        let expanded = expand_macro("!r $2d$1 $*", &args("6 3"));
+        assert_eq!(expanded.unwrap(), "!r 3d6 6 3");
+
+        let expanded = expand_macro("!r 1d20 + $", &args(""));
+        assert_eq!(expanded.unwrap(), "!r 1d20 + $");
+    }
+
+    #[test]
+    fn expand_missing_argument_test() {
+        assert!(expand_macro("!r 1d20 + $2", &args("1")).is_err());
+    }
+
+    #[test]
+    fn expand_size_limit_test() {
+        let long = vec!["x".repeat(MAX_EXPANDED_LENGTH)];
+        assert!(expand_macro("!r $1", &long).is_err());
+    }
+
+    #[test]
+    fn parse_macro_commands_test() {
+        use MacroCommand::*;
+        let parse = |input: &str| MacroCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse(""), Some(List));
+        assert_eq!(
+            parse("set shoot !pool dexterity + firearms"),
+            Some(Set(
+                "shoot".to_string(),
+                "!pool dexterity + firearms".to_string()
+            ))
+        );
+        assert_eq!(parse("del shoot"), Some(Delete("shoot".to_string())));
+        assert_eq!(
+            parse("roomset init !r 1d20"),
+            Some(SetRoom("init".to_string(), "!r 1d20".to_string()))
+        );
+        assert_eq!(parse("roomdel init"), Some(DeleteRoom("init".to_string())));
+
+        assert_eq!(parse("set shoot pool 3"), None);
+        assert_eq!(parse("set 2shoot !pool 3"), None);
+        assert_eq!(
+            parse(&format!("set x !{}", "r".repeat(MAX_MACRO_LENGTH))),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_run_macro_test() {
+        let run = RunMacroCommand::try_from("shoot 2 3".to_string()).unwrap();
+        assert_eq!(run.name, "shoot");
+        assert_eq!(run.args, args("2 3"));
+        assert!(RunMacroCommand::try_from("".to_string()).is_err());
+    }
+
+    #[test]
+    fn room_macros_need_moderator_test() {
+        let permission = |input: &str| {
+            MacroCommand::try_from(input.to_string())
+                .unwrap()
+                .permission()
+        };
+
+        assert_eq!(permission("set init !r 1d20"), Permission::Anyone);
+        assert_eq!(permission("roomset init !r 1d20"), Permission::Moderator);
+        assert_eq!(permission("roomdel init"), Permission::Moderator);
+    }
+}
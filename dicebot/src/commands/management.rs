@@ -1,4 +1,4 @@
-use super::{Command, Execution, ExecutionResult};
+use super::{Command, Execution, ExecutionResult, Permission};
 use crate::db::Users;
 use crate::error::BotError::{AccountDoesNotExist, PasswordCreationError};
 use crate::logic::hash_password;
@@ -68,6 +68,10 @@ impl Command for UnlinkCommand {
         true
     }
 
+    fn permission(&self) -> Permission {
+        Permission::Registered
+    }
+
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let mut user = ctx
             .db
@@ -105,6 +109,10 @@ impl Command for LinkCommand {
         true
     }
 
+    fn permission(&self) -> Permission {
+        Permission::Registered
+    }
+
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let mut user = ctx
             .db
@@ -186,6 +194,10 @@ impl Command for UnregisterCommand {
         true
     }
 
+    fn permission(&self) -> Permission {
+        Permission::Registered
+    }
+
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let user = ctx.db.get_user(&ctx.username).await?;
 
@@ -1,16 +1,33 @@
 use crate::context::Context;
+use crate::db::{RollHistory, RoomSettings};
 use crate::error::BotError;
+use crate::logic;
+use crate::matrix;
+use crate::models::{AccountStatus, GameSystem, RollResult, RollSummary, RoomSetting};
+use crate::templates;
 use async_trait::async_trait;
 use log::{error, info};
+use matrix_sdk::identifiers::UserId;
+use std::convert::TryFrom;
+use std::fmt;
 use thiserror::Error;
 
+pub mod admin;
 pub mod basic_rolling;
+pub mod characters;
 pub mod cofd;
 pub mod cthulhu;
+pub mod fairness;
+pub mod history;
+pub mod macros;
 pub mod management;
 pub mod misc;
 pub mod parser;
+pub mod probability;
+pub mod room_settings;
+pub mod room_variables;
 pub mod rooms;
+pub mod sheets;
 pub mod variables;
 
 /// A custom error type specifically related to parsing command text.
@@ -25,6 +42,43 @@ pub enum CommandError {
 
     #[error("ignored command")]
     IgnoredCommand,
+
+    #[error("only {0} can execute this command")]
+    InsufficientPermissions(Permission),
+}
+
+/// Power level of room moderators. Matrix clients give moderators
+/// this level by default.
+const MODERATOR_POWER_LEVEL: i64 = 50;
+
+/// Who is allowed to execute a command. Each level includes the
+/// levels below it, and bot admins may execute every command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Anyone who can send messages to the bot.
+    Anyone,
+
+    /// Users who registered an account with the bot.
+    Registered,
+
+    /// Users with at least moderator power level in the room.
+    Moderator,
+
+    /// Users listed as bot admins in the config.
+    Admin,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let users = match self {
+            Permission::Anyone => "users",
+            Permission::Registered => "registered users",
+            Permission::Moderator => "room moderators",
+            Permission::Admin => "bot admins",
+        };
+
+        write!(f, "{}", users)
+    }
 }
 
 /// A successfully executed command returns a message to be sent back
@@ -33,19 +87,78 @@ pub enum CommandError {
 #[derive(Debug)]
 pub struct Execution {
     html: String,
+    roll: Option<RollResult>,
+
+    /// The fair mode session whose secret seeded the dice of the roll.
+    fair_session: Option<i64>,
 }
 
 impl Execution {
     pub fn success(html: String) -> ExecutionResult {
-        Ok(Execution { html })
+        Ok(Execution {
+            html,
+            roll: None,
+            fair_session: None,
+        })
+    }
+
+    /// A successful dice roll for a game system. The summary is
+    /// recorded in the roll history, and rooms with a roll template
+    /// for the system get the template rendered instead of the HTML.
+    pub fn roll(html: String, system: GameSystem, summary: RollSummary) -> ExecutionResult {
+        Ok(Execution {
+            html,
+            roll: Some(RollResult { system, summary }),
+            fair_session: None,
+        })
+    }
+
+    /// Mark a roll as made with dice seeded by a fair mode session.
+    /// Executions that did not roll dice are left alone.
+    fn in_fair_session(self, fair_session: Option<i64>) -> Execution {
+        match self.roll {
+            Some(_) => Execution {
+                fair_session,
+                ..self
+            },
+            None => self,
+        }
     }
 
-    /// Response message in HTML.
+    /// Response message in HTML. Fair mode rolls name their session,
+    /// so they can be verified once its secret is revealed.
     pub fn html(&self) -> String {
-        self.html.clone()
+        match self.fair_session {
+            Some(session) => format!("{}<br/><em>Fair roll, session {}</em>", self.html, session),
+            None => self.html.clone(),
+        }
+    }
+
+    /// The fair mode session that seeded the dice, if the command
+    /// rolled dice in fair mode.
+    pub fn fair_session(&self) -> Option<i64> {
+        self.fair_session
+    }
+
+    /// Structured summary of the roll, if the command rolled dice.
+    pub fn roll_summary(&self) -> Option<&RollSummary> {
+        self.roll.as_ref().map(|roll| &roll.summary)
+    }
+
+    /// Structured result of the roll, if the command rolled dice.
+    pub fn roll_result(&self) -> Option<&RollResult> {
+        self.roll.as_ref()
     }
 }
 
+/// Escape user-provided text for inclusion in an HTML response.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// Wraps either a successful command execution response, or an error
 /// that occurred.
 pub type ExecutionResult = Result<Execution, BotError>;
@@ -96,36 +209,171 @@ pub trait Command: Send + Sync {
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult;
     fn name(&self) -> &'static str;
     fn is_secure(&self) -> bool;
+
+    /// Who may execute the command. Anyone, unless the command says
+    /// otherwise.
+    fn permission(&self) -> Permission {
+        Permission::Anyone
+    }
+
+    /// Whether the command does nothing but roll dice. Only such
+    /// commands are rolled again by reacting to their reply.
+    fn is_roll(&self) -> bool {
+        false
+    }
 }
 
-/// Determine if we are allowed to execute this command. Currently the
-/// rules are that secure commands must be executed in secure rooms
-/// (encrypted + direct), and anything else can be executed where
-/// ever. Later, we can add stuff like admin/regular user power
-/// separation, etc.
-fn execution_allowed(cmd: &(impl Command + ?Sized), ctx: &Context<'_>) -> Result<(), CommandError> {
-    match cmd {
-        cmd if cmd.is_secure() && ctx.is_secure() => Ok(()),
-        cmd if cmd.is_secure() && !ctx.is_secure() => Err(CommandError::InsecureExecution),
-        _ => Ok(()),
+/// Check that the user executing a command has the given
+/// permission. Moderator power levels are checked in the active room.
+async fn ensure_permission(ctx: &Context<'_>, permission: Permission) -> Result<(), BotError> {
+    let allowed = match permission {
+        _ if ctx.admin => true,
+        Permission::Anyone => true,
+        Permission::Registered => ctx.account.account_status() == AccountStatus::Registered,
+        Permission::Moderator => {
+            let user_id = UserId::try_from(ctx.username)?;
+            let power_level =
+                matrix::get_power_level(&ctx.matrix_client, ctx.active_room_id(), &user_id).await?;
+
+            power_level >= MODERATOR_POWER_LEVEL
+        }
+        Permission::Admin => false,
+    };
+
+    match allowed {
+        true => Ok(()),
+        false => Err(CommandError::InsufficientPermissions(permission).into()),
+    }
+}
+
+/// Determine if we are allowed to execute this command. Secure
+/// commands must be executed in secure rooms (encrypted + direct),
+/// and the user must have the permission the command asks for.
+async fn execution_allowed(
+    cmd: &(impl Command + ?Sized),
+    ctx: &Context<'_>,
+) -> Result<(), BotError> {
+    if cmd.is_secure() && !ctx.is_secure() {
+        return Err(CommandError::InsecureExecution.into());
     }
+
+    ensure_permission(ctx, cmd.permission()).await
 }
 
 /// Attempt to execute a command, and return the content that should
 /// go back to Matrix, if the command was executed, whether or not the
 /// command was successful.
 pub async fn execute_command(ctx: &Context<'_>) -> ExecutionResult {
-    let cmd = parser::parse_command(&ctx.message_body)?;
+    // Replies have the verbosity of the room, unless the command asks
+    // for another with a flag.
+    let (message, verbosity) = parser::take_verbosity_flag(ctx.message_body);
+    let verbosity = match verbosity {
+        Some(verbosity) => verbosity,
+        None => logic::room_verbosity(&ctx.db, ctx.active_room_id().as_str()).await?,
+    };
+
+    let ctx = &Context {
+        message_body: &message,
+        verbosity,
+        ..ctx.clone()
+    };
+
+    let cmd = parse_in_room(ctx.message_body, ctx).await?;
 
-    let result = match execution_allowed(cmd.as_ref(), ctx) {
+    let result = match execution_allowed(cmd.as_ref(), ctx).await {
         Ok(_) => cmd.execute(ctx).await,
-        Err(e) => Err(e.into()),
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(execution) => {
+            record_roll(ctx, &execution).await;
+            let execution = apply_roll_template(ctx, execution).await;
+            Ok(execution.in_fair_session(ctx.rng.fair_session()))
+        }
+        Err(e) => Err(e),
     };
 
     log_command(cmd.as_ref(), ctx, &result);
     result
 }
 
+/// The name of the command that a message would execute in the
+/// active room, if the message is a valid command.
+pub async fn command_name(ctx: &Context<'_>) -> Option<&'static str> {
+    let (message, _) = parser::take_verbosity_flag(ctx.message_body);
+    parse_in_room(&message, ctx)
+        .await
+        .ok()
+        .map(|cmd| cmd.name())
+}
+
+/// Parse a command, with !r rolling dice for the default game system
+/// of the room.
+pub(super) async fn parse_in_room(
+    input: &str,
+    ctx: &Context<'_>,
+) -> Result<Box<dyn Command>, BotError> {
+    let room_id = ctx.active_room_id().as_str();
+    let system = logic::room_game_system(&ctx.db, room_id).await?;
+    parser::parse_command_in_system(input, system)
+}
+
+/// Store the roll of a successfully executed command in the roll
+/// history, if it rolled dice. A failure to record the roll is
+/// logged, but does not fail the command.
+async fn record_roll(ctx: &Context<'_>, execution: &Execution) {
+    if let Some(roll) = execution.roll_summary() {
+        let room_id = ctx.room_id().as_str();
+        let command = ctx.message_body.trim();
+
+        if let Err(e) = ctx
+            .db
+            .record_roll(
+                room_id,
+                ctx.thread_id,
+                ctx.username,
+                command,
+                roll,
+                ctx.rng.fair_session(),
+            )
+            .await
+        {
+            error!("Could not record roll in history: {}", e);
+        }
+    }
+}
+
+/// Render the reply to a roll with the roll template the room has
+/// for its game system, if there is one. A template that cannot be
+/// looked up or rendered is logged, and the built-in reply is kept.
+async fn apply_roll_template(ctx: &Context<'_>, mut execution: Execution) -> Execution {
+    let roll = match &execution.roll {
+        Some(roll) => roll,
+        None => return execution,
+    };
+
+    let room_id = ctx.active_room_id().as_str();
+    let setting = RoomSetting::RollTemplate(roll.system);
+
+    let rendered: Result<Option<String>, BotError> =
+        match ctx.db.get_room_setting(room_id, setting).await {
+            Ok(Some(template)) => templates::render(&template, ctx.username, roll)
+                .map(Some)
+                .map_err(|e| e.into()),
+            Ok(None) => Ok(None),
+            Err(e) => Err(e.into()),
+        };
+
+    match rendered {
+        Ok(Some(html)) => execution.html = html,
+        Ok(None) => (),
+        Err(e) => error!("Could not render roll template: {}", e),
+    }
+
+    execution
+}
+
 /// Log result of an executed command.
 fn log_command(cmd: &(impl Command + ?Sized), ctx: &Context, result: &ExecutionResult) {
     use substring::Substring;
@@ -160,6 +408,8 @@ fn log_command(cmd: &(impl Command + ?Sized), ctx: &Context, result: &ExecutionR
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::Verbosity;
+    use crate::rng::DiceRng;
     use management::RegisterCommand;
     use url::Url;
 
@@ -200,10 +450,14 @@ mod tests {
             active_room: secure_room!(),
             username: "myusername",
             message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let cmd = RegisterCommand;
-        assert_eq!(execution_allowed(&cmd, &ctx).is_ok(), true);
+        assert!(execution_allowed(&cmd, &ctx).await.is_ok());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -223,10 +477,14 @@ mod tests {
             active_room: secure_room!(),
             username: "myusername",
             message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let cmd = variables::GetVariableCommand("".to_owned());
-        assert_eq!(execution_allowed(&cmd, &ctx).is_ok(), true);
+        assert!(execution_allowed(&cmd, &ctx).await.is_ok());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -246,10 +504,14 @@ mod tests {
             active_room: dummy_room!(),
             username: "myusername",
             message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let cmd = variables::GetVariableCommand("".to_owned());
-        assert_eq!(execution_allowed(&cmd, &ctx).is_ok(), true);
+        assert!(execution_allowed(&cmd, &ctx).await.is_ok());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -269,10 +531,77 @@ mod tests {
             active_room: dummy_room!(),
             username: "myusername",
             message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let cmd = RegisterCommand;
-        assert_eq!(execution_allowed(&cmd, &ctx).is_err(), true);
+        assert!(execution_allowed(&cmd, &ctx).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn unregistered_user_denied_registered_command() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        let db = crate::db::sqlite::Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+
+        let ctx = Context {
+            account: crate::models::Account::default(),
+            db,
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: secure_room!(),
+            active_room: secure_room!(),
+            username: "myusername",
+            message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        let cmd = management::UnregisterCommand;
+        let result = execution_allowed(&cmd, &ctx).await;
+        assert!(matches!(
+            result,
+            Err(BotError::CommandError(
+                CommandError::InsufficientPermissions(Permission::Registered)
+            ))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn bot_admin_allows_execution() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        let db = crate::db::sqlite::Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+
+        let ctx = Context {
+            account: crate::models::Account::default(),
+            db,
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: dummy_room!(),
+            active_room: dummy_room!(),
+            username: "@admin:example.com",
+            message_body: "!notacommand",
+            thread_id: None,
+            admin: true,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        let cmd = room_variables::DeleteRoomVariableCommand("x".to_owned());
+        assert!(execution_allowed(&cmd, &ctx).await.is_ok());
+
+        let cmd = management::RegisterCommand;
+        assert!(execution_allowed(&cmd, &ctx).await.is_err());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -292,9 +621,56 @@ mod tests {
             active_room: dummy_room!(),
             username: "myusername",
             message_body: "!notacommand",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let result = execute_command(&ctx).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn roll_template_replaces_roll_reply() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        let db = crate::db::sqlite::Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+
+        let ctx = Context {
+            account: crate::models::Account::default(),
+            db,
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: dummy_room!(),
+            active_room: dummy_room!(),
+            username: "myusername",
+            message_body: "!roll 3",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        let room_id = ctx.active_room_id().as_str();
+        let setting = RoomSetting::RollTemplate(GameSystem::Basic);
+        ctx.db
+            .set_room_setting(room_id, setting, Some("{user} got <b>{total}</b>"))
+            .await
+            .expect("Could not set roll template");
+
+        let result = execute_command(&ctx).await.expect("Roll failed");
+        assert_eq!(result.html(), "myusername got <b>3</b>");
+
+        // Other game systems keep the built-in reply.
+        let ctx = Context {
+            message_body: "!cthroll 50",
+            ..ctx.clone()
+        };
+
+        let result = execute_command(&ctx).await.expect("Roll failed");
+        assert!(result.html().starts_with("<strong>Roll:</strong>"));
+    }
 }
@@ -4,18 +4,32 @@
  * axfive-matrix-dicebot project.
  */
 use crate::commands::{
-    basic_rolling::RollCommand,
+    admin::AdminCommand,
+    basic_rolling::{RollCommand, RollVariableCommand},
+    characters::CharacterCommand,
     cofd::PoolRollCommand,
     cthulhu::{CthAdvanceRoll, CthRoll},
+    fairness::FairnessCommand,
+    history::HistoryCommand,
+    macros::{MacroCommand, RunMacroCommand},
     management::{CheckCommand, LinkCommand, RegisterCommand, UnlinkCommand, UnregisterCommand},
     misc::HelpCommand,
+    probability::ProbabilityCommand,
+    room_settings::{ConfigCommand, PrefixCommand, TemplateCommand},
+    room_variables::{
+        DeleteRoomVariableCommand, GetAllRoomVariablesCommand, GetRoomVariableCommand,
+        SetRoomVariableCommand,
+    },
     rooms::{ListRoomsCommand, SetRoomCommand},
+    sheets::{ExportSheetCommand, ImportSheetCommand},
     variables::{
         DeleteVariableCommand, GetAllVariablesCommand, GetVariableCommand, SetVariableCommand,
     },
     Command,
 };
 use crate::error::BotError;
+use crate::models::{GameSystem, Verbosity};
+use crate::parser::variables::is_valid_variable_name;
 use combine::parser::char::{char, letter, space};
 use combine::{any, many1, optional, Parser};
 use std::convert::TryFrom;
@@ -34,7 +48,7 @@ pub enum CommandParsingError {
 /// else" parts. Extracts the command separately from its input (i.e.
 /// rest of the line) and returns a tuple of (command_input, command).
 /// Whitespace at the start and end of the command input is removed.
-fn split_command(input: &str) -> Result<(String, String), CommandParsingError> {
+pub(super) fn split_command(input: &str) -> Result<(String, String), CommandParsingError> {
     let input = input.trim();
 
     let exclamation = char('!');
@@ -61,6 +75,31 @@ fn split_command(input: &str) -> Result<(String, String), CommandParsingError> {
     Ok((command, command_input))
 }
 
+/// Take a verbosity flag off a command, where it comes right after
+/// the command name, like "!r --compact 1d20". Returns the command
+/// without the flag, and the verbosity the flag asked for.
+pub(super) fn take_verbosity_flag(input: &str) -> (String, Option<Verbosity>) {
+    let input = input.trim();
+    let (command, rest) = match input.split_once(char::is_whitespace) {
+        Some(split) => split,
+        None => return (input.to_owned(), None),
+    };
+
+    let rest = rest.trim_start();
+    let (flag, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let verbosity = flag
+        .strip_prefix("--")
+        .and_then(|flag| Verbosity::from_key(&flag.to_lowercase()));
+
+    match verbosity {
+        Some(_) => {
+            let command = format!("{} {}", command, rest.trim_start());
+            (command.trim_end().to_owned(), verbosity)
+        }
+        None => (input.to_owned(), None),
+    }
+}
+
 /// Atempt to convert text input to a Boxed command type. Shortens
 /// boilerplate.
 macro_rules! convert_to {
@@ -69,21 +108,76 @@ macro_rules! convert_to {
     };
 }
 
+/// Parse the input of !roll: a dice expression, or the name of a
+/// stored one.
+fn parse_roll(cmd_input: String) -> Result<Box<dyn Command>, BotError> {
+    convert_to!(RollCommand, cmd_input.clone()).or_else(|e| {
+        // Not a dice expression, but it could name a stored one.
+        match is_valid_variable_name(cmd_input.trim()) {
+            true => convert_to!(RollVariableCommand, cmd_input),
+            false => Err(e),
+        }
+    })
+}
+
 /// Potentially parse a command expression. If we recognize the
 /// command, an error should be raised if the command is misparsed. If
 /// we don't recognize the command, return an error.
 pub fn parse_command(input: &str) -> Result<Box<dyn Command>, BotError> {
+    parse_command_in_system(input, GameSystem::Basic)
+}
+
+/// Whether a command does nothing but roll dice in the given game
+/// system. A verbosity flag only changes the reply, so a command with
+/// one is a roll if it is a roll without it.
+pub fn is_roll_in_system(input: &str, system: GameSystem) -> bool {
+    let (input, _) = take_verbosity_flag(input);
+    parse_command_in_system(&input, system).map_or(false, |command| command.is_roll())
+}
+
+/// Parse a command expression like parse_command, with !r rolling
+/// dice for the given game system.
+pub fn parse_command_in_system(
+    input: &str,
+    system: GameSystem,
+) -> Result<Box<dyn Command>, BotError> {
     match split_command(input) {
         Ok((cmd, cmd_input)) => match cmd.to_lowercase().as_ref() {
             "variables" => convert_to!(GetAllVariablesCommand, cmd_input),
             "get" => convert_to!(GetVariableCommand, cmd_input),
             "set" => convert_to!(SetVariableCommand, cmd_input),
+            "inc" => SetVariableCommand::increment(cmd_input)
+                .map(|cmd| Box::new(cmd) as Box<dyn Command>),
+            "dec" => SetVariableCommand::decrement(cmd_input)
+                .map(|cmd| Box::new(cmd) as Box<dyn Command>),
             "del" => convert_to!(DeleteVariableCommand, cmd_input),
-            "r" | "roll" => convert_to!(RollCommand, cmd_input),
+            "import" => convert_to!(ImportSheetCommand, cmd_input),
+            "export" => convert_to!(ExportSheetCommand, cmd_input),
+            "char" | "character" => convert_to!(CharacterCommand, cmd_input),
+            "macro" | "macros" => convert_to!(MacroCommand, cmd_input),
+            "m" => convert_to!(RunMacroCommand, cmd_input),
+            "inline" => convert_to!(ConfigCommand, format!("inline {}", cmd_input)),
+            "prefix" => convert_to!(PrefixCommand, cmd_input),
+            "threads" => convert_to!(ConfigCommand, format!("threads {}", cmd_input)),
+            "config" => convert_to!(ConfigCommand, cmd_input),
+            "template" => convert_to!(TemplateCommand, cmd_input),
+            "roomvars" => convert_to!(GetAllRoomVariablesCommand, cmd_input),
+            "roomget" => convert_to!(GetRoomVariableCommand, cmd_input),
+            "roomset" => convert_to!(SetRoomVariableCommand, cmd_input),
+            "roomdel" => convert_to!(DeleteRoomVariableCommand, cmd_input),
+            "r" => match system {
+                GameSystem::Basic => parse_roll(cmd_input),
+                GameSystem::ChroniclesOfDarkness => convert_to!(PoolRollCommand, cmd_input),
+                GameSystem::CallOfCthulhu => convert_to!(CthRoll, cmd_input),
+            },
+            "roll" => parse_roll(cmd_input),
             "rp" | "pool" => convert_to!(PoolRollCommand, cmd_input),
             "chance" => PoolRollCommand::chance_die().map(|cmd| Box::new(cmd) as Box<dyn Command>),
             "cthroll" => convert_to!(CthRoll, cmd_input),
             "cthadv" | "ctharoll" => convert_to!(CthAdvanceRoll, cmd_input),
+            "prob" => convert_to!(ProbabilityCommand, cmd_input),
+            "history" => convert_to!(HistoryCommand, cmd_input),
+            "fairness" => convert_to!(FairnessCommand, cmd_input),
             "help" => convert_to!(HelpCommand, cmd_input),
             "register" => convert_to!(RegisterCommand, cmd_input),
             "link" => convert_to!(LinkCommand, cmd_input),
@@ -92,6 +186,7 @@ pub fn parse_command(input: &str) -> Result<Box<dyn Command>, BotError> {
             "unregister" => convert_to!(UnregisterCommand, cmd_input),
             "rooms" => convert_to!(ListRoomsCommand, cmd_input),
             "room" => convert_to!(SetRoomCommand, cmd_input),
+            "admin" => convert_to!(AdminCommand, cmd_input),
             _ => Err(CommandParsingError::UnrecognizedCommand(cmd).into()),
         },
         //All other errors passed up.
@@ -211,6 +306,7 @@ mod tests {
     fn roll_malformed_expression_test() {
         assert!(parse_command("!roll 1d20asdlfkj").is_err());
         assert!(parse_command("!roll 1d20asdlfkj   ").is_err());
+        assert!(parse_command("!roll sword + 1").is_err());
     }
 
     #[test]
@@ -233,6 +329,59 @@ mod tests {
         parse_command("   !help stuff   ").expect("was error");
     }
 
+    #[test]
+    fn roll_stored_expression_test() {
+        assert_eq!(
+            parse_command("!r sword").unwrap().name(),
+            "roll stored dice expression"
+        );
+        assert_eq!(
+            parse_command("!r 1d20").unwrap().name(),
+            "roll regular dice"
+        );
+    }
+
+    #[test]
+    fn roll_in_default_system_test() {
+        let parse = |input: &str, system| parse_command_in_system(input, system).unwrap().name();
+
+        assert_eq!(
+            parse("!r 8", GameSystem::ChroniclesOfDarkness),
+            "roll dice pool"
+        );
+        assert_eq!(
+            parse("!r 40", GameSystem::CallOfCthulhu),
+            "roll percentile dice"
+        );
+        assert_eq!(parse("!r 1d20", GameSystem::Basic), "roll regular dice");
+        assert_eq!(
+            parse("!roll 1d20", GameSystem::ChroniclesOfDarkness),
+            "roll regular dice"
+        );
+    }
+
+    #[test]
+    fn take_verbosity_flag_test() {
+        assert_eq!(
+            take_verbosity_flag("!r --compact 1d20"),
+            ("!r 1d20".to_string(), Some(Verbosity::Compact))
+        );
+        assert_eq!(
+            take_verbosity_flag(" !pool --DETAILED 8 "),
+            ("!pool 8".to_string(), Some(Verbosity::Detailed))
+        );
+        assert_eq!(
+            take_verbosity_flag("!chance --normal"),
+            ("!chance".to_string(), Some(Verbosity::Normal))
+        );
+        assert_eq!(
+            take_verbosity_flag("!r 1d20 --compact"),
+            ("!r 1d20 --compact".to_string(), None)
+        );
+        assert_eq!(take_verbosity_flag("!r --loud 1d20").1, None);
+        assert_eq!(take_verbosity_flag("!chance").1, None);
+    }
+
     #[test]
     fn roll_whitespace_test() {
         parse_command("!roll 1d4 + 5d6 -3   ").expect("was error");
@@ -240,6 +389,43 @@ mod tests {
         parse_command("   !roll 1d4 + 5d6 -3   ").expect("was error");
     }
 
+    #[test]
+    fn room_variable_commands_test() {
+        parse_command("!roomvars").expect("was error");
+        parse_command("!roomget #doom").expect("was error");
+        parse_command("!roomset #doom 3").expect("was error");
+        parse_command("!roomset doom -1").expect("was error");
+        parse_command("!roomdel doom").expect("was error");
+        assert!(parse_command("!roomset doom lots").is_err());
+    }
+
+    #[test]
+    fn only_dice_rolls_are_rolls_test() {
+        let is_roll = |input: &str| parse_command(input).map(|command| command.is_roll());
+
+        assert!(is_roll("!r 1d20 + 3").unwrap());
+        assert!(is_roll("!rp 5").unwrap());
+        assert!(is_roll("!cthroll 40").unwrap());
+        assert!(!is_roll("!cthadv 40").unwrap());
+        assert!(!is_roll("!m attack").unwrap());
+        assert!(!is_roll("!set strength 3").unwrap());
+        assert!(!is_roll("!unregister").unwrap());
+    }
+
+    #[test]
+    fn rolls_with_verbosity_flag_are_rolls_test() {
+        assert!(is_roll_in_system("!r --compact 1d20", GameSystem::Basic));
+        assert!(is_roll_in_system(
+            "!r --DETAILED 8",
+            GameSystem::ChroniclesOfDarkness
+        ));
+        assert!(!is_roll_in_system(
+            "!set --compact str 3",
+            GameSystem::Basic
+        ));
+        assert!(!is_roll_in_system("!r --loud 1d20", GameSystem::Basic));
+    }
+
     #[test]
     fn case_insensitive_test() {
         parse_command("!CTHROLL 40").expect("command parsing is not case sensitive.");
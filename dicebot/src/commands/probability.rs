@@ -0,0 +1,145 @@
+use super::basic_rolling::RollCommand;
+use super::{Command, CommandError, Execution, ExecutionResult};
+use crate::basic::dice::ElementExpression;
+use crate::basic::probability::expression_odds;
+use crate::cofd::dice::{DicePool, DicePoolWithContext};
+use crate::cofd::parser::{create_chance_die, parse_dice_pool};
+use crate::cofd::probability::pool_odds;
+use crate::context::Context;
+use crate::cthulhu::dice::{DiceRoll, DiceRollWithContext};
+use crate::cthulhu::parser::parse_regular_roll;
+use crate::cthulhu::probability::regular_roll_odds;
+use crate::error::BotError;
+use crate::probability::{histogram, Odds};
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// What to calculate the odds of. Mirrors the rolling commands.
+pub enum ProbabilityCommand {
+    /// Regular dice, with an optional target the total must meet.
+    Basic(ElementExpression, Option<i32>),
+
+    /// A Chronicles of Darkness dice pool.
+    Pool(DicePool),
+
+    /// A Call of Cthulhu percentile roll.
+    Cthulhu(DiceRoll),
+}
+
+/// Split "2d6+3 >= 10" into the expression and the target.
+fn parse_basic(input: &str) -> Result<ProbabilityCommand, BotError> {
+    let (expression, target) = match input.split_once(">=") {
+        Some((expression, target)) => {
+            let target = target.trim();
+            let target = target.parse::<i32>().map_err(|_| {
+                CommandError::InvalidCommand(format!("not a valid target: {}", target))
+            })?;
+
+            (expression, Some(target))
+        }
+        None => (input, None),
+    };
+
+    let RollCommand(expression) = RollCommand::try_from(expression.trim().to_owned())?;
+    Ok(ProbabilityCommand::Basic(expression, target))
+}
+
+impl TryFrom<String> for ProbabilityCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let input = input.trim();
+        let (system, rest) = input.split_once(' ').unwrap_or((input, ""));
+
+        let command = match system.to_lowercase().as_ref() {
+            "" => return Err(CommandError::InvalidCommand(input.to_owned()).into()),
+            "rp" | "pool" => ProbabilityCommand::Pool(parse_dice_pool(rest.trim())?),
+            "chance" => ProbabilityCommand::Pool(create_chance_die()?),
+            "cth" | "cthroll" => ProbabilityCommand::Cthulhu(parse_regular_roll(rest.trim())?),
+            _ => parse_basic(input)?,
+        };
+
+        Ok(command)
+    }
+}
+
+fn fmt_odds(odds: &Odds) -> String {
+    let mut statistics: Vec<String> = odds
+        .statistics
+        .iter()
+        .map(|(name, value)| format!("<strong>{}:</strong> {}", name, value))
+        .collect();
+
+    if let Some(rolls) = odds.simulated_rolls {
+        statistics.push(format!(
+            "<em>Estimated from {} simulated rolls.</em>",
+            rolls
+        ));
+    }
+
+    format!(
+        "<strong>Odds:</strong> {}</p><p>{}</p><pre><code>{}</code></pre>",
+        odds.description,
+        statistics.join("<br/>"),
+        histogram(&odds.histogram)
+    )
+}
+
+#[async_trait]
+impl Command for ProbabilityCommand {
+    fn name(&self) -> &'static str {
+        "calculate roll probabilities"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let odds = match self {
+            ProbabilityCommand::Basic(expression, target) => {
+                expression_odds(expression, *target, &ctx.rng)?
+            }
+            ProbabilityCommand::Pool(pool) => pool_odds(&DicePoolWithContext(pool, ctx)).await?,
+            ProbabilityCommand::Cthulhu(roll) => {
+                regular_roll_odds(&DiceRollWithContext(roll, ctx)).await?
+            }
+        };
+
+        Execution::success(fmt_odds(&odds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_probability_systems_test() {
+        let parse = |input: &str| ProbabilityCommand::try_from(input.to_string());
+
+        assert!(matches!(
+            parse("2d6+3"),
+            Ok(ProbabilityCommand::Basic(_, None))
+        ));
+        assert!(matches!(
+            parse("1d20 + 5 >= 15"),
+            Ok(ProbabilityCommand::Basic(_, Some(15)))
+        ));
+        assert!(matches!(parse("pool n:8"), Ok(ProbabilityCommand::Pool(_))));
+        assert!(matches!(parse("chance"), Ok(ProbabilityCommand::Pool(_))));
+        assert!(matches!(
+            parse("cth bb:50"),
+            Ok(ProbabilityCommand::Cthulhu(_))
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_probability_test() {
+        let parse = |input: &str| ProbabilityCommand::try_from(input.to_string());
+
+        assert!(parse("").is_err());
+        assert!(parse("1d20 >= abc").is_err());
+        assert!(parse("pool").is_err());
+    }
+}
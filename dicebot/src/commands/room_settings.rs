@@ -0,0 +1,425 @@
+use super::{escape_html, Command, CommandError, Execution, ExecutionResult, Permission};
+use crate::cofd::dice::DEFAULT_MAX_DISPLAYED_ROLLS;
+use crate::context::Context;
+use crate::db::RoomSettings;
+use crate::error::BotError;
+use crate::models::{GameSystem, ReplyType, RoomSetting, Verbosity};
+use crate::templates;
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// Longest command prefix a room can set.
+pub const MAX_PREFIX_LENGTH: usize = 5;
+
+/// Change the command prefix of the room (moderators only), or go
+/// back to the default prefix.
+pub enum PrefixCommand {
+    Show,
+    Set(String),
+    Reset,
+}
+
+impl TryFrom<String> for PrefixCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let prefix = input.trim();
+        let invalid = |reason: String| CommandError::InvalidCommand(reason).into();
+
+        match prefix {
+            "" => Ok(PrefixCommand::Show),
+            "default" => Ok(PrefixCommand::Reset),
+            _ if prefix.chars().count() > MAX_PREFIX_LENGTH => Err(invalid(format!(
+                "prefix can be at most {} characters",
+                MAX_PREFIX_LENGTH
+            ))),
+            _ if prefix
+                .chars()
+                .any(|c| c.is_alphanumeric() || c.is_whitespace()) =>
+            {
+                Err(invalid(
+                    "prefix cannot have letters, digits or spaces".into(),
+                ))
+            }
+            _ => Ok(PrefixCommand::Set(prefix.to_owned())),
+        }
+    }
+}
+
+#[async_trait]
+impl Command for PrefixCommand {
+    fn name(&self) -> &'static str {
+        "command prefix setting"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        match self {
+            PrefixCommand::Show => Permission::Anyone,
+            PrefixCommand::Set(_) | PrefixCommand::Reset => Permission::Moderator,
+        }
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let room_id = ctx.active_room_id().as_str();
+        let setting = RoomSetting::CommandPrefix;
+
+        let prefix = match self {
+            PrefixCommand::Show => ctx.db.get_room_setting(room_id, setting).await?,
+            PrefixCommand::Set(prefix) => {
+                ctx.db
+                    .set_room_setting(room_id, setting, Some(prefix))
+                    .await?;
+                Some(prefix.clone())
+            }
+            PrefixCommand::Reset => {
+                ctx.db.set_room_setting(room_id, setting, None).await?;
+                None
+            }
+        };
+
+        let html = match prefix {
+            Some(prefix) => format!("<strong>Command prefix:</strong> {}", escape_html(&prefix)),
+            None => "<strong>Command prefix:</strong> default".to_string(),
+        };
+
+        Execution::success(html)
+    }
+}
+
+/// The room settings that can be changed with !config, by the name
+/// they have there.
+const CONFIG_SETTINGS: [(&str, RoomSetting); 7] = [
+    ("system", RoomSetting::DefaultSystem),
+    ("verbosity", RoomSetting::Verbosity),
+    ("reply", RoomSetting::ReplyType),
+    ("inline", RoomSetting::InlineRolls),
+    ("threads", RoomSetting::ThreadScope),
+    ("maxdice", RoomSetting::MaxDisplayedDice),
+    ("uploads", RoomSetting::SheetUploads),
+];
+
+/// Most dice of a dice pool that a room can choose to show.
+pub const MAX_DISPLAYED_DICE_LIMIT: usize = 100;
+
+fn config_name(setting: RoomSetting) -> &'static str {
+    CONFIG_SETTINGS
+        .iter()
+        .find(|(_, s)| *s == setting)
+        .map_or_else(|| setting.key(), |(name, _)| name)
+}
+
+/// The value a setting has in rooms that never changed it.
+fn default_value(setting: RoomSetting) -> String {
+    match setting {
+        RoomSetting::DefaultSystem => GameSystem::default().key().to_string(),
+        RoomSetting::Verbosity => Verbosity::default().key().to_string(),
+        RoomSetting::ReplyType => ReplyType::default().key().to_string(),
+        RoomSetting::InlineRolls | RoomSetting::ThreadScope | RoomSetting::SheetUploads => {
+            "off".to_string()
+        }
+        RoomSetting::MaxDisplayedDice => DEFAULT_MAX_DISPLAYED_ROLLS.to_string(),
+        RoomSetting::CommandPrefix | RoomSetting::RollTemplate(_) => "default".to_string(),
+    }
+}
+
+fn is_valid_value(setting: RoomSetting, value: &str) -> bool {
+    match setting {
+        RoomSetting::DefaultSystem => GameSystem::from_key(value).is_some(),
+        RoomSetting::Verbosity => Verbosity::from_key(value).is_some(),
+        RoomSetting::ReplyType => ReplyType::from_key(value).is_some(),
+        RoomSetting::InlineRolls | RoomSetting::ThreadScope | RoomSetting::SheetUploads => {
+            matches!(value, "on" | "off")
+        }
+        RoomSetting::MaxDisplayedDice => {
+            matches!(value.parse::<usize>(), Ok(1..=MAX_DISPLAYED_DICE_LIMIT))
+        }
+        RoomSetting::CommandPrefix | RoomSetting::RollTemplate(_) => false,
+    }
+}
+
+/// Show the settings of the room, or one of them, or change one of
+/// them (moderators only). A setting changed to "default" goes back
+/// to its default value. !inline and !threads are short for the
+/// settings of the same name.
+#[derive(Debug, PartialEq)]
+pub enum ConfigCommand {
+    Show,
+    Get(RoomSetting),
+    Set(RoomSetting, Option<String>),
+}
+
+impl TryFrom<String> for ConfigCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let input = input.trim().to_lowercase();
+        let invalid = |reason: String| CommandError::InvalidCommand(reason).into();
+
+        if input.is_empty() {
+            return Ok(ConfigCommand::Show);
+        }
+
+        let (name, value) = input
+            .split_once(char::is_whitespace)
+            .unwrap_or((&input, ""));
+        let value = value.trim();
+
+        let setting = CONFIG_SETTINGS
+            .iter()
+            .find(|(setting_name, _)| *setting_name == name)
+            .map(|(_, setting)| *setting)
+            .ok_or_else(|| invalid(format!("unknown setting: {}", name)))?;
+
+        match value {
+            "" => Ok(ConfigCommand::Get(setting)),
+            "default" => Ok(ConfigCommand::Set(setting, None)),
+            _ if is_valid_value(setting, value) => {
+                Ok(ConfigCommand::Set(setting, Some(value.to_owned())))
+            }
+            _ => Err(invalid(format!("invalid value for {}: {}", name, value))),
+        }
+    }
+}
+
+#[async_trait]
+impl Command for ConfigCommand {
+    fn name(&self) -> &'static str {
+        "room settings"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        match self {
+            ConfigCommand::Show | ConfigCommand::Get(_) => Permission::Anyone,
+            ConfigCommand::Set(..) => Permission::Moderator,
+        }
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let room_id = ctx.active_room_id().as_str();
+        let describe = |setting: RoomSetting, value: Option<String>| match value {
+            Some(value) => format!(
+                "<strong>{}:</strong> {}",
+                config_name(setting),
+                escape_html(&value)
+            ),
+            None => format!(
+                "<strong>{}:</strong> {} (default)",
+                config_name(setting),
+                default_value(setting)
+            ),
+        };
+
+        let html = match self {
+            ConfigCommand::Show => {
+                let mut settings = vec![];
+                for &(_, setting) in CONFIG_SETTINGS.iter() {
+                    let value = ctx.db.get_room_setting(room_id, setting).await?;
+                    settings.push(describe(setting, value));
+                }
+
+                format!(
+                    "<p><strong>Room settings</strong></p><p>{}</p>",
+                    settings.join("<br/>")
+                )
+            }
+            ConfigCommand::Get(setting) => {
+                let value = ctx.db.get_room_setting(room_id, *setting).await?;
+                describe(*setting, value)
+            }
+            ConfigCommand::Set(setting, value) => {
+                ctx.db
+                    .set_room_setting(room_id, *setting, value.as_deref())
+                    .await?;
+                describe(*setting, value.clone())
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+/// Show the roll templates of the room, or change the template of a
+/// game system (moderators only). A template changed to "default"
+/// goes back to the built-in formatting.
+#[derive(Debug, PartialEq)]
+pub enum TemplateCommand {
+    Show,
+    Set(GameSystem, Option<String>),
+}
+
+impl TryFrom<String> for TemplateCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Ok(TemplateCommand::Show);
+        }
+
+        let (system, template) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let template = template.trim();
+
+        let system = GameSystem::from_key(&system.to_lowercase()).ok_or_else(|| {
+            CommandError::InvalidCommand(format!("unknown game system: {}", system))
+        })?;
+
+        match template {
+            "default" => Ok(TemplateCommand::Set(system, None)),
+            _ => {
+                templates::validate(template)?;
+                Ok(TemplateCommand::Set(system, Some(template.to_owned())))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Command for TemplateCommand {
+    fn name(&self) -> &'static str {
+        "roll templates"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        match self {
+            TemplateCommand::Show => Permission::Anyone,
+            TemplateCommand::Set(..) => Permission::Moderator,
+        }
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let room_id = ctx.active_room_id().as_str();
+        let describe = |system: GameSystem, template: Option<String>| match template {
+            Some(template) => format!(
+                "<strong>{}:</strong> <code>{}</code>",
+                system.key(),
+                escape_html(&template)
+            ),
+            None => format!("<strong>{}:</strong> built-in", system.key()),
+        };
+
+        let html = match self {
+            TemplateCommand::Show => {
+                let mut templates = vec![];
+                for &system in GameSystem::ALL.iter() {
+                    let setting = RoomSetting::RollTemplate(system);
+                    let template = ctx.db.get_room_setting(room_id, setting).await?;
+                    templates.push(describe(system, template));
+                }
+
+                format!(
+                    "<p><strong>Roll templates</strong></p><p>{}</p>",
+                    templates.join("<br/>")
+                )
+            }
+            TemplateCommand::Set(system, template) => {
+                let setting = RoomSetting::RollTemplate(*system);
+                ctx.db
+                    .set_room_setting(room_id, setting, template.as_deref())
+                    .await?;
+                describe(*system, template.clone())
+            }
+        };
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_command_test() {
+        let parse = |input: &str| ConfigCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse(" "), Some(ConfigCommand::Show));
+        assert_eq!(
+            parse("System CoFD"),
+            Some(ConfigCommand::Set(
+                RoomSetting::DefaultSystem,
+                Some("cofd".to_owned())
+            ))
+        );
+        assert_eq!(
+            parse("maxdice default"),
+            Some(ConfigCommand::Set(RoomSetting::MaxDisplayedDice, None))
+        );
+        assert_eq!(
+            parse("reply message"),
+            Some(ConfigCommand::Set(
+                RoomSetting::ReplyType,
+                Some("message".to_owned())
+            ))
+        );
+
+        assert_eq!(parse("system dnd"), None);
+        assert_eq!(parse("maxdice 0"), None);
+        assert_eq!(parse("maxdice 1000"), None);
+        assert_eq!(
+            parse("inline"),
+            Some(ConfigCommand::Get(RoomSetting::InlineRolls))
+        );
+        assert_eq!(parse("threads maybe"), None);
+        assert_eq!(parse("prefix ."), None);
+    }
+
+    #[test]
+    fn parse_template_command_test() {
+        let parse = |input: &str| TemplateCommand::try_from(input.to_string()).ok();
+
+        assert_eq!(parse(""), Some(TemplateCommand::Show));
+        assert_eq!(
+            parse("CoC {user}: <b>{total}</b>"),
+            Some(TemplateCommand::Set(
+                GameSystem::CallOfCthulhu,
+                Some("{user}: <b>{total}</b>".to_owned())
+            ))
+        );
+        assert_eq!(
+            parse("basic default"),
+            Some(TemplateCommand::Set(GameSystem::Basic, None))
+        );
+
+        assert_eq!(parse("dnd {total}"), None);
+        assert_eq!(parse("basic {damage}"), None);
+        assert_eq!(parse("cofd"), None);
+    }
+
+    #[test]
+    fn parse_prefix_command_test() {
+        let parse = |input: &str| PrefixCommand::try_from(input.to_string());
+
+        assert!(matches!(parse(""), Ok(PrefixCommand::Show)));
+        assert!(matches!(parse("default"), Ok(PrefixCommand::Reset)));
+        assert!(matches!(parse(" .. "), Ok(PrefixCommand::Set(p)) if p == ".."));
+        assert!(parse("roll").is_err());
+        assert!(parse("! !").is_err());
+        assert!(parse("!!!!!!").is_err());
+    }
+
+    #[test]
+    fn prefix_changes_need_moderator_test() {
+        let permission = |input: &str| {
+            PrefixCommand::try_from(input.to_string())
+                .unwrap()
+                .permission()
+        };
+
+        assert_eq!(permission(""), Permission::Anyone);
+        assert_eq!(permission("."), Permission::Moderator);
+        assert_eq!(permission("default"), Permission::Moderator);
+    }
+}
@@ -0,0 +1,171 @@
+use super::{Command, Execution, ExecutionResult, Permission};
+use crate::context::Context;
+use crate::db::errors::DataError;
+use crate::db::RoomVariables;
+use crate::error::BotError;
+use crate::parser::variables::parse_variable_name;
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+pub struct GetAllRoomVariablesCommand;
+
+impl TryFrom<String> for GetAllRoomVariablesCommand {
+    type Error = BotError;
+
+    fn try_from(_: String) -> Result<Self, Self::Error> {
+        Ok(GetAllRoomVariablesCommand)
+    }
+}
+
+#[async_trait]
+impl Command for GetAllRoomVariablesCommand {
+    fn name(&self) -> &'static str {
+        "get all room variables"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let variables = ctx
+            .db
+            .get_room_variables(ctx.active_room_id().as_str())
+            .await?;
+
+        let mut variable_list: Vec<String> = variables
+            .into_iter()
+            .map(|(name, value)| format!(" - #{} = {}", name, value))
+            .collect();
+
+        variable_list.sort();
+
+        let html = format!(
+            "<strong>Room Variables:</strong><br/>{}",
+            variable_list.join("<br/>")
+        );
+
+        Execution::success(html)
+    }
+}
+
+pub struct GetRoomVariableCommand(pub String);
+
+impl TryFrom<String> for GetRoomVariableCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let name = parse_variable_name(input.trim().trim_start_matches('#'))?;
+        Ok(GetRoomVariableCommand(name))
+    }
+}
+
+#[async_trait]
+impl Command for GetRoomVariableCommand {
+    fn name(&self) -> &'static str {
+        "retrieve room variable value"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let name = &self.0;
+        let result = ctx
+            .db
+            .get_room_variable(ctx.active_room_id().as_str(), name)
+            .await;
+
+        let value = match result {
+            Ok(num) => format!("#{} = {}", name, num),
+            Err(DataError::KeyDoesNotExist(_)) => format!("#{} is not set", name),
+            Err(e) => return Err(e.into()),
+        };
+
+        let html = format!("<strong>Room Variable:</strong> {}", value);
+        Execution::success(html)
+    }
+}
+
+pub struct SetRoomVariableCommand(pub String, pub i32);
+
+impl TryFrom<String> for SetRoomVariableCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let input = input.trim_start_matches('#');
+        let (variable_name, value) = crate::parser::variables::parse_set_variable(input)?;
+        Ok(SetRoomVariableCommand(variable_name, value))
+    }
+}
+
+#[async_trait]
+impl Command for SetRoomVariableCommand {
+    fn name(&self) -> &'static str {
+        "set room variable value"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        Permission::Moderator
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let name = &self.0;
+        let value = self.1;
+
+        ctx.db
+            .set_room_variable(ctx.active_room_id().as_str(), name, value)
+            .await?;
+
+        let html = format!("<strong>Set Room Variable:</strong> #{} = {}", name, value);
+        Execution::success(html)
+    }
+}
+
+pub struct DeleteRoomVariableCommand(pub String);
+
+impl TryFrom<String> for DeleteRoomVariableCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let name = parse_variable_name(input.trim().trim_start_matches('#'))?;
+        Ok(DeleteRoomVariableCommand(name))
+    }
+}
+
+#[async_trait]
+impl Command for DeleteRoomVariableCommand {
+    fn name(&self) -> &'static str {
+        "delete room variable"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    fn permission(&self) -> Permission {
+        Permission::Moderator
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let name = &self.0;
+        let result = ctx
+            .db
+            .delete_room_variable(ctx.active_room_id().as_str(), name)
+            .await;
+
+        let value = match result {
+            Ok(()) => format!("#{} now unset", name),
+            Err(DataError::KeyDoesNotExist(_)) => format!("#{} is not currently set", name),
+            Err(e) => return Err(e.into()),
+        };
+
+        let html = format!("<strong>Remove Room Variable:</strong> {}", value);
+        Execution::success(html)
+    }
+}
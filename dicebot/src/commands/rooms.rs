@@ -1,4 +1,4 @@
-use super::{Command, Execution, ExecutionResult};
+use super::{Command, Execution, ExecutionResult, Permission};
 use crate::context::Context;
 use crate::db::Users;
 use crate::error::BotError;
@@ -138,11 +138,11 @@ impl Command for SetRoomCommand {
         true
     }
 
-    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
-        if !ctx.account.is_registered() {
-            return Err(BotError::AccountDoesNotExist);
-        }
+    fn permission(&self) -> Permission {
+        Permission::Registered
+    }
 
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let rooms_for_user = get_rooms_for_user(&ctx.matrix_client, ctx.username).await?;
         let room = search_for_room(&rooms_for_user, &self.0);
 
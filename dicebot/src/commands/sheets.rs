@@ -0,0 +1,183 @@
+use super::{escape_html, Command, CommandError, Execution, ExecutionResult};
+use crate::context::Context;
+use crate::db::Variables;
+use crate::error::BotError;
+use crate::models::VariableValue;
+use crate::sheets::{export_sheet, parse_sheet, KeyError, SheetError, SheetFormat, SheetInput};
+use async_trait::async_trait;
+use std::convert::TryFrom;
+
+/// Import a character sheet into the user's variables.
+pub struct ImportSheetCommand(pub SheetInput);
+
+/// The keys of a sheet that could not be imported, and why. The keys
+/// come from the sheet, so they are escaped.
+fn fmt_import_errors(errors: &[KeyError]) -> String {
+    let errors: Vec<String> = errors
+        .iter()
+        .map(|error| {
+            format!(
+                " - {}: {}",
+                escape_html(&error.key),
+                escape_html(&error.reason)
+            )
+        })
+        .collect();
+
+    format!(
+        "<strong>Import failed:</strong> no variables were imported<br/>{}",
+        errors.join("<br/>")
+    )
+}
+
+impl TryFrom<String> for ImportSheetCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        match input.trim() {
+            "" => Err(CommandError::InvalidCommand("sheet is missing".to_string()).into()),
+            input => Ok(ImportSheetCommand(SheetInput::from_message(input))),
+        }
+    }
+}
+
+#[async_trait]
+impl Command for ImportSheetCommand {
+    fn name(&self) -> &'static str {
+        "import character sheet"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let variables = match parse_sheet(&self.0) {
+            Ok(variables) => variables,
+            Err(SheetError::InvalidVariables(errors)) => {
+                return Execution::success(fmt_import_errors(&errors));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let skipped = ctx
+            .db
+            .set_user_variables(ctx.username, ctx.active_room_id().as_str(), &variables)
+            .await?;
+
+        let imported = variables.len() - skipped.len();
+        let html = match skipped.is_empty() {
+            true => format!("<strong>Imported:</strong> {} variables", imported),
+            false => format!(
+                "<strong>Imported:</strong> {} variables<br/>\
+                 <strong>Not imported</strong> (text or dice variables have these names): {}",
+                imported,
+                escape_html(&skipped.join(", "))
+            ),
+        };
+
+        Execution::success(html)
+    }
+}
+
+/// Export the user's variables as a character sheet.
+pub struct ExportSheetCommand(pub SheetFormat);
+
+impl TryFrom<String> for ExportSheetCommand {
+    type Error = BotError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let format = match input.trim() {
+            "" => SheetFormat::Json,
+            format => SheetFormat::from_name(format).ok_or_else(|| {
+                CommandError::InvalidCommand(format!("unknown sheet format: {}", format))
+            })?,
+        };
+
+        Ok(ExportSheetCommand(format))
+    }
+}
+
+#[async_trait]
+impl Command for ExportSheetCommand {
+    fn name(&self) -> &'static str {
+        "export character sheet"
+    }
+
+    fn is_secure(&self) -> bool {
+        false
+    }
+
+    async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
+        let format = self.0;
+        let values = ctx
+            .db
+            .get_variable_values(ctx.username, ctx.active_room_id().as_str())
+            .await?;
+
+        // Sheets only hold numbers, so text and dice variables are
+        // listed under the sheet instead.
+        let mut skipped: Vec<&str> = vec![];
+        let mut variables = vec![];
+        for (name, value) in &values {
+            match value {
+                VariableValue::Integer(value) => variables.push((name, value)),
+                _ => skipped.push(name),
+            }
+        }
+
+        let mut html = format!(
+            "<strong>Character Sheet:</strong></p><pre><code class=\"language-{}\">{}</code></pre>",
+            format,
+            export_sheet(variables, format)
+        );
+
+        if !skipped.is_empty() {
+            skipped.sort_unstable();
+            html.push_str(&format!(
+                "<p><strong>Not exported</strong> (text and dice variables): {}",
+                escape_html(&skipped.join(", "))
+            ));
+        }
+
+        Execution::success(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_export_format_test() {
+        let parse = |input: &str| ExportSheetCommand::try_from(input.to_string());
+
+        assert!(matches!(
+            parse(""),
+            Ok(ExportSheetCommand(SheetFormat::Json))
+        ));
+        assert!(matches!(
+            parse("TOML"),
+            Ok(ExportSheetCommand(SheetFormat::Toml))
+        ));
+        assert!(parse("yaml").is_err());
+    }
+
+    #[test]
+    fn import_requires_sheet_test() {
+        assert!(ImportSheetCommand::try_from("   ".to_string()).is_err());
+    }
+
+    #[test]
+    fn import_errors_escape_keys_test() {
+        let input = SheetInput::from_message(r#"{"<a href=x>str</a>": 1}"#);
+        let errors = match parse_sheet(&input) {
+            Err(SheetError::InvalidVariables(errors)) => errors,
+            _ => panic!("Sheet with markup in a key was imported"),
+        };
+
+        let html = fmt_import_errors(&errors);
+        assert!(html.contains("&lt;a href=x&gt;str&lt;/a&gt;"));
+        assert!(!html.contains("<a href"));
+    }
+}
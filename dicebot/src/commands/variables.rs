@@ -1,8 +1,14 @@
-use super::{Command, Execution, ExecutionResult};
+use super::{escape_html, Command, Execution, ExecutionResult};
 use crate::context::Context;
 use crate::db::errors::DataError;
 use crate::db::Variables;
 use crate::error::BotError;
+use crate::logic;
+use crate::models::{UpdatedVariable, ValueChange, VariableValue};
+use crate::parser::variables::{
+    parse_increment, parse_typed_variable, parse_variable_name, parse_variable_update,
+    UpdateOperator, VariableUpdate,
+};
 use async_trait::async_trait;
 use std::convert::TryFrom;
 
@@ -29,12 +35,12 @@ impl Command for GetAllVariablesCommand {
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
         let variables = ctx
             .db
-            .get_user_variables(&ctx.username, ctx.active_room_id().as_str())
+            .get_variable_values(ctx.username, ctx.active_room_id().as_str())
             .await?;
 
         let mut variable_list: Vec<String> = variables
             .into_iter()
-            .map(|(name, value)| format!(" - {} = {}", name, value))
+            .map(|(name, value)| format!(" - {} = {}", name, escape_html(&value.to_string())))
             .collect();
 
         variable_list.sort();
@@ -55,7 +61,7 @@ impl TryFrom<String> for GetVariableCommand {
     type Error = BotError;
 
     fn try_from(input: String) -> Result<Self, Self::Error> {
-        Ok(GetVariableCommand(input))
+        Ok(GetVariableCommand(parse_variable_name(&input)?))
     }
 }
 
@@ -73,11 +79,11 @@ impl Command for GetVariableCommand {
         let name = &self.0;
         let result = ctx
             .db
-            .get_user_variable(&ctx.username, ctx.active_room_id().as_str(), name)
+            .get_variable_value(ctx.username, ctx.active_room_id().as_str(), name)
             .await;
 
         let value = match result {
-            Ok(num) => format!("{} = {}", name, num),
+            Ok(value) => format!("{} = {}", name, escape_html(&value.to_string())),
             Err(DataError::KeyDoesNotExist(_)) => format!("{} is not set", name),
             Err(e) => return Err(e.into()),
         };
@@ -87,14 +93,62 @@ impl Command for GetVariableCommand {
     }
 }
 
-pub struct SetVariableCommand(pub String, pub i32);
+pub enum SetVariableCommand {
+    /// Set an integer variable, or change it relative to its current
+    /// value.
+    Update(VariableUpdate),
+
+    /// Store text or a dice expression.
+    Store(String, VariableValue),
+}
 
 impl TryFrom<String> for SetVariableCommand {
     type Error = BotError;
 
     fn try_from(input: String) -> Result<Self, Self::Error> {
-        let (variable_name, value) = crate::parser::variables::parse_set_variable(&input)?;
-        Ok(SetVariableCommand(variable_name, value))
+        let command = match parse_typed_variable(&input)? {
+            Some((name, value)) => SetVariableCommand::Store(name, value),
+            None => SetVariableCommand::Update(parse_variable_update(&input)?),
+        };
+
+        Ok(command)
+    }
+}
+
+impl SetVariableCommand {
+    /// Add to a variable, 1 by default.
+    pub fn increment(input: String) -> Result<Self, BotError> {
+        let update = parse_increment(&input, UpdateOperator::Add)?;
+        Ok(SetVariableCommand::Update(update))
+    }
+
+    /// Subtract from a variable, 1 by default.
+    pub fn decrement(input: String) -> Result<Self, BotError> {
+        let update = parse_increment(&input, UpdateOperator::Subtract)?;
+        Ok(SetVariableCommand::Update(update))
+    }
+}
+
+/// Describe the outcome of an update, e.g. "hp = 3 (was 7; min 0)".
+fn describe_update(name: &str, updated: &UpdatedVariable) -> String {
+    let mut notes = vec![];
+
+    if let Some(old_value) = updated.old_value {
+        notes.push(format!("was {}", old_value));
+    }
+
+    let bounds: Vec<String> = vec![("min", updated.min), ("max", updated.max)]
+        .into_iter()
+        .filter_map(|(bound, value)| value.map(|value| format!("{} {}", bound, value)))
+        .collect();
+
+    if !bounds.is_empty() {
+        notes.push(bounds.join(", "));
+    }
+
+    match notes.is_empty() {
+        true => format!("{} = {}", name, updated.value),
+        false => format!("{} = {} ({})", name, updated.value, notes.join("; ")),
     }
 }
 
@@ -109,14 +163,51 @@ impl Command for SetVariableCommand {
     }
 
     async fn execute(&self, ctx: &Context<'_>) -> ExecutionResult {
-        let name = &self.0;
-        let value = self.1;
+        let update = match self {
+            SetVariableCommand::Update(update) => update,
+            SetVariableCommand::Store(name, value) => {
+                ctx.db
+                    .set_variable_value(ctx.username, ctx.active_room_id().as_str(), name, value)
+                    .await?;
+
+                let content = format!("{} = {}", name, escape_html(&value.to_string()));
+                let html = format!("<strong>Set Variable:</strong> {}", content);
+                return Execution::success(html);
+            }
+        };
 
-        ctx.db
-            .set_user_variable(&ctx.username, ctx.active_room_id().as_str(), name, value)
-            .await?;
+        let name = &update.name;
+
+        let change = match update.expression.is_empty() {
+            true => ValueChange::Keep,
+            false => {
+                let amount = logic::evaluate_terms(&update.expression, ctx).await?;
+                match update.operator {
+                    UpdateOperator::Assign => ValueChange::Set(amount),
+                    UpdateOperator::Add => ValueChange::Add(amount),
+                    UpdateOperator::Subtract => ValueChange::Add(amount.saturating_neg()),
+                }
+            }
+        };
+
+        let result = ctx
+            .db
+            .update_user_variable(
+                ctx.username,
+                ctx.active_room_id().as_str(),
+                name,
+                change,
+                update.min,
+                update.max,
+            )
+            .await;
+
+        let content = match result {
+            Ok(updated) => describe_update(name, &updated),
+            Err(DataError::KeyDoesNotExist(_)) => format!("{} is not set", name),
+            Err(e) => return Err(e.into()),
+        };
 
-        let content = format!("{} = {}", name, value);
         let html = format!("<strong>Set Variable:</strong> {}", content);
         Execution::success(html)
     }
@@ -128,7 +219,7 @@ impl TryFrom<String> for DeleteVariableCommand {
     type Error = BotError;
 
     fn try_from(input: String) -> Result<Self, Self::Error> {
-        Ok(DeleteVariableCommand(input))
+        Ok(DeleteVariableCommand(parse_variable_name(&input)?))
     }
 }
 
@@ -159,3 +250,29 @@ impl Command for DeleteVariableCommand {
         Execution::success(html)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_update_test() {
+        let mut updated = UpdatedVariable {
+            old_value: None,
+            value: 10,
+            min: None,
+            max: None,
+        };
+
+        assert_eq!(describe_update("hp", &updated), "hp = 10");
+
+        updated.old_value = Some(12);
+        updated.min = Some(0);
+        updated.max = Some(10);
+
+        assert_eq!(
+            describe_update("hp", &updated),
+            "hp = 10 (was 12; min 0, max 10)"
+        );
+    }
+}
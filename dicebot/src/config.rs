@@ -43,6 +43,20 @@ struct MatrixConfig {
 
 const DEFAULT_OLDEST_MESSAGE_AGE: u64 = 15 * 60;
 
+pub const DEFAULT_COMMAND_PREFIX: &str = "!";
+
+/// By default, a user can send one message with the most commands
+/// allowed, and then about one command every two seconds.
+const DEFAULT_USER_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 50,
+    per_minute: 30,
+};
+
+const DEFAULT_ROOM_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 100,
+    per_minute: 120,
+};
+
 fn db_path_from_env() -> String {
     env::var("DATABASE_PATH")
         .expect("could not find database path in config or environment variable")
@@ -62,6 +76,52 @@ struct BotConfig {
     /// want to connect to it. The RPC server will reject any clients
     /// that don't present the shared key.
     rpc_key: Option<String>,
+
+    /// What commands start with, unless a room has its own prefix.
+    /// Defaults to "!".
+    command_prefix: Option<String>,
+
+    /// Matrix user IDs of the bot admins, who may execute every
+    /// command, including the admin commands.
+    admins: Option<Vec<String>>,
+
+    /// Who may invite the bot to rooms: Matrix user IDs, or homeserver
+    /// names to allow every user of that homeserver. If not specified,
+    /// anyone may invite the bot.
+    allowed_inviters: Option<Vec<String>>,
+
+    /// Only accept invites from users with a registered account.
+    /// Defaults to false.
+    require_registered_inviter: Option<bool>,
+
+    /// Leave rooms once the bot is the only member left. Defaults to
+    /// true.
+    leave_empty_rooms: Option<bool>,
+
+    /// How many commands users and rooms can execute.
+    rate_limit: Option<RateLimitConfig>,
+
+    /// Seed every roll from a secret committed to in advance and the
+    /// ID of the event that made the roll, so that rolls can be
+    /// verified once the secret is revealed. Defaults to false.
+    fair_rolls: Option<bool>,
+}
+
+/// A token bucket rate limit: commands can be executed in a burst of
+/// up to "burst" commands, and the allowance refills at "per_minute"
+/// commands per minute.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+/// The "rate_limit" part of the bot section. Every user, and every
+/// room, gets its own allowance.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct RateLimitConfig {
+    user: Option<RateLimit>,
+    room: Option<RateLimit>,
 }
 
 /// The "database" section of the config file.
@@ -101,6 +161,54 @@ impl BotConfig {
     fn rpc_key(&self) -> Option<String> {
         self.rpc_key.clone()
     }
+
+    #[inline]
+    #[must_use]
+    fn command_prefix(&self) -> Option<&str> {
+        self.command_prefix.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    fn admins(&self) -> &[String] {
+        self.admins.as_deref().unwrap_or_default()
+    }
+
+    #[inline]
+    #[must_use]
+    fn allowed_inviters(&self) -> Option<&[String]> {
+        self.allowed_inviters.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    fn require_registered_inviter(&self) -> bool {
+        self.require_registered_inviter.unwrap_or(false)
+    }
+
+    #[inline]
+    #[must_use]
+    fn leave_empty_rooms(&self) -> bool {
+        self.leave_empty_rooms.unwrap_or(true)
+    }
+
+    #[inline]
+    #[must_use]
+    fn fair_rolls(&self) -> bool {
+        self.fair_rolls.unwrap_or(false)
+    }
+
+    #[inline]
+    #[must_use]
+    fn user_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit.as_ref().and_then(|rl| rl.user)
+    }
+
+    #[inline]
+    #[must_use]
+    fn room_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit.as_ref().and_then(|rl| rl.room)
+    }
 }
 
 /// Represents the toml config file for the dicebot. The sections of
@@ -170,6 +278,91 @@ impl Config {
     pub fn rpc_key(&self) -> Option<String> {
         self.bot.as_ref().and_then(|bc| bc.rpc_key())
     }
+
+    /// The command prefix used in rooms that have not set their own.
+    /// This is the configured prefix, or "!" if there is none.
+    #[inline]
+    #[must_use]
+    pub fn command_prefix(&self) -> &str {
+        self.bot
+            .as_ref()
+            .and_then(|bc| bc.command_prefix())
+            .unwrap_or(DEFAULT_COMMAND_PREFIX)
+    }
+
+    /// Whether a user is one of the bot admins in the config. There
+    /// are no bot admins unless the config lists them.
+    #[inline]
+    #[must_use]
+    pub fn is_admin(&self, user_id: &str) -> bool {
+        self.bot
+            .as_ref()
+            .map_or(false, |bc| bc.admins().iter().any(|admin| admin == user_id))
+    }
+
+    /// Whether a user may invite the bot to a room. The user must be
+    /// in the allowed inviters, or be on one of the allowed
+    /// homeservers. Everyone may invite the bot if there is no list.
+    #[must_use]
+    pub fn is_inviter_allowed(&self, user_id: &str) -> bool {
+        let allowed = match self.bot.as_ref().and_then(|bc| bc.allowed_inviters()) {
+            Some(allowed) => allowed,
+            None => return true,
+        };
+
+        let server_name = user_id.split_once(':').map(|(_, server)| server);
+        allowed.iter().any(|entry| match entry.strip_prefix('@') {
+            Some(_) => entry == user_id,
+            None => Some(entry.as_str()) == server_name,
+        })
+    }
+
+    /// Whether invites are only accepted from users with a registered
+    /// account.
+    #[inline]
+    #[must_use]
+    pub fn require_registered_inviter(&self) -> bool {
+        self.bot
+            .as_ref()
+            .map_or(false, |bc| bc.require_registered_inviter())
+    }
+
+    /// Whether the bot leaves rooms once it is the only member left.
+    #[inline]
+    #[must_use]
+    pub fn leave_empty_rooms(&self) -> bool {
+        self.bot.as_ref().map_or(true, |bc| bc.leave_empty_rooms())
+    }
+
+    /// Whether rolls are seeded from a committed secret, so they can
+    /// be verified later.
+    #[inline]
+    #[must_use]
+    pub fn fair_rolls(&self) -> bool {
+        self.bot.as_ref().map_or(false, |bc| bc.fair_rolls())
+    }
+
+    /// How many commands each user can execute. Falls back to a burst
+    /// of 50 commands, refilling at 30 commands per minute.
+    #[inline]
+    #[must_use]
+    pub fn user_rate_limit(&self) -> RateLimit {
+        self.bot
+            .as_ref()
+            .and_then(|bc| bc.user_rate_limit())
+            .unwrap_or(DEFAULT_USER_RATE_LIMIT)
+    }
+
+    /// How many commands can be executed in each room. Falls back to
+    /// a burst of 100 commands, refilling at 120 commands per minute.
+    #[inline]
+    #[must_use]
+    pub fn room_rate_limit(&self) -> RateLimit {
+        self.bot
+            .as_ref()
+            .and_then(|bc| bc.room_rate_limit())
+            .unwrap_or(DEFAULT_ROOM_RATE_LIMIT)
+    }
 }
 
 #[cfg(test)]
@@ -213,6 +406,28 @@ mod tests {
         assert_eq!(15 * 60, cfg.oldest_message_age());
     }
 
+    #[test]
+    fn command_prefix_default_and_setting_test() {
+        let mut cfg = Config {
+            matrix: MatrixConfig {
+                home_server: "".to_owned(),
+                username: "".to_owned(),
+                password: "".to_owned(),
+            },
+            database: None,
+            bot: None,
+        };
+
+        assert_eq!("!", cfg.command_prefix());
+
+        cfg.bot = Some(BotConfig {
+            command_prefix: Some(".".to_owned()),
+            ..Default::default()
+        });
+
+        assert_eq!(".", cfg.command_prefix());
+    }
+
     #[test]
     fn db_path_uses_setting_first_test() {
         let cfg = Config {
@@ -339,4 +554,77 @@ mod tests {
         let cfg: Result<_, _> = deserialize_config(contents);
         assert_eq!(true, cfg.is_ok());
     }
+
+    #[test]
+    fn admins_test() {
+        let mut cfg = Config {
+            matrix: MatrixConfig {
+                home_server: "".to_owned(),
+                username: "".to_owned(),
+                password: "".to_owned(),
+            },
+            database: None,
+            bot: None,
+        };
+
+        assert!(!cfg.is_admin("@admin:example.com"));
+
+        cfg.bot = Some(BotConfig {
+            admins: Some(vec!["@admin:example.com".to_owned()]),
+            ..Default::default()
+        });
+
+        assert!(cfg.is_admin("@admin:example.com"));
+        assert!(!cfg.is_admin("@user:example.com"));
+    }
+
+    #[test]
+    fn allowed_inviters_test() {
+        let mut cfg = Config {
+            matrix: MatrixConfig {
+                home_server: "".to_owned(),
+                username: "".to_owned(),
+                password: "".to_owned(),
+            },
+            database: None,
+            bot: None,
+        };
+
+        assert!(cfg.is_inviter_allowed("@anyone:example.com"));
+
+        cfg.bot = Some(BotConfig {
+            allowed_inviters: Some(vec![
+                "@friend:elsewhere.com".to_owned(),
+                "example.com".to_owned(),
+            ]),
+            ..Default::default()
+        });
+
+        assert!(cfg.is_inviter_allowed("@anyone:example.com"));
+        assert!(cfg.is_inviter_allowed("@friend:elsewhere.com"));
+        assert!(!cfg.is_inviter_allowed("@stranger:elsewhere.com"));
+        assert!(!cfg.is_inviter_allowed("@example.com:evil.com"));
+    }
+
+    #[test]
+    fn rate_limits_test() {
+        let contents = indoc! {"
+            [matrix]
+            home_server = 'https://matrix.example.com'
+            username = 'username'
+            password = 'password'
+
+            [bot.rate_limit]
+            user = { burst = 5, per_minute = 10 }
+        "};
+
+        let cfg = deserialize_config(contents).expect("could not parse config");
+        let user_limit = RateLimit {
+            burst: 5,
+            per_minute: 10,
+        };
+
+        assert_eq!(user_limit, cfg.user_rate_limit());
+        assert_eq!(DEFAULT_ROOM_RATE_LIMIT, cfg.room_rate_limit());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::db::sqlite::Database;
 use crate::error::BotError;
-use crate::models::Account;
+use crate::models::{Account, Verbosity};
+use crate::rng::DiceRng;
 use matrix_sdk::identifiers::{RoomId, UserId};
 use matrix_sdk::room::Joined;
 use matrix_sdk::Client;
@@ -17,6 +18,20 @@ pub struct Context<'a> {
     pub username: &'a str,
     pub message_body: &'a str,
     pub account: Account,
+
+    /// The thread the command was sent in, if the room keeps the
+    /// state of each thread apart.
+    pub thread_id: Option<&'a str>,
+
+    /// Whether the sender is one of the bot admins in the config.
+    pub admin: bool,
+
+    /// How much detail replies to rolls have: the setting of the room,
+    /// unless the command asked for another.
+    pub verbosity: Verbosity,
+
+    /// Where the dice rolled by the command get their randomness.
+    pub rng: DiceRng,
 }
 
 impl Context<'_> {
@@ -3,9 +3,7 @@ use crate::db::Variables;
 use crate::error::{BotError, DiceRollingError};
 use crate::logic::calculate_single_die_amount;
 use crate::parser::dice::{Amount, DiceParsingError, Element};
-use rand::rngs::StdRng;
 use rand::Rng;
-use rand::SeedableRng;
 use std::convert::TryFrom;
 use std::fmt;
 
@@ -110,14 +108,6 @@ pub struct ExecutedDiceRoll {
     pub roll: RolledDice,
 }
 
-impl fmt::Display for ExecutedDiceRoll {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = format!("target: {}, with {}", self.target, self.modifier);
-        write!(f, "{}", message)?;
-        Ok(())
-    }
-}
-
 /// A struct wrapping the target and the actual advancement roll
 /// result. This is done for formatting purposes, so we can display
 /// the target number (calculated from resolving variables) separately
@@ -131,26 +121,38 @@ pub struct ExecutedAdvancementRoll {
     pub roll: RolledAdvancement,
 }
 
-impl fmt::Display for ExecutedAdvancementRoll {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = format!("target: {}", self.target);
-        write!(f, "{}", message)?;
-        Ok(())
-    }
-}
-
-//TODO need to keep track of all rolled numbers for informational purposes!
 /// The outcome of a roll.
 pub struct RolledDice {
     /// The d100 result actually rolled.
     num_rolled: u32,
 
+    /// Every d100 result rolled, one for each die. With bonus or
+    /// penalty dice, one of them is picked as the result.
+    rolls: Vec<u32>,
+
     /// The number we must meet for the roll to be considered a
     /// success.
     target: u32,
 }
 
 impl RolledDice {
+    /// The d100 result actually rolled.
+    pub fn num_rolled(&self) -> u32 {
+        self.num_rolled
+    }
+
+    /// Every d100 result rolled, including the ones not picked
+    /// because of bonus or penalty dice.
+    pub fn rolls(&self) -> &[u32] {
+        &self.rolls
+    }
+
+    /// The number we must meet for the roll to be considered a
+    /// success.
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
     /// Calculate what type of success or failure this roll is.
     /// Consult the RollResult enum for descriptions of what each
     /// result requires.
@@ -173,19 +175,6 @@ impl RolledDice {
     }
 }
 
-impl fmt::Display for RolledDice {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = format!(
-            "{} against {}: {}",
-            self.num_rolled,
-            self.target,
-            self.result()
-        );
-        write!(f, "{}", message)?;
-        Ok(())
-    }
-}
-
 /// A planned advancement roll, where the target number is the
 /// existing skill amount.
 #[derive(Clone, Debug, PartialEq)]
@@ -216,6 +205,16 @@ pub struct RolledAdvancement {
 }
 
 impl RolledAdvancement {
+    /// The d100 result actually rolled.
+    pub fn num_rolled(&self) -> u32 {
+        self.num_rolled
+    }
+
+    /// The skill amount before the roll.
+    pub fn existing_skill(&self) -> u32 {
+        self.existing_skill
+    }
+
     /// The new skill amount, which will be the same if the roll was a
     /// failure.
     pub fn new_skill_amount(&self) -> u32 {
@@ -234,30 +233,9 @@ impl RolledAdvancement {
     }
 }
 
-impl fmt::Display for RolledAdvancement {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = if self.successful {
-            format!(
-                "success! new skill is {} (advanced by {}).",
-                self.new_skill_amount(),
-                self.advancement
-            )
-        } else {
-            format!("failure! skill remains at {}", self.existing_skill)
-        };
-
-        write!(
-            f,
-            "rolled {} against {}: {}",
-            self.num_rolled, self.existing_skill, message
-        )?;
-        Ok(())
-    }
-}
-
 /// This is a trait so we can inject controlled dice rolls in unit
 /// tests.
-trait DieRoller {
+pub(super) trait DieRoller {
     fn roll(&mut self) -> u32;
 }
 
@@ -299,7 +277,7 @@ fn roll_percentile_dice<R: DieRoller>(roller: &mut R, unit_roll: u32) -> u32 {
     }
 }
 
-fn roll_regular_dice<R: DieRoller>(
+pub(super) fn roll_regular_dice<R: DieRoller>(
     modifier: &DiceRollModifier,
     target: u32,
     roller: &mut R,
@@ -327,6 +305,7 @@ fn roll_regular_dice<R: DieRoller>(
 
     RolledDice {
         num_rolled: *num_rolled,
+        rolls: rolls.clone(),
         target: target,
     }
 }
@@ -366,8 +345,11 @@ pub async fn regular_roll(
     let target = calculate_single_die_amount(&roll_with_ctx.0.amount, roll_with_ctx.1).await?;
     let target = u32::try_from(target).map_err(|_| DiceRollingError::InvalidAmount)?;
 
-    let mut roller = RngDieRoller::<StdRng>(SeedableRng::from_entropy());
-    let rolled_dice = roll_regular_dice(&roll_with_ctx.0.modifier, target, &mut roller);
+    let modifier = &roll_with_ctx.0.modifier;
+    let rolled_dice = roll_with_ctx
+        .1
+        .rng
+        .with(|rng| roll_regular_dice(modifier, target, &mut RngDieRoller(rng)));
 
     Ok(ExecutedDiceRoll {
         target,
@@ -408,10 +390,10 @@ pub async fn advancement_roll(
         return Err(DiceRollingError::InvalidAmount.into());
     }
 
-    let mut roller = RngDieRoller::<StdRng>(SeedableRng::from_entropy());
-    let roll = roll_advancement_dice(target, &mut roller);
-
-    drop(roller);
+    let roll = roll_with_ctx
+        .1
+        .rng
+        .with(|rng| roll_advancement_dice(target, &mut RngDieRoller(rng)));
 
     if roll.successful && is_variable!(existing_skill) {
         let variable_name: &str = extract_variable(existing_skill)?;
@@ -425,7 +407,9 @@ pub async fn advancement_roll(
 mod tests {
     use super::*;
     use crate::db::sqlite::Database;
+    use crate::models::Verbosity;
     use crate::parser::dice::{Amount, Element, Operator};
+    use crate::rng::DiceRng;
     use url::Url;
 
     macro_rules! dummy_room {
@@ -516,6 +500,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let roll_with_ctx = DiceRollWithContext(&roll, &ctx);
@@ -554,6 +542,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let roll_with_ctx = AdvancementRollWithContext(&roll, &ctx);
@@ -592,6 +584,10 @@ mod tests {
             active_room: dummy_room!(),
             username: "username",
             message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
         };
 
         let roll_with_ctx = AdvancementRollWithContext(&roll, &ctx);
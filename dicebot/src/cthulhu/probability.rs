@@ -0,0 +1,165 @@
+use super::dice::{roll_regular_dice, DiceRollWithContext, DieRoller, RollResult};
+use crate::error::{BotError, DiceRollingError};
+use crate::logic::calculate_single_die_amount;
+use crate::probability::{enumerate_rolls, percent, Distribution, Odds, RollEnumerator};
+use std::convert::TryFrom;
+
+/// Every roll result, best first, in the order shown in histograms.
+const ROLL_RESULTS: [RollResult; 6] = [
+    RollResult::CriticalSuccess,
+    RollResult::ExtremeSuccess,
+    RollResult::HardSuccess,
+    RollResult::Success,
+    RollResult::Failure,
+    RollResult::Fumble,
+];
+
+impl DieRoller for RollEnumerator {
+    fn roll(&mut self) -> u32 {
+        (self.next_die(10) - 1) as u32
+    }
+}
+
+fn is_success(result: RollResult) -> bool {
+    !matches!(result, RollResult::Failure | RollResult::Fumble)
+}
+
+/// Calculate the exact odds of a percentile roll, including bonus or
+/// penalty dice: the chance of success, the expected result, and how
+/// likely each kind of success or failure is. There are at most ten
+/// thousand ways to roll the dice, so every one of them is checked.
+pub async fn regular_roll_odds(roll_with_ctx: &DiceRollWithContext<'_>) -> Result<Odds, BotError> {
+    let target = calculate_single_die_amount(&roll_with_ctx.0.amount, roll_with_ctx.1).await?;
+    let target = u32::try_from(target).map_err(|_| DiceRollingError::InvalidAmount)?;
+    let modifier = roll_with_ctx.0.modifier;
+
+    let outcomes = enumerate_rolls(|roller| roll_regular_dice(&modifier, target, roller));
+
+    let chance_of = |wanted: &dyn Fn(RollResult) -> bool| -> f64 {
+        outcomes
+            .iter()
+            .filter(|(rolled, _)| wanted(rolled.result()))
+            .map(|(_, probability)| probability)
+            .sum()
+    };
+
+    let rolled = Distribution::from_outcomes(
+        outcomes
+            .iter()
+            .map(|(rolled, probability)| (rolled.num_rolled() as i32, *probability)),
+    );
+
+    let histogram = ROLL_RESULTS
+        .iter()
+        .map(|&result| {
+            let label = result.to_string().trim_end_matches('!').to_string();
+            (label, chance_of(&|rolled| rolled == result))
+        })
+        .collect();
+
+    Ok(Odds {
+        description: format!("target: {}, with {}", target, modifier),
+        statistics: vec![
+            (
+                "Success chance".to_string(),
+                percent(chance_of(&is_success)),
+            ),
+            (
+                "Expected roll".to_string(),
+                format!("{:.2}", rolled.expected_value()),
+            ),
+        ],
+        histogram,
+        simulated_rolls: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cthulhu::dice::{DiceRoll, DiceRollModifier};
+    use crate::db::sqlite::Database;
+    use crate::models::Verbosity;
+    use crate::parser::dice::{Amount, Element, Operator};
+    use crate::rng::DiceRng;
+    use url::Url;
+
+    macro_rules! dummy_room {
+        () => {
+            crate::context::RoomContext {
+                id: &matrix_sdk::identifiers::room_id!("!fakeroomid:example.com"),
+                display_name: "displayname".to_owned(),
+                secure: false,
+            }
+        };
+    }
+
+    async fn odds_for(target: i32, modifier: DiceRollModifier) -> Odds {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let homeserver = Url::parse("http://example.com").unwrap();
+        let ctx = crate::context::Context {
+            account: crate::models::Account::default(),
+            db,
+            matrix_client: matrix_sdk::Client::new(homeserver).unwrap(),
+            origin_room: dummy_room!(),
+            active_room: dummy_room!(),
+            username: "username",
+            message_body: "message",
+            thread_id: None,
+            admin: false,
+            verbosity: Verbosity::default(),
+            rng: DiceRng::default(),
+        };
+
+        let roll = DiceRoll {
+            amount: Amount {
+                operator: Operator::Plus,
+                element: Element::Number(target),
+            },
+            modifier,
+        };
+
+        regular_roll_odds(&DiceRollWithContext(&roll, &ctx))
+            .await
+            .expect("Could not calculate odds")
+    }
+
+    fn stat(odds: &Odds, name: &str) -> String {
+        odds.statistics
+            .iter()
+            .find(|(stat_name, _)| stat_name == name)
+            .map(|(_, value)| value.clone())
+            .expect("Statistic not found")
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn normal_roll_odds_test() {
+        let odds = odds_for(50, DiceRollModifier::Normal).await;
+        assert_eq!("50.00%", stat(&odds, "Success chance"));
+        assert_eq!("50.50", stat(&odds, "Expected roll"));
+
+        let (_, fumble) = odds
+            .histogram
+            .iter()
+            .find(|(label, _)| label == "fumble")
+            .expect("No fumble row in histogram");
+
+        assert!((fumble - 0.01).abs() < 1e-9);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn bonus_and_penalty_dice_change_odds_test() {
+        let bonus = odds_for(50, DiceRollModifier::OneBonus).await;
+        let penalty = odds_for(50, DiceRollModifier::TwoPenalty).await;
+
+        // Half of all tens dice succeed against 50, whatever the unit
+        // die is. A bonus die fails only if both tens dice fail, and
+        // two penalty dice succeed only if all three tens dice do.
+        assert_eq!("75.00%", stat(&bonus, "Success chance"));
+        assert_eq!("12.50%", stat(&penalty, "Success chance"));
+    }
+}
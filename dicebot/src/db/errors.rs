@@ -6,6 +6,12 @@ pub enum DataError {
     #[error("value does not exist for key: {0}")]
     KeyDoesNotExist(String),
 
+    #[error("value already exists for key: {0}")]
+    KeyAlreadyExists(String),
+
+    #[error("minimum {0} is greater than maximum {1}")]
+    InvalidBounds(i32, i32),
+
     #[error("too many entries")]
     TooManyEntries,
 
@@ -29,4 +35,7 @@ pub enum DataError {
 
     #[error("numeric conversion error")]
     NumericConversionError(#[from] TryFromIntError),
+
+    #[error("stored json was invalid: {0}")]
+    JsonError(#[from] serde_json::Error),
 }
@@ -1,5 +1,8 @@
 use crate::error::BotError;
-use crate::models::User;
+use crate::models::{
+    BoundChange, Character, CommandReply, FairnessSession, Macro, MessageReply, RollRecord,
+    RollSummary, RoomSetting, UpdatedVariable, User, ValueChange, VariableValue,
+};
 use async_trait::async_trait;
 use errors::DataError;
 use std::collections::HashMap;
@@ -12,6 +15,9 @@ pub(crate) trait DbState {
     async fn get_device_id(&self) -> Result<Option<String>, DataError>;
 
     async fn set_device_id(&self, device_id: &str) -> Result<(), DataError>;
+
+    /// Size of the database, in bytes.
+    async fn database_size(&self) -> Result<i64, DataError>;
 }
 
 #[async_trait]
@@ -20,6 +26,9 @@ pub(crate) trait Users {
 
     async fn get_user(&self, username: &str) -> Result<Option<User>, DataError>;
 
+    /// Every user account, ordered by username.
+    async fn get_users(&self) -> Result<Vec<User>, DataError>;
+
     async fn delete_user(&self, username: &str) -> Result<(), DataError>;
 
     async fn authenticate_user(
@@ -32,12 +41,221 @@ pub(crate) trait Users {
 #[async_trait]
 pub(crate) trait Rooms {
     async fn should_process(&self, room_id: &str, event_id: &str) -> Result<bool, DataError>;
+
+    /// Remember the reply the bot sent to a command message, replacing
+    /// any earlier reply.
+    async fn record_reply(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        reply: &MessageReply,
+    ) -> Result<(), DataError>;
+
+    /// The reply the bot sent to a command message, if any.
+    async fn get_reply(
+        &self,
+        room_id: &str,
+        event_id: &str,
+    ) -> Result<Option<MessageReply>, DataError>;
+
+    /// Remember the command message a roll reply of the bot answered.
+    async fn record_command_reply(
+        &self,
+        room_id: &str,
+        reply_event_id: &str,
+        command: &CommandReply,
+    ) -> Result<(), DataError>;
+
+    /// The command message a roll reply of the bot answered, if any.
+    async fn get_command_reply(
+        &self,
+        room_id: &str,
+        reply_event_id: &str,
+    ) -> Result<Option<CommandReply>, DataError>;
+}
+
+/// Settings of a room, stored as text. A room that never changed a
+/// setting has no value for it.
+#[async_trait]
+pub(crate) trait RoomSettings {
+    async fn get_room_setting(
+        &self,
+        room_id: &str,
+        setting: RoomSetting,
+    ) -> Result<Option<String>, DataError>;
+
+    /// Change a setting, or go back to the default if no value is
+    /// given.
+    async fn set_room_setting(
+        &self,
+        room_id: &str,
+        setting: RoomSetting,
+        value: Option<&str>,
+    ) -> Result<(), DataError>;
+}
+
+#[async_trait]
+pub(crate) trait RollHistory {
+    /// Record a roll made by a user in a room, timestamped now. The
+    /// thread is given if the roll was made in a thread of a room
+    /// that keeps the history of each thread apart, and the fair mode
+    /// session if the dice were seeded from its secret.
+    async fn record_roll(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        command: &str,
+        result: &RollSummary,
+        fair_session: Option<i64>,
+    ) -> Result<(), DataError>;
+
+    /// The most recent rolls of a user in a room, newest first. If a
+    /// thread is given, only rolls made in that thread are returned.
+    async fn get_user_rolls(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError>;
+
+    /// The most recent rolls of everyone in a room, newest first. If
+    /// a thread is given, only rolls made in that thread are returned.
+    async fn get_room_rolls(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError>;
+
+    /// The most recent rolls in a room made at or after the given
+    /// UNIX timestamp, at most limit of them, oldest first.
+    async fn get_room_rolls_since(
+        &self,
+        room_id: &str,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError>;
+}
+
+#[async_trait]
+pub(crate) trait Characters {
+    /// Create a new character for a user in a room, and make it the
+    /// active character.
+    async fn create_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError>;
+
+    /// All characters of a user in a room, sorted by name.
+    async fn get_characters(&self, user: &str, room_id: &str) -> Result<Vec<Character>, DataError>;
+
+    /// Make the named character active, or go back to the user's own
+    /// variables if no name is given.
+    async fn set_active_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: Option<&str>,
+    ) -> Result<(), DataError>;
+
+    /// Delete a character and all of its variables.
+    async fn delete_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError>;
+}
+
+/// Macros of a user in a room, and macros shared by the whole room.
+/// Functions taking an optional user work on the room's macros when
+/// no user is given.
+#[async_trait]
+pub(crate) trait Macros {
+    /// The user's macros and the room's macros, sorted by name.
+    async fn get_macros(&self, user: &str, room_id: &str) -> Result<Vec<Macro>, DataError>;
+
+    /// Look up a macro by name. The user's own macro wins over a room
+    /// macro of the same name.
+    async fn get_macro(&self, user: &str, room_id: &str, name: &str) -> Result<Macro, DataError>;
+
+    /// Number of macros owned by the user, or by the room.
+    async fn count_macros(&self, user: Option<&str>, room_id: &str) -> Result<i64, DataError>;
+
+    /// Create a macro, or replace the body of an existing one.
+    async fn set_macro(
+        &self,
+        user: Option<&str>,
+        room_id: &str,
+        name: &str,
+        body: &str,
+    ) -> Result<(), DataError>;
+
+    async fn delete_macro(
+        &self,
+        user: Option<&str>,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError>;
+}
+
+/// The sessions of fair mode. At most one session is unrevealed: the
+/// current one, whose secret the rolls are seeded from.
+#[async_trait]
+pub(crate) trait FairnessSessions {
+    /// Reveal the current session, if any, and start a new one.
+    async fn start_fairness_session(
+        &self,
+        secret: &str,
+        commitment: &str,
+    ) -> Result<FairnessSession, DataError>;
+
+    /// Reveal the current session, if any. Returns whether there was one.
+    async fn reveal_fairness_session(&self) -> Result<bool, DataError>;
+
+    async fn current_fairness_session(&self) -> Result<Option<FairnessSession>, DataError>;
+
+    /// The most recently revealed sessions, newest first.
+    async fn revealed_fairness_sessions(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<FairnessSession>, DataError>;
 }
 
 // TODO move this up to the top once we delete sled. Traits will be the
 // main API, then we can have different impls for different DBs.
+/// Variables of a user in a room. If the user has an active character
+/// in the room, the variables belong to that character. Functions
+/// dealing in i32 only see integer variables; the others work with
+/// variables of every type.
 #[async_trait]
 pub trait Variables {
+    async fn get_variable_values(
+        &self,
+        user: &str,
+        room_id: &str,
+    ) -> Result<HashMap<String, VariableValue>, DataError>;
+
+    async fn get_variable_value(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+    ) -> Result<VariableValue, DataError>;
+
+    /// Set a variable of any type, replacing the old value and type.
+    async fn set_variable_value(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+        value: &VariableValue,
+    ) -> Result<(), DataError>;
+
     async fn get_user_variables(
         &self,
         user: &str,
@@ -61,10 +279,64 @@ pub trait Variables {
         value: i32,
     ) -> Result<(), DataError>;
 
+    /// Change a variable and its bounds in a single transaction. The
+    /// new value is limited to the variable's bounds. Adding to a
+    /// variable that is not set is an error.
+    async fn update_user_variable(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+        change: ValueChange,
+        min: BoundChange,
+        max: BoundChange,
+    ) -> Result<UpdatedVariable, DataError>;
+
+    /// Set many variables at once, replacing any existing values. Either
+    /// all of the variables are set, or none are. Existing text and
+    /// dice variables are left alone, and their names are returned.
+    async fn set_user_variables(
+        &self,
+        user: &str,
+        room_id: &str,
+        variables: &[(String, i32)],
+    ) -> Result<Vec<String>, DataError>;
+
     async fn delete_user_variable(
         &self,
         user: &str,
         room_id: &str,
         variable_name: &str,
     ) -> Result<(), DataError>;
+
+    /// Delete every variable of a user, of all their characters, in
+    /// one room or in every room. Returns how many were deleted.
+    async fn delete_all_user_variables(
+        &self,
+        user: &str,
+        room_id: Option<&str>,
+    ) -> Result<u64, DataError>;
+}
+
+/// Variables shared by everyone in a room, like a Momentum pool or a
+/// clock. Stored separately from user variables.
+#[async_trait]
+pub trait RoomVariables {
+    async fn get_room_variables(&self, room_id: &str) -> Result<HashMap<String, i32>, DataError>;
+
+    async fn get_room_variable(&self, room_id: &str, variable_name: &str)
+        -> Result<i32, DataError>;
+
+    async fn set_room_variable(
+        &self,
+        room_id: &str,
+        variable_name: &str,
+        value: i32,
+    ) -> Result<(), DataError>;
+
+    async fn delete_room_variable(
+        &self,
+        room_id: &str,
+        variable_name: &str,
+    ) -> Result<(), DataError>;
 }
@@ -0,0 +1,282 @@
+use super::Database;
+use crate::db::{errors::DataError, Characters};
+use crate::models::Character;
+use async_trait::async_trait;
+
+#[async_trait]
+impl Characters for Database {
+    async fn create_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError> {
+        let mut tx = self.conn.begin().await?;
+
+        let existing: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM characters WHERE user_id = ? AND room_id = ? AND name = ?",
+        )
+        .bind(user)
+        .bind(room_id)
+        .bind(name)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if existing.is_some() {
+            return Err(DataError::KeyAlreadyExists(name.to_string()));
+        }
+
+        sqlx::query("UPDATE characters SET active = 0 WHERE user_id = ? AND room_id = ?")
+            .bind(user)
+            .bind(room_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO characters (user_id, room_id, name, active)
+             VALUES (?, ?, ?, 1)",
+        )
+        .bind(user)
+        .bind(room_id)
+        .bind(name)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_characters(&self, user: &str, room_id: &str) -> Result<Vec<Character>, DataError> {
+        let characters = sqlx::query_as::<_, Character>(
+            "SELECT name, active FROM characters
+             WHERE user_id = ? AND room_id = ?
+             ORDER BY name",
+        )
+        .bind(user)
+        .bind(room_id)
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(characters)
+    }
+
+    async fn set_active_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: Option<&str>,
+    ) -> Result<(), DataError> {
+        let mut tx = self.conn.begin().await?;
+
+        // Comparing against NULL matches no character, which leaves
+        // the user without an active character.
+        sqlx::query(
+            "UPDATE characters SET active = COALESCE(name = ?, 0)
+             WHERE user_id = ? AND room_id = ?",
+        )
+        .bind(name)
+        .bind(user)
+        .bind(room_id)
+        .execute(&mut tx)
+        .await?;
+
+        if let Some(name) = name {
+            let active: Option<(i64,)> = sqlx::query_as(
+                "SELECT id FROM characters
+                 WHERE user_id = ? AND room_id = ? AND active = 1",
+            )
+            .bind(user)
+            .bind(room_id)
+            .fetch_optional(&mut tx)
+            .await?;
+
+            if active.is_none() {
+                return Err(DataError::KeyDoesNotExist(name.to_string()));
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_character(
+        &self,
+        user: &str,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError> {
+        let mut tx = self.conn.begin().await?;
+
+        let character: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM characters WHERE user_id = ? AND room_id = ? AND name = ?",
+        )
+        .bind(user)
+        .bind(room_id)
+        .bind(name)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let (character_id,) =
+            character.ok_or_else(|| DataError::KeyDoesNotExist(name.to_string()))?;
+
+        sqlx::query("DELETE FROM user_variables WHERE character_id = ?")
+            .bind(character_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("DELETE FROM characters WHERE id = ?")
+            .bind(character_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::Database;
+    use crate::db::{Characters, Variables};
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    fn character(name: &str, active: bool) -> Character {
+        Character {
+            name: name.to_string(),
+            active,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn create_character_makes_it_active_test() {
+        with_db(|db| async move {
+            db.create_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not create character");
+
+            db.create_character("myuser", "myroom", "Bob")
+                .await
+                .expect("Could not create character");
+
+            let characters = db
+                .get_characters("myuser", "myroom")
+                .await
+                .expect("Could not get characters");
+
+            assert_eq!(
+                characters,
+                vec![character("Alice", false), character("Bob", true)]
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn create_duplicate_character_test() {
+        with_db(|db| async move {
+            db.create_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not create character");
+
+            let result = db.create_character("myuser", "myroom", "alice").await;
+            assert!(matches!(result, Err(DataError::KeyAlreadyExists(_))));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn switch_to_missing_character_test() {
+        with_db(|db| async move {
+            db.create_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not create character");
+
+            let result = db
+                .set_active_character("myuser", "myroom", Some("Bob"))
+                .await;
+
+            assert!(matches!(result, Err(DataError::KeyDoesNotExist(_))));
+
+            // The failed switch must not deactivate the current character.
+            let characters = db.get_characters("myuser", "myroom").await.unwrap();
+            assert_eq!(characters, vec![character("Alice", true)]);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn variables_belong_to_active_character_test() {
+        with_db(|db| async move {
+            db.set_user_variable("myuser", "myroom", "strength", 1)
+                .await
+                .expect("Could not set variable");
+
+            db.create_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not create character");
+
+            db.set_user_variable("myuser", "myroom", "strength", 4)
+                .await
+                .expect("Could not set variable");
+
+            let alice_value = db.get_user_variable("myuser", "myroom", "strength").await;
+            assert_eq!(alice_value.unwrap(), 4);
+
+            db.set_active_character("myuser", "myroom", None)
+                .await
+                .expect("Could not switch character");
+
+            let own_value = db.get_user_variable("myuser", "myroom", "strength").await;
+            assert_eq!(own_value.unwrap(), 1);
+
+            db.set_active_character("myuser", "myroom", Some("alice"))
+                .await
+                .expect("Could not switch character");
+
+            let variables = db.get_user_variables("myuser", "myroom").await.unwrap();
+            assert_eq!(variables.get("strength"), Some(&4));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn delete_character_removes_variables_test() {
+        with_db(|db| async move {
+            db.create_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not create character");
+
+            db.set_user_variable("myuser", "myroom", "strength", 4)
+                .await
+                .expect("Could not set variable");
+
+            db.delete_character("myuser", "myroom", "Alice")
+                .await
+                .expect("Could not delete character");
+
+            let characters = db.get_characters("myuser", "myroom").await.unwrap();
+            assert!(characters.is_empty());
+
+            let count = db.get_variable_count("myuser", "myroom").await.unwrap();
+            assert_eq!(count, 0);
+        })
+        .await;
+    }
+}
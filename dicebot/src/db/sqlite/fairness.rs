@@ -0,0 +1,137 @@
+use super::Database;
+use crate::db::{errors::DataError, FairnessSessions};
+use crate::models::FairnessSession;
+use async_trait::async_trait;
+use sqlx::{Sqlite, Transaction};
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> Result<i64, DataError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock has gone backwards")
+        .as_secs();
+
+    Ok(i64::try_from(now)?)
+}
+
+async fn reveal(tx: &mut Transaction<'_, Sqlite>, revealed_at: i64) -> Result<bool, DataError> {
+    let result =
+        sqlx::query("UPDATE fairness_sessions SET revealed_at = ? WHERE revealed_at IS NULL")
+            .bind(revealed_at)
+            .execute(tx)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[async_trait]
+impl FairnessSessions for Database {
+    async fn start_fairness_session(
+        &self,
+        secret: &str,
+        commitment: &str,
+    ) -> Result<FairnessSession, DataError> {
+        let now = now()?;
+        let mut tx = self.conn.begin().await?;
+        reveal(&mut tx, now).await?;
+
+        let id = sqlx::query(
+            "INSERT INTO fairness_sessions (secret, commitment, started_at) VALUES (?, ?, ?)",
+        )
+        .bind(secret)
+        .bind(commitment)
+        .bind(now)
+        .execute(&mut tx)
+        .await?
+        .last_insert_rowid();
+
+        tx.commit().await?;
+
+        Ok(FairnessSession {
+            id,
+            secret: secret.to_owned(),
+            commitment: commitment.to_owned(),
+            started_at: now,
+            revealed_at: None,
+        })
+    }
+
+    async fn reveal_fairness_session(&self) -> Result<bool, DataError> {
+        let mut tx = self.conn.begin().await?;
+        let revealed = reveal(&mut tx, now()?).await?;
+        tx.commit().await?;
+        Ok(revealed)
+    }
+
+    async fn current_fairness_session(&self) -> Result<Option<FairnessSession>, DataError> {
+        let session = sqlx::query_as::<_, FairnessSession>(
+            "SELECT id, secret, commitment, started_at, revealed_at FROM fairness_sessions
+             WHERE revealed_at IS NULL ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn revealed_fairness_sessions(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<FairnessSession>, DataError> {
+        let sessions = sqlx::query_as::<_, FairnessSession>(
+            "SELECT id, secret, commitment, started_at, revealed_at FROM fairness_sessions
+             WHERE revealed_at IS NOT NULL ORDER BY id DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn starting_session_reveals_previous_test() {
+        with_db(|db| async move {
+            assert_eq!(db.current_fairness_session().await.unwrap(), None);
+
+            let first = db.start_fairness_session("one", "hash one").await.unwrap();
+            let second = db.start_fairness_session("two", "hash two").await.unwrap();
+            assert_eq!(db.current_fairness_session().await.unwrap(), Some(second));
+
+            let revealed = db.revealed_fairness_sessions(5).await.unwrap();
+            assert_eq!(revealed.len(), 1);
+            assert_eq!(revealed[0].id, first.id);
+            assert_eq!(revealed[0].secret, "one");
+            assert!(revealed[0].revealed_at.is_some());
+
+            assert!(db.reveal_fairness_session().await.unwrap());
+            assert!(!db.reveal_fairness_session().await.unwrap());
+            assert_eq!(db.current_fairness_session().await.unwrap(), None);
+            assert_eq!(db.revealed_fairness_sessions(5).await.unwrap().len(), 2);
+        })
+        .await;
+    }
+}
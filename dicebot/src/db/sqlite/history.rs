@@ -0,0 +1,368 @@
+use super::Database;
+use crate::db::{errors::DataError, RollHistory};
+use crate::models::{RollRecord, RollSummary};
+use async_trait::async_trait;
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A roll history row as stored in the database. The result column
+/// holds the JSON-serialized RollSummary.
+#[derive(sqlx::FromRow)]
+struct RollHistoryRow {
+    room_id: String,
+    user_id: String,
+    command: String,
+    result: String,
+    roll_timestamp: i64,
+    fair_session_id: Option<i64>,
+}
+
+impl TryFrom<RollHistoryRow> for RollRecord {
+    type Error = DataError;
+
+    fn try_from(row: RollHistoryRow) -> Result<Self, Self::Error> {
+        Ok(RollRecord {
+            room_id: row.room_id,
+            user_id: row.user_id,
+            command: row.command,
+            result: serde_json::from_str(&row.result)?,
+            timestamp: row.roll_timestamp,
+            fair_session: row.fair_session_id,
+        })
+    }
+}
+
+fn into_records(rows: Vec<RollHistoryRow>) -> Result<Vec<RollRecord>, DataError> {
+    rows.into_iter().map(RollRecord::try_from).collect()
+}
+
+#[async_trait]
+impl RollHistory for Database {
+    async fn record_roll(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        command: &str,
+        result: &RollSummary,
+        fair_session: Option<i64>,
+    ) -> Result<(), DataError> {
+        let now: i64 = i64::try_from(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Clock has gone backwards")
+                .as_secs(),
+        )?;
+
+        let result = serde_json::to_string(result)?;
+
+        sqlx::query(
+            r#"INSERT INTO roll_history
+                      (room_id, thread_id, user_id, command, result, roll_timestamp,
+                       fair_session_id)
+                      VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(room_id)
+        .bind(thread_id)
+        .bind(user_id)
+        .bind(command)
+        .bind(result)
+        .bind(now)
+        .bind(fair_session)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_rolls(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError> {
+        let rows: Vec<RollHistoryRow> = sqlx::query_as(
+            r#"SELECT room_id, user_id, command, result, roll_timestamp, fair_session_id
+               FROM roll_history
+               WHERE room_id = ? AND (? IS NULL OR thread_id = ?) AND user_id = ?
+               ORDER BY roll_timestamp DESC, id DESC
+               LIMIT ?"#,
+        )
+        .bind(room_id)
+        .bind(thread_id)
+        .bind(thread_id)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+
+        into_records(rows)
+    }
+
+    async fn get_room_rolls(
+        &self,
+        room_id: &str,
+        thread_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError> {
+        let rows: Vec<RollHistoryRow> = sqlx::query_as(
+            r#"SELECT room_id, user_id, command, result, roll_timestamp, fair_session_id
+               FROM roll_history
+               WHERE room_id = ? AND (? IS NULL OR thread_id = ?)
+               ORDER BY roll_timestamp DESC, id DESC
+               LIMIT ?"#,
+        )
+        .bind(room_id)
+        .bind(thread_id)
+        .bind(thread_id)
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+
+        into_records(rows)
+    }
+
+    async fn get_room_rolls_since(
+        &self,
+        room_id: &str,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<RollRecord>, DataError> {
+        let rows: Vec<RollHistoryRow> = sqlx::query_as(
+            r#"SELECT room_id, user_id, command, result, roll_timestamp, fair_session_id
+               FROM (SELECT * FROM roll_history
+                     WHERE room_id = ? AND roll_timestamp >= ?
+                     ORDER BY roll_timestamp DESC, id DESC
+                     LIMIT ?)
+               ORDER BY roll_timestamp ASC, id ASC"#,
+        )
+        .bind(room_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+
+        into_records(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::Database;
+    use crate::db::RollHistory;
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    fn summary(total: i32) -> RollSummary {
+        RollSummary {
+            expression: "1d20".to_string(),
+            dice: vec![total],
+            total,
+            outcome: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn record_and_get_user_rolls_test() {
+        with_db(|db| async move {
+            for total in 1..=3 {
+                db.record_roll(
+                    "myroom",
+                    None,
+                    "myuser",
+                    "!roll 1d20",
+                    &summary(total),
+                    None,
+                )
+                .await
+                .expect("Could not record roll");
+            }
+
+            let rolls = db
+                .get_user_rolls("myroom", None, "myuser", 2)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(rolls.len(), 2);
+            assert_eq!(rolls[0].result, summary(3));
+            assert_eq!(rolls[1].result, summary(2));
+            assert_eq!(rolls[0].command, "!roll 1d20");
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn user_rolls_respect_user_and_room_test() {
+        with_db(|db| async move {
+            db.record_roll("myroom", None, "otheruser", "!roll 1d20", &summary(1), None)
+                .await
+                .expect("Could not record roll");
+
+            db.record_roll("otherroom", None, "myuser", "!roll 1d20", &summary(1), None)
+                .await
+                .expect("Could not record roll");
+
+            let rolls = db
+                .get_user_rolls("myroom", None, "myuser", 10)
+                .await
+                .expect("Could not get rolls");
+
+            assert!(rolls.is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn get_room_rolls_includes_all_users_test() {
+        with_db(|db| async move {
+            db.record_roll("myroom", None, "user1", "!roll 1d20", &summary(1), None)
+                .await
+                .expect("Could not record roll");
+
+            db.record_roll("myroom", None, "user2", "!roll 1d20", &summary(2), None)
+                .await
+                .expect("Could not record roll");
+
+            let rolls = db
+                .get_room_rolls("myroom", None, 10)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(rolls.len(), 2);
+            assert_eq!(rolls[0].user_id, "user2");
+            assert_eq!(rolls[1].user_id, "user1");
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn thread_rolls_are_kept_apart_test() {
+        with_db(|db| async move {
+            db.record_roll("myroom", None, "myuser", "!roll 1d20", &summary(1), None)
+                .await
+                .expect("Could not record roll");
+
+            db.record_roll(
+                "myroom",
+                Some("$thread"),
+                "myuser",
+                "!roll 1d20",
+                &summary(2),
+                None,
+            )
+            .await
+            .expect("Could not record roll");
+
+            let thread_rolls = db
+                .get_room_rolls("myroom", Some("$thread"), 10)
+                .await
+                .expect("Could not get rolls");
+
+            let user_rolls = db
+                .get_user_rolls("myroom", Some("$other"), "myuser", 10)
+                .await
+                .expect("Could not get rolls");
+
+            let all_rolls = db
+                .get_room_rolls("myroom", None, 10)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(thread_rolls.len(), 1);
+            assert_eq!(thread_rolls[0].result, summary(2));
+            assert!(user_rolls.is_empty());
+            assert_eq!(all_rolls.len(), 2);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fair_session_is_recorded_test() {
+        with_db(|db| async move {
+            db.record_roll("myroom", None, "myuser", "!roll 1d20", &summary(1), Some(3))
+                .await
+                .expect("Could not record roll");
+
+            db.record_roll("myroom", None, "myuser", "!roll 1d20", &summary(2), None)
+                .await
+                .expect("Could not record roll");
+
+            let rolls = db
+                .get_room_rolls("myroom", None, 10)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(rolls[0].fair_session, None);
+            assert_eq!(rolls[1].fair_session, Some(3));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn get_room_rolls_since_filters_by_time_test() {
+        with_db(|db| async move {
+            db.record_roll("myroom", None, "myuser", "!roll 1d20", &summary(1), None)
+                .await
+                .expect("Could not record roll");
+
+            let all_rolls = db
+                .get_room_rolls_since("myroom", 0, 10)
+                .await
+                .expect("Could not get rolls");
+
+            let future_rolls = db
+                .get_room_rolls_since("myroom", i64::MAX, 10)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(all_rolls.len(), 1);
+            assert!(future_rolls.is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn get_room_rolls_since_keeps_newest_test() {
+        with_db(|db| async move {
+            for total in 1..=3 {
+                db.record_roll(
+                    "myroom",
+                    None,
+                    "myuser",
+                    "!roll 1d20",
+                    &summary(total),
+                    None,
+                )
+                .await
+                .expect("Could not record roll");
+            }
+
+            let rolls = db
+                .get_room_rolls_since("myroom", 0, 2)
+                .await
+                .expect("Could not get rolls");
+
+            assert_eq!(rolls.len(), 2);
+            assert_eq!(rolls[0].result, summary(2));
+            assert_eq!(rolls[1].result, summary(3));
+        })
+        .await;
+    }
+}
@@ -0,0 +1,183 @@
+use super::Database;
+use crate::db::{errors::DataError, Macros};
+use crate::models::Macro;
+use crate::parser::variables::normalize_variable_name;
+use async_trait::async_trait;
+
+/// Room macros are stored with an empty user ID.
+fn owner(user: Option<&str>) -> &str {
+    user.unwrap_or("")
+}
+
+#[async_trait]
+impl Macros for Database {
+    async fn get_macros(&self, user: &str, room_id: &str) -> Result<Vec<Macro>, DataError> {
+        let macros = sqlx::query_as::<_, Macro>(
+            "SELECT name, body, user_id = '' AS room FROM macros
+             WHERE room_id = ? AND user_id IN (?, '')
+             ORDER BY name, room",
+        )
+        .bind(room_id)
+        .bind(user)
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(macros)
+    }
+
+    async fn get_macro(&self, user: &str, room_id: &str, name: &str) -> Result<Macro, DataError> {
+        // The user's own macro sorts before the room macro.
+        let found = sqlx::query_as::<_, Macro>(
+            "SELECT name, body, user_id = '' AS room FROM macros
+             WHERE room_id = ? AND user_id IN (?, '') AND name = ?
+             ORDER BY room LIMIT 1",
+        )
+        .bind(room_id)
+        .bind(user)
+        .bind(normalize_variable_name(name))
+        .fetch_optional(&self.conn)
+        .await?;
+
+        found.ok_or_else(|| DataError::KeyDoesNotExist(name.to_string()))
+    }
+
+    async fn count_macros(&self, user: Option<&str>, room_id: &str) -> Result<i64, DataError> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM macros WHERE room_id = ? AND user_id = ?")
+                .bind(room_id)
+                .bind(owner(user))
+                .fetch_one(&self.conn)
+                .await?;
+
+        Ok(count)
+    }
+
+    async fn set_macro(
+        &self,
+        user: Option<&str>,
+        room_id: &str,
+        name: &str,
+        body: &str,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            "INSERT INTO macros (room_id, user_id, name, body) VALUES (?, ?, ?, ?)
+             ON CONFLICT(room_id, user_id, name) DO UPDATE SET body = excluded.body",
+        )
+        .bind(room_id)
+        .bind(owner(user))
+        .bind(normalize_variable_name(name))
+        .bind(body)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_macro(
+        &self,
+        user: Option<&str>,
+        room_id: &str,
+        name: &str,
+    ) -> Result<(), DataError> {
+        let result =
+            sqlx::query("DELETE FROM macros WHERE room_id = ? AND user_id = ? AND name = ?")
+                .bind(room_id)
+                .bind(owner(user))
+                .bind(normalize_variable_name(name))
+                .execute(&self.conn)
+                .await?;
+
+        match result.rows_affected() {
+            0 => Err(DataError::KeyDoesNotExist(name.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::Database;
+    use crate::db::Macros;
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn user_macro_wins_over_room_macro_test() {
+        with_db(|db| async move {
+            db.set_macro(None, "myroom", "shoot", "!pool 3")
+                .await
+                .expect("Could not set macro");
+
+            let found = db.get_macro("myuser", "myroom", "Shoot").await.unwrap();
+            assert_eq!(found.body, "!pool 3");
+            assert!(found.room);
+
+            db.set_macro(Some("myuser"), "myroom", "shoot", "!pool 5")
+                .await
+                .expect("Could not set macro");
+
+            let found = db.get_macro("myuser", "myroom", "shoot").await.unwrap();
+            assert_eq!(found.body, "!pool 5");
+            assert!(!found.room);
+
+            let other = db.get_macro("otheruser", "myroom", "shoot").await.unwrap();
+            assert_eq!(other.body, "!pool 3");
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn list_count_and_delete_macros_test() {
+        with_db(|db| async move {
+            db.set_macro(Some("myuser"), "myroom", "shoot", "!pool 3")
+                .await
+                .expect("Could not set macro");
+
+            db.set_macro(Some("myuser"), "myroom", "shoot", "!pool 4")
+                .await
+                .expect("Could not set macro");
+
+            db.set_macro(None, "myroom", "init", "!r 1d20")
+                .await
+                .expect("Could not set macro");
+
+            db.set_macro(Some("otheruser"), "myroom", "dodge", "!r 1d6")
+                .await
+                .expect("Could not set macro");
+
+            let macros = db.get_macros("myuser", "myroom").await.unwrap();
+            let names: Vec<&str> = macros.iter().map(|m| m.name.as_str()).collect();
+            assert_eq!(names, vec!["init", "shoot"]);
+
+            let count = db.count_macros(Some("myuser"), "myroom").await.unwrap();
+            assert_eq!(count, 1);
+
+            db.delete_macro(Some("myuser"), "myroom", "shoot")
+                .await
+                .expect("Could not delete macro");
+
+            let result = db.delete_macro(Some("myuser"), "myroom", "init").await;
+            assert!(matches!(result, Err(DataError::KeyDoesNotExist(_))));
+
+            let result = db.get_macro("myuser", "myroom", "shoot").await;
+            assert!(matches!(result, Err(DataError::KeyDoesNotExist(_))));
+        })
+        .await;
+    }
+}
@@ -0,0 +1,19 @@
+use barrel::backend::Sqlite;
+use barrel::{types, Migration};
+
+pub fn migration() -> String {
+    let mut m = Migration::new();
+
+    //Every roll made by a user in a room, with its structured result
+    //stored as JSON.
+    m.create_table("roll_history", move |t| {
+        t.add_column("id", types::primary());
+        t.add_column("room_id", types::text().nullable(false));
+        t.add_column("user_id", types::text().nullable(false));
+        t.add_column("command", types::text().nullable(false));
+        t.add_column("result", types::text().nullable(false));
+        t.add_column("roll_timestamp", types::integer().nullable(false));
+    });
+
+    m.make::<Sqlite>()
+}
@@ -0,0 +1,13 @@
+pub fn migration() -> String {
+    // Room variables are unique per room, so setting one replaces
+    // the old value.
+    r#"
+      CREATE TABLE IF NOT EXISTS "room_variables" (
+         "room_id" TEXT NOT NULL,
+         "key" TEXT NOT NULL,
+         "value" INTEGER NOT NULL,
+         PRIMARY KEY ("room_id", "key")
+      );
+    "#
+    .to_string()
+}
@@ -0,0 +1,18 @@
+pub fn migration() -> String {
+    // Characters belong to a user in a room. At most one of them is
+    // active at a time. Variables set while no character is active
+    // keep character_id 0, which is also what all existing variables
+    // get.
+    r#"
+      CREATE TABLE IF NOT EXISTS "characters" (
+         "id" INTEGER PRIMARY KEY NOT NULL,
+         "user_id" TEXT NOT NULL,
+         "room_id" TEXT NOT NULL,
+         "name" TEXT NOT NULL COLLATE NOCASE,
+         "active" INTEGER NOT NULL DEFAULT 0,
+         UNIQUE ("user_id", "room_id", "name")
+      );
+      ALTER TABLE user_variables ADD COLUMN "character_id" INTEGER NOT NULL DEFAULT 0;
+    "#
+    .to_string()
+}
@@ -0,0 +1,17 @@
+pub fn migration() -> String {
+    // Setting a variable used to insert a new row every time. Keep
+    // only the most recent value of each variable, so that variables
+    // can be updated in place from now on. Variables may also have
+    // optional minimum and maximum values.
+    r#"
+      DELETE FROM user_variables WHERE rowid NOT IN (
+         SELECT MAX(rowid) FROM user_variables
+         GROUP BY user_id, room_id, character_id, key
+      );
+      CREATE UNIQUE INDEX IF NOT EXISTS "user_variables_unique_key"
+         ON user_variables (user_id, room_id, character_id, key);
+      ALTER TABLE user_variables ADD COLUMN "min_value" INTEGER;
+      ALTER TABLE user_variables ADD COLUMN "max_value" INTEGER;
+    "#
+    .to_string()
+}
@@ -0,0 +1,10 @@
+pub fn migration() -> String {
+    // Variables can hold text or dice expressions as well as
+    // integers. Those are kept in text_value, and value is 0. Every
+    // existing variable is an integer.
+    r#"
+      ALTER TABLE user_variables ADD COLUMN "kind" TEXT NOT NULL DEFAULT 'integer';
+      ALTER TABLE user_variables ADD COLUMN "text_value" TEXT;
+    "#
+    .to_string()
+}
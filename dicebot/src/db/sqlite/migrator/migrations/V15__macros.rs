@@ -0,0 +1,14 @@
+pub fn migration() -> String {
+    // Macros belong to a user in a room. Room macros are shared by
+    // everyone in the room, and have an empty user ID.
+    r#"
+      CREATE TABLE IF NOT EXISTS "macros" (
+         "room_id" TEXT NOT NULL,
+         "user_id" TEXT NOT NULL,
+         "name" TEXT NOT NULL,
+         "body" TEXT NOT NULL,
+         PRIMARY KEY ("room_id", "user_id", "name")
+      );
+    "#
+    .to_string()
+}
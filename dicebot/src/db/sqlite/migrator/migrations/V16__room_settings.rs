@@ -0,0 +1,13 @@
+pub fn migration() -> String {
+    // Settings changed by room moderators, one row per setting. Rooms
+    // without a row use the default.
+    r#"
+      CREATE TABLE IF NOT EXISTS "room_settings" (
+         "room_id" TEXT NOT NULL,
+         "key" TEXT NOT NULL,
+         "value" TEXT NOT NULL,
+         PRIMARY KEY ("room_id", "key")
+      );
+    "#
+    .to_string()
+}
@@ -0,0 +1,13 @@
+pub fn migration() -> String {
+    // The reply the bot sent to a command message, so that the reply
+    // can be edited when the command message is edited.
+    r#"
+      CREATE TABLE IF NOT EXISTS "replies" (
+         "room_id" TEXT NOT NULL,
+         "event_id" TEXT NOT NULL,
+         "reply_event_id" TEXT NOT NULL,
+         PRIMARY KEY ("room_id", "event_id")
+      );
+    "#
+    .to_string()
+}
@@ -0,0 +1,8 @@
+pub fn migration() -> String {
+    // The thread a roll was made in, for rooms that keep the roll
+    // history of each thread apart.
+    r#"
+      ALTER TABLE roll_history ADD COLUMN "thread_id" TEXT;
+    "#
+    .to_string()
+}
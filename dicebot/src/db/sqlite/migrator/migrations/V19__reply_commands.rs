@@ -0,0 +1,16 @@
+pub fn migration() -> String {
+    // The command message that each roll reply of the bot answered, so
+    // that the roll can be made again by reacting to the reply.
+    r#"
+      CREATE TABLE IF NOT EXISTS "reply_commands" (
+         "room_id" TEXT NOT NULL,
+         "reply_event_id" TEXT NOT NULL,
+         "event_id" TEXT NOT NULL,
+         "sender" TEXT NOT NULL,
+         "message" TEXT NOT NULL,
+         "thread_id" TEXT,
+         PRIMARY KEY ("room_id", "reply_event_id")
+      );
+    "#
+    .to_string()
+}
@@ -0,0 +1,14 @@
+pub fn migration() -> String {
+    // The secrets that rolls are seeded from in fair mode. A session
+    // is revealed when the next one starts, or fair mode is turned off.
+    r#"
+      CREATE TABLE IF NOT EXISTS "fairness_sessions" (
+         "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+         "secret" TEXT NOT NULL,
+         "commitment" TEXT NOT NULL,
+         "started_at" INTEGER NOT NULL,
+         "revealed_at" INTEGER
+      );
+    "#
+    .to_string()
+}
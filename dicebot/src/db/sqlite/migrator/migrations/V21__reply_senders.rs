@@ -0,0 +1,9 @@
+pub fn migration() -> String {
+    // The sender of a command message that the bot replied to, so
+    // that only the sender's own edits of the command are answered.
+    // Replies recorded before have no sender.
+    r#"
+      ALTER TABLE replies ADD COLUMN "sender" TEXT;
+    "#
+    .to_string()
+}
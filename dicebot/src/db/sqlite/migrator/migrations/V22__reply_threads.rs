@@ -0,0 +1,8 @@
+pub fn migration() -> String {
+    // The thread a command message that the bot replied to was sent
+    // in. Edits do not say which thread the edited message is in.
+    r#"
+      ALTER TABLE replies ADD COLUMN "thread_id" TEXT;
+    "#
+    .to_string()
+}
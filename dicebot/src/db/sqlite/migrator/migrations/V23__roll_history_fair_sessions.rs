@@ -0,0 +1,8 @@
+pub fn migration() -> String {
+    // The fair mode session whose secret seeded the dice of a roll,
+    // so the roll can be verified once the secret is revealed.
+    r#"
+      ALTER TABLE roll_history ADD COLUMN "fair_session_id" INTEGER;
+    "#
+    .to_string()
+}
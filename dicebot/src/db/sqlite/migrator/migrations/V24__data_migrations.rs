@@ -0,0 +1,11 @@
+pub fn migration() -> String {
+    // Changes to the data that are made in Rust instead of SQL, like
+    // normalizing variable names, are recorded here once they are
+    // done, so that they run only once.
+    r#"
+      CREATE TABLE data_migrations (
+        name TEXT PRIMARY KEY NOT NULL
+      );
+    "#
+    .to_string()
+}
@@ -1,7 +1,8 @@
-use log::info;
+use crate::parser::variables::normalize_variable_name;
+use log::{info, warn};
 use refinery::config::{Config, ConfigDbType};
-use sqlx::sqlite::SqliteConnectOptions;
-use sqlx::ConnectOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{ConnectOptions, Connection};
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -29,5 +30,147 @@ pub async fn migrate(db_path: &str) -> Result<(), MigrationError> {
     let mut conn = Config::new(ConfigDbType::Sqlite).set_db_path(db_path);
     info!("Running migrations");
     migrations::runner().run(&mut conn)?;
+
+    let mut conn = SqliteConnectOptions::from_str(&format!("sqlite://{}", db_path))?
+        .connect()
+        .await?;
+
+    normalize_variable_names(&mut conn).await?;
+    Ok(())
+}
+
+/// Name of the variable name normalization in the data_migrations
+/// table.
+const NORMALIZE_VARIABLE_NAMES: &str = "normalize_variable_names";
+
+/// Variable names are case-insensitive, so they are stored in
+/// lowercase. SQLite's lower() only understands ASCII, which is why
+/// this is not a regular SQL migration. It runs once, and is then
+/// recorded in the data_migrations table. When two variables differ
+/// only by case, the one already in lowercase is kept, or else the
+/// oldest one. Every renamed and dropped variable is logged.
+async fn normalize_variable_names(conn: &mut SqliteConnection) -> Result<(), MigrationError> {
+    let mut tx = conn.begin().await?;
+
+    let done: Option<(String,)> = sqlx::query_as("SELECT name FROM data_migrations WHERE name = ?")
+        .bind(NORMALIZE_VARIABLE_NAMES)
+        .fetch_optional(&mut tx)
+        .await?;
+
+    if done.is_some() {
+        return Ok(());
+    }
+
+    info!("Normalizing variable names");
+
+    for table in &["user_variables", "room_variables"] {
+        let select = format!("SELECT rowid, key FROM {} ORDER BY rowid", table);
+        let rows: Vec<(i64, String)> = sqlx::query_as(&select).fetch_all(&mut tx).await?;
+
+        let update = format!("UPDATE OR IGNORE {} SET key = ? WHERE rowid = ?", table);
+        let delete = format!("DELETE FROM {} WHERE rowid = ? AND key = ?", table);
+
+        for (rowid, key) in rows {
+            let normalized = normalize_variable_name(&key);
+            if normalized == key {
+                continue;
+            }
+
+            let renamed = sqlx::query(&update)
+                .bind(&normalized)
+                .bind(rowid)
+                .execute(&mut tx)
+                .await?;
+
+            if renamed.rows_affected() > 0 {
+                info!("Renamed variable {} to {} in {}", key, normalized, table);
+                continue;
+            }
+
+            // The update is ignored if the normalized name is taken.
+            sqlx::query(&delete)
+                .bind(rowid)
+                .bind(&key)
+                .execute(&mut tx)
+                .await?;
+
+            warn!(
+                "Dropped variable {} (row {}) in {}: {} already exists",
+                key, rowid, table, normalized
+            );
+        }
+    }
+
+    sqlx::query("INSERT INTO data_migrations (name) VALUES (?)")
+        .bind(NORMALIZE_VARIABLE_NAMES)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn normalize_variable_names_test() {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        let db_path = db_path.path().to_str().unwrap();
+        migrate(db_path).await.unwrap();
+
+        let mut conn = SqliteConnectOptions::from_str(&format!("sqlite://{}", db_path))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        let variables = [
+            ("Stärke", 3),
+            ("str", 1),
+            ("STR", 2),
+            ("Dex", 4),
+            ("DEX", 5),
+        ];
+        for (key, value) in &variables {
+            sqlx::query(
+                "INSERT INTO user_variables (user_id, room_id, key, value)
+                 VALUES ('myuser', 'myroom', ?, ?)",
+            )
+            .bind(key)
+            .bind(value)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        }
+
+        // The names were normalized when the database was created, so
+        // the variables stay as they are.
+        migrate(db_path).await.unwrap();
+
+        let query = "SELECT key, value FROM user_variables";
+        let rows: Vec<(String, i32)> = sqlx::query_as(query).fetch_all(&mut conn).await.unwrap();
+        assert_eq!(rows.len(), variables.len());
+
+        // Databases from before the normalization get it once.
+        sqlx::query("DELETE FROM data_migrations")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        migrate(db_path).await.unwrap();
+
+        let mut rows: Vec<(String, i32)> =
+            sqlx::query_as(query).fetch_all(&mut conn).await.unwrap();
+        rows.sort();
+        assert_eq!(
+            rows,
+            vec![
+                ("dex".to_string(), 4),
+                ("str".to_string(), 1),
+                ("stärke".to_string(), 3)
+            ]
+        );
+    }
+}
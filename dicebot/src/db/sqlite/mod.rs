@@ -4,7 +4,13 @@ use sqlx::ConnectOptions;
 use std::clone::Clone;
 use std::str::FromStr;
 
+pub mod characters;
+pub mod fairness;
+pub mod history;
+pub mod macros;
 pub mod migrator;
+pub mod room_settings;
+pub mod room_variables;
 pub mod rooms;
 pub mod state;
 pub mod users;
@@ -0,0 +1,99 @@
+use super::Database;
+use crate::db::{errors::DataError, RoomSettings};
+use crate::models::RoomSetting;
+use async_trait::async_trait;
+
+#[async_trait]
+impl RoomSettings for Database {
+    async fn get_room_setting(
+        &self,
+        room_id: &str,
+        setting: RoomSetting,
+    ) -> Result<Option<String>, DataError> {
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT value FROM room_settings WHERE room_id = ? AND key = ?")
+                .bind(room_id)
+                .bind(setting.key())
+                .fetch_optional(&self.conn)
+                .await?;
+
+        Ok(row.map(|(value,)| value))
+    }
+
+    async fn set_room_setting(
+        &self,
+        room_id: &str,
+        setting: RoomSetting,
+        value: Option<&str>,
+    ) -> Result<(), DataError> {
+        let query = match value {
+            Some(value) => sqlx::query(
+                "INSERT INTO room_settings (room_id, key, value) VALUES (?, ?, ?)
+                 ON CONFLICT(room_id, key) DO UPDATE SET value = excluded.value",
+            )
+            .bind(room_id)
+            .bind(setting.key())
+            .bind(value),
+            None => sqlx::query("DELETE FROM room_settings WHERE room_id = ? AND key = ?")
+                .bind(room_id)
+                .bind(setting.key()),
+        };
+
+        query.execute(&self.conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::Database;
+    use crate::db::RoomSettings;
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_and_reset_room_setting_test() {
+        with_db(|db| async move {
+            let setting = RoomSetting::InlineRolls;
+            let value = db.get_room_setting("myroom", setting).await.unwrap();
+            assert_eq!(value, None);
+
+            db.set_room_setting("myroom", setting, Some("on"))
+                .await
+                .expect("Could not set setting");
+
+            db.set_room_setting("myroom", setting, Some("off"))
+                .await
+                .expect("Could not set setting");
+
+            let value = db.get_room_setting("myroom", setting).await.unwrap();
+            let other = db.get_room_setting("otherroom", setting).await.unwrap();
+            assert_eq!(value.as_deref(), Some("off"));
+            assert_eq!(other, None);
+
+            db.set_room_setting("myroom", setting, None)
+                .await
+                .expect("Could not reset setting");
+
+            let value = db.get_room_setting("myroom", setting).await.unwrap();
+            assert_eq!(value, None);
+        })
+        .await;
+    }
+}
@@ -0,0 +1,170 @@
+use super::Database;
+use crate::db::{errors::DataError, RoomVariables};
+use crate::parser::variables::normalize_variable_name;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+#[async_trait]
+impl RoomVariables for Database {
+    async fn get_room_variables(&self, room_id: &str) -> Result<HashMap<String, i32>, DataError> {
+        let rows: Vec<(String, i32)> = sqlx::query_as(
+            r#"SELECT key, value FROM room_variables
+               WHERE room_id = ?"#,
+        )
+        .bind(room_id)
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn get_room_variable(
+        &self,
+        room_id: &str,
+        variable_name: &str,
+    ) -> Result<i32, DataError> {
+        let row: Option<(i32,)> = sqlx::query_as(
+            r#"SELECT value FROM room_variables
+               WHERE room_id = ? AND key = ?"#,
+        )
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .fetch_optional(&self.conn)
+        .await?;
+
+        row.map(|(value,)| value)
+            .ok_or_else(|| DataError::KeyDoesNotExist(variable_name.to_string()))
+    }
+
+    async fn set_room_variable(
+        &self,
+        room_id: &str,
+        variable_name: &str,
+        value: i32,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            "INSERT INTO room_variables (room_id, key, value) VALUES (?, ?, ?)
+             ON CONFLICT(room_id, key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(value)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_room_variable(
+        &self,
+        room_id: &str,
+        variable_name: &str,
+    ) -> Result<(), DataError> {
+        let result = sqlx::query("DELETE FROM room_variables WHERE room_id = ? AND key = ?")
+            .bind(room_id)
+            .bind(normalize_variable_name(variable_name))
+            .execute(&self.conn)
+            .await?;
+
+        match result.rows_affected() {
+            0 => Err(DataError::KeyDoesNotExist(variable_name.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::Database;
+    use crate::db::RoomVariables;
+    use std::future::Future;
+
+    async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
+    where
+        Fut: Future<Output = ()>,
+    {
+        let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+        crate::db::sqlite::migrator::migrate(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = Database::new(db_path.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        f(db).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_and_get_room_variable_test() {
+        with_db(|db| async move {
+            db.set_room_variable("myroom", "doom", 1)
+                .await
+                .expect("Could not set variable");
+
+            let value = db
+                .get_room_variable("myroom", "doom")
+                .await
+                .expect("Could not get variable");
+
+            assert_eq!(value, 1);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn setting_room_variable_replaces_value_test() {
+        with_db(|db| async move {
+            db.set_room_variable("myroom", "doom", 1)
+                .await
+                .expect("Could not set variable");
+
+            db.set_room_variable("myroom", "doom", 5)
+                .await
+                .expect("Could not set variable");
+
+            let variables = db
+                .get_room_variables("myroom")
+                .await
+                .expect("Could not get variables");
+
+            assert_eq!(variables.len(), 1);
+            assert_eq!(variables.get("doom"), Some(&5));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn room_variables_respect_room_id_test() {
+        with_db(|db| async move {
+            db.set_room_variable("otherroom", "doom", 1)
+                .await
+                .expect("Could not set variable");
+
+            let value = db.get_room_variable("myroom", "doom").await;
+            assert!(matches!(value, Err(DataError::KeyDoesNotExist(_))));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn delete_room_variable_test() {
+        with_db(|db| async move {
+            db.set_room_variable("myroom", "doom", 1)
+                .await
+                .expect("Could not set variable");
+
+            db.delete_room_variable("myroom", "doom")
+                .await
+                .expect("Could not delete variable");
+
+            let value = db.get_room_variable("myroom", "doom").await;
+            assert!(matches!(value, Err(DataError::KeyDoesNotExist(_))));
+
+            let deleted_again = db.delete_room_variable("myroom", "doom").await;
+            assert!(matches!(deleted_again, Err(DataError::KeyDoesNotExist(_))));
+        })
+        .await;
+    }
+}
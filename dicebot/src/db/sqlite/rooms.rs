@@ -1,5 +1,6 @@
 use super::Database;
 use crate::db::{errors::DataError, Rooms};
+use crate::models::{CommandReply, MessageReply};
 use async_trait::async_trait;
 use sqlx::SqlitePool;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -47,12 +48,93 @@ impl Rooms for Database {
             }
         }
     }
+
+    async fn record_reply(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        reply: &MessageReply,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            "INSERT INTO replies (room_id, event_id, reply_event_id, sender, thread_id)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(room_id, event_id) DO UPDATE SET reply_event_id = excluded.reply_event_id",
+        )
+        .bind(room_id)
+        .bind(event_id)
+        .bind(&reply.reply_event_id)
+        .bind(&reply.sender)
+        .bind(&reply.thread_id)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_reply(
+        &self,
+        room_id: &str,
+        event_id: &str,
+    ) -> Result<Option<MessageReply>, DataError> {
+        let reply = sqlx::query_as::<_, MessageReply>(
+            "SELECT reply_event_id, sender, thread_id FROM replies
+             WHERE room_id = ? AND event_id = ?",
+        )
+        .bind(room_id)
+        .bind(event_id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(reply)
+    }
+
+    async fn record_command_reply(
+        &self,
+        room_id: &str,
+        reply_event_id: &str,
+        command: &CommandReply,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            "INSERT INTO reply_commands
+                (room_id, reply_event_id, event_id, sender, message, thread_id)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(room_id, reply_event_id) DO UPDATE SET message = excluded.message",
+        )
+        .bind(room_id)
+        .bind(reply_event_id)
+        .bind(&command.event_id)
+        .bind(&command.sender)
+        .bind(&command.message)
+        .bind(&command.thread_id)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_command_reply(
+        &self,
+        room_id: &str,
+        reply_event_id: &str,
+    ) -> Result<Option<CommandReply>, DataError> {
+        let command = sqlx::query_as::<_, CommandReply>(
+            "SELECT event_id, sender, message, thread_id FROM reply_commands
+             WHERE room_id = ? AND reply_event_id = ?",
+        )
+        .bind(room_id)
+        .bind(reply_event_id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        Ok(command)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::db::sqlite::Database;
     use crate::db::Rooms;
+    use crate::models::{CommandReply, MessageReply};
     use std::future::Future;
 
     async fn with_db<Fut>(f: impl FnOnce(Database) -> Fut)
@@ -90,4 +172,63 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn record_and_get_reply_test() {
+        with_db(|db| async move {
+            let reply = db.get_reply("myroom", "command").await.unwrap();
+            assert_eq!(reply, None);
+
+            let reply = |reply_event_id: &str| MessageReply {
+                reply_event_id: reply_event_id.to_string(),
+                sender: Some("@user:example.com".to_string()),
+                thread_id: Some("thread".to_string()),
+            };
+
+            db.record_reply("myroom", "command", &reply("reply1"))
+                .await
+                .expect("Could not record reply");
+
+            db.record_reply("myroom", "command", &reply("reply2"))
+                .await
+                .expect("Could not record reply");
+
+            let recorded = db.get_reply("myroom", "command").await.unwrap();
+            let other = db.get_reply("otherroom", "command").await.unwrap();
+            assert_eq!(recorded, Some(reply("reply2")));
+            assert_eq!(other, None);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn record_and_get_command_reply_test() {
+        with_db(|db| async move {
+            let command = CommandReply {
+                event_id: "command".to_string(),
+                sender: "@user:example.com".to_string(),
+                message: "!r 1d20".to_string(),
+                thread_id: Some("thread".to_string()),
+            };
+
+            db.record_command_reply("myroom", "reply", &command)
+                .await
+                .expect("Could not record command reply");
+
+            let edited = CommandReply {
+                message: "!r 1d20+1".to_string(),
+                ..command.clone()
+            };
+
+            db.record_command_reply("myroom", "reply", &edited)
+                .await
+                .expect("Could not record command reply");
+
+            let found = db.get_command_reply("myroom", "reply").await.unwrap();
+            let other = db.get_command_reply("otherroom", "reply").await.unwrap();
+            assert_eq!(found, Some(edited));
+            assert_eq!(other, None);
+        })
+        .await;
+    }
 }
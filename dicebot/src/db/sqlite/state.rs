@@ -31,6 +31,16 @@ impl DbState for Database {
 
         Ok(())
     }
+
+    async fn database_size(&self) -> Result<i64, DataError> {
+        let (size,): (i64,) = sqlx::query_as(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.conn)
+        .await?;
+
+        Ok(size)
+    }
 }
 
 #[cfg(test)]
@@ -97,4 +107,13 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn database_size_test() {
+        with_db(|db| async move {
+            let size = db.database_size().await.expect("Could not get size");
+            assert!(size > 0);
+        })
+        .await;
+    }
 }
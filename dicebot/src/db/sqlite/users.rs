@@ -75,6 +75,23 @@ impl Users for Database {
         Ok(user_row)
     }
 
+    async fn get_users(&self) -> Result<Vec<User>, DataError> {
+        let users: Vec<User> = sqlx::query_as(
+            r#"SELECT
+               a.user_id as "username",
+               a.password,
+               s.active_room,
+               COALESCE(a.account_status, 'not_registered') as "account_status"
+               FROM accounts a
+               LEFT JOIN user_state s on a.user_id = s.user_id
+               ORDER BY a.user_id"#,
+        )
+        .fetch_all(&self.conn)
+        .await?;
+
+        Ok(users)
+    }
+
     async fn authenticate_user(
         &self,
         username: &str,
@@ -138,6 +155,26 @@ mod tests {
         .await;
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn get_all_users_test() {
+        with_db(|db| async move {
+            for username in &["userb", "usera"] {
+                db.upsert_user(&User {
+                    username: username.to_string(),
+                    account_status: AccountStatus::Registered,
+                    ..Default::default()
+                })
+                .await
+                .expect("Could not insert user");
+            }
+
+            let users = db.get_users().await.expect("User retrieval query failed");
+            let names: Vec<&str> = users.iter().map(|u| u.username.as_str()).collect();
+            assert_eq!(names, vec!["usera", "userb"]);
+        })
+        .await;
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn can_get_user_with_no_state_record() {
         with_db(|db| async move {
@@ -1,38 +1,163 @@
 use super::Database;
 use crate::db::{errors::DataError, Variables};
+use crate::models::{BoundChange, UpdatedVariable, ValueChange, VariableValue};
+use crate::parser::variables::normalize_variable_name;
 use async_trait::async_trait;
 use std::collections::HashMap;
 
+// Every query resolves the user's active character in the room with
+// this subquery. Variables set without an active character have
+// character ID 0. The subquery binds the user and room ID.
+macro_rules! active_character {
+    () => {
+        "COALESCE((SELECT id FROM characters
+                   WHERE user_id = ? AND room_id = ? AND active = 1), 0)"
+    };
+}
+
+/// Limit a value to the bounds of a variable.
+fn clamp(value: i32, min: Option<i32>, max: Option<i32>) -> i32 {
+    let value = min.map_or(value, |min| value.max(min));
+    max.map_or(value, |max| value.min(max))
+}
+
+/// Rebuild a variable from its columns in the database.
+fn variable_value(
+    kind: &str,
+    value: i32,
+    text: Option<String>,
+) -> Result<VariableValue, DataError> {
+    match (kind, text) {
+        ("integer", _) => Ok(VariableValue::Integer(value)),
+        ("text", Some(text)) => Ok(VariableValue::Text(text)),
+        ("dice", Some(expression)) => Ok(VariableValue::Dice(expression)),
+        _ => Err(DataError::InvalidValue),
+    }
+}
+
 #[async_trait]
 impl Variables for Database {
+    async fn get_variable_values(
+        &self,
+        user: &str,
+        room_id: &str,
+    ) -> Result<HashMap<String, VariableValue>, DataError> {
+        let rows: Vec<(String, String, i32, Option<String>)> = sqlx::query_as(concat!(
+            "SELECT key, kind, value, text_value FROM user_variables
+             WHERE room_id = ? AND user_id = ? AND character_id = ",
+            active_character!()
+        ))
+        .bind(room_id)
+        .bind(user)
+        .bind(user)
+        .bind(room_id)
+        .fetch_all(&self.conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|(key, kind, value, text)| Ok((key, variable_value(&kind, value, text)?)))
+            .collect()
+    }
+
+    async fn get_variable_value(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+    ) -> Result<VariableValue, DataError> {
+        let row: Option<(String, i32, Option<String>)> = sqlx::query_as(concat!(
+            "SELECT kind, value, text_value FROM user_variables
+             WHERE user_id = ? AND room_id = ? AND key = ? AND character_id = ",
+            active_character!()
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(user)
+        .bind(room_id)
+        .fetch_optional(&self.conn)
+        .await?;
+
+        match row {
+            Some((kind, value, text)) => variable_value(&kind, value, text),
+            None => Err(DataError::KeyDoesNotExist(variable_name.to_string())),
+        }
+    }
+
+    async fn set_variable_value(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+        value: &VariableValue,
+    ) -> Result<(), DataError> {
+        let (integer, text) = match value {
+            VariableValue::Integer(integer) => (*integer, None),
+            VariableValue::Text(text) | VariableValue::Dice(text) => (0, Some(text)),
+        };
+
+        // Bounds only make sense for integers, so they are removed
+        // when the type changes.
+        sqlx::query(concat!(
+            "INSERT INTO user_variables
+                    (user_id, room_id, key, value, kind, text_value, character_id)
+                    values (?, ?, ?, ?, ?, ?, ",
+            active_character!(),
+            ") ON CONFLICT(user_id, room_id, character_id, key)
+               DO UPDATE SET value = excluded.value,
+                             text_value = excluded.text_value,
+                             min_value = CASE WHEN kind = excluded.kind THEN min_value END,
+                             max_value = CASE WHEN kind = excluded.kind THEN max_value END,
+                             kind = excluded.kind"
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(integer)
+        .bind(value.kind())
+        .bind(text)
+        .bind(user)
+        .bind(room_id)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
     async fn get_user_variables(
         &self,
         user: &str,
         room_id: &str,
     ) -> Result<HashMap<String, i32>, DataError> {
-        let rows = sqlx::query!(
-            r#"SELECT key, value as "value: i32" FROM user_variables
-               WHERE room_id = ? AND user_id = ?"#,
-            room_id,
-            user
-        )
+        let rows: Vec<(String, i32)> = sqlx::query_as(concat!(
+            "SELECT key, value FROM user_variables
+             WHERE room_id = ? AND user_id = ? AND kind = 'integer' AND character_id = ",
+            active_character!()
+        ))
+        .bind(room_id)
+        .bind(user)
+        .bind(user)
+        .bind(room_id)
         .fetch_all(&self.conn)
         .await?;
 
-        Ok(rows.into_iter().map(|row| (row.key, row.value)).collect())
+        Ok(rows.into_iter().collect())
     }
 
     async fn get_variable_count(&self, user: &str, room_id: &str) -> Result<i32, DataError> {
-        let row = sqlx::query!(
-            r#"SELECT count(*) as "count: i32" FROM user_variables
-               WHERE room_id = ? and user_id = ?"#,
-            room_id,
-            user
-        )
+        let row: Option<(i32,)> = sqlx::query_as(concat!(
+            "SELECT count(*) FROM user_variables
+             WHERE room_id = ? AND user_id = ? AND character_id = ",
+            active_character!()
+        ))
+        .bind(room_id)
+        .bind(user)
+        .bind(user)
+        .bind(room_id)
         .fetch_optional(&self.conn)
         .await?;
 
-        Ok(row.map(|r| r.count).unwrap_or(0))
+        Ok(row.map(|(count,)| count).unwrap_or(0))
     }
 
     async fn get_user_variable(
@@ -41,17 +166,21 @@ impl Variables for Database {
         room_id: &str,
         variable_name: &str,
     ) -> Result<i32, DataError> {
-        let row = sqlx::query!(
-            r#"SELECT value as "value: i32" FROM user_variables
-               WHERE user_id = ? AND room_id = ? AND key = ?"#,
-            user,
-            room_id,
-            variable_name
-        )
+        let row: Option<(i32,)> = sqlx::query_as(concat!(
+            "SELECT value FROM user_variables
+             WHERE user_id = ? AND room_id = ? AND key = ? AND kind = 'integer'
+             AND character_id = ",
+            active_character!()
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(user)
+        .bind(room_id)
         .fetch_optional(&self.conn)
         .await?;
 
-        row.map(|r| r.value)
+        row.map(|(value,)| value)
             .ok_or_else(|| DataError::KeyDoesNotExist(variable_name.to_string()))
     }
 
@@ -62,39 +191,176 @@ impl Variables for Database {
         variable_name: &str,
         value: i32,
     ) -> Result<(), DataError> {
-        sqlx::query(
+        sqlx::query(concat!(
             "INSERT INTO user_variables
-                    (user_id, room_id, key, value)
-                    values (?, ?, ?, ?)",
-        )
+                    (user_id, room_id, key, value, character_id)
+                    values (?, ?, ?, ?, ",
+            active_character!(),
+            ") ON CONFLICT(user_id, room_id, character_id, key)
+               DO UPDATE SET value = excluded.value, kind = 'integer', text_value = NULL"
+        ))
         .bind(user)
         .bind(room_id)
-        .bind(variable_name)
+        .bind(normalize_variable_name(variable_name))
         .bind(value)
+        .bind(user)
+        .bind(room_id)
         .execute(&self.conn)
         .await?;
 
         Ok(())
     }
 
+    async fn update_user_variable(
+        &self,
+        user: &str,
+        room_id: &str,
+        variable_name: &str,
+        change: ValueChange,
+        min: BoundChange,
+        max: BoundChange,
+    ) -> Result<UpdatedVariable, DataError> {
+        let mut tx = self.conn.begin().await?;
+
+        let existing: Option<(i32, Option<i32>, Option<i32>)> = sqlx::query_as(concat!(
+            "SELECT value, min_value, max_value FROM user_variables
+             WHERE user_id = ? AND room_id = ? AND key = ? AND kind = 'integer'
+             AND character_id = ",
+            active_character!()
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(user)
+        .bind(room_id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let old_value = existing.map(|(value, _, _)| value);
+        let min = min.apply(existing.and_then(|(_, min, _)| min));
+        let max = max.apply(existing.and_then(|(_, _, max)| max));
+
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(DataError::InvalidBounds(min, max));
+            }
+        }
+
+        let value = change
+            .apply(old_value)
+            .map(|value| clamp(value, min, max))
+            .ok_or_else(|| DataError::KeyDoesNotExist(variable_name.to_string()))?;
+
+        sqlx::query(concat!(
+            "INSERT INTO user_variables
+                    (user_id, room_id, key, value, min_value, max_value, character_id)
+                    values (?, ?, ?, ?, ?, ?, ",
+            active_character!(),
+            ") ON CONFLICT(user_id, room_id, character_id, key)
+               DO UPDATE SET value = excluded.value,
+                             min_value = excluded.min_value,
+                             max_value = excluded.max_value,
+                             kind = 'integer',
+                             text_value = NULL"
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
+        .bind(value)
+        .bind(min)
+        .bind(max)
+        .bind(user)
+        .bind(room_id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UpdatedVariable {
+            old_value,
+            value,
+            min,
+            max,
+        })
+    }
+
+    async fn set_user_variables(
+        &self,
+        user: &str,
+        room_id: &str,
+        variables: &[(String, i32)],
+    ) -> Result<Vec<String>, DataError> {
+        let mut tx = self.conn.begin().await?;
+        let mut skipped = vec![];
+
+        // Existing bounds are kept, so imported values may not respect
+        // them until the variable is next updated. Nothing changes for
+        // a variable of another type, which is reported as skipped.
+        for (variable_name, value) in variables {
+            let result = sqlx::query(concat!(
+                "INSERT INTO user_variables
+                        (user_id, room_id, key, value, character_id)
+                        values (?, ?, ?, ?, ",
+                active_character!(),
+                ") ON CONFLICT(user_id, room_id, character_id, key)
+                   DO UPDATE SET value = excluded.value WHERE kind = 'integer'"
+            ))
+            .bind(user)
+            .bind(room_id)
+            .bind(normalize_variable_name(variable_name))
+            .bind(value)
+            .bind(user)
+            .bind(room_id)
+            .execute(&mut tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                skipped.push(variable_name.clone());
+            }
+        }
+
+        tx.commit().await?;
+        Ok(skipped)
+    }
+
     async fn delete_user_variable(
         &self,
         user: &str,
         room_id: &str,
         variable_name: &str,
     ) -> Result<(), DataError> {
-        sqlx::query(
+        sqlx::query(concat!(
             "DELETE FROM user_variables
-             WHERE user_id = ? AND room_id = ? AND key = ?",
-        )
+             WHERE user_id = ? AND room_id = ? AND key = ? AND character_id = ",
+            active_character!()
+        ))
+        .bind(user)
+        .bind(room_id)
+        .bind(normalize_variable_name(variable_name))
         .bind(user)
         .bind(room_id)
-        .bind(variable_name)
         .execute(&self.conn)
         .await?;
 
         Ok(())
     }
+
+    async fn delete_all_user_variables(
+        &self,
+        user: &str,
+        room_id: Option<&str>,
+    ) -> Result<u64, DataError> {
+        let result = sqlx::query(
+            "DELETE FROM user_variables WHERE user_id = ? AND (? IS NULL OR room_id = ?)",
+        )
+        .bind(user)
+        .bind(room_id)
+        .bind(room_id)
+        .execute(&self.conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
 
 #[cfg(test)]
@@ -254,4 +520,284 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn delete_all_user_variables_test() {
+        with_db(|db| async move {
+            for room_id in &["myroom", "otherroom"] {
+                db.set_user_variable("myuser", room_id, "var1", 1)
+                    .await
+                    .expect("Could not set variable");
+            }
+
+            db.set_user_variable("otheruser", "myroom", "var1", 1)
+                .await
+                .expect("Could not set variable");
+
+            let deleted = db
+                .delete_all_user_variables("myuser", Some("myroom"))
+                .await
+                .expect("Could not delete variables");
+
+            assert_eq!(deleted, 1);
+
+            let deleted = db
+                .delete_all_user_variables("myuser", None)
+                .await
+                .expect("Could not delete variables");
+
+            assert_eq!(deleted, 1);
+
+            let other = db.get_user_variable("otheruser", "myroom", "var1").await;
+            assert!(other.is_ok());
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_many_variables_replaces_values_test() {
+        with_db(|db| async move {
+            db.set_user_variable("myuser", "myroom", "var1", 1)
+                .await
+                .expect("Could not set variable");
+
+            let variables = vec![("var1".to_string(), 5), ("var2".to_string(), 2)];
+            let skipped = db
+                .set_user_variables("myuser", "myroom", &variables)
+                .await
+                .expect("Could not set variables");
+
+            assert!(skipped.is_empty());
+
+            let count = db
+                .get_variable_count("myuser", "myroom")
+                .await
+                .expect("Could not get count");
+
+            assert_eq!(count, 2);
+
+            let var1 = db.get_user_variable("myuser", "myroom", "var1").await;
+            assert_eq!(var1.unwrap(), 5);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_many_variables_skips_other_types_test() {
+        with_db(|db| async move {
+            let weapon = VariableValue::Dice("1d8".to_string());
+            db.set_variable_value("myuser", "myroom", "weapon", &weapon)
+                .await
+                .expect("Could not set variable");
+
+            let variables = vec![("Weapon".to_string(), 5), ("var2".to_string(), 2)];
+            let skipped = db
+                .set_user_variables("myuser", "myroom", &variables)
+                .await
+                .expect("Could not set variables");
+
+            assert_eq!(skipped, vec!["Weapon".to_string()]);
+
+            let value = db.get_variable_value("myuser", "myroom", "weapon").await;
+            assert_eq!(value.unwrap(), weapon);
+
+            let var2 = db.get_user_variable("myuser", "myroom", "var2").await;
+            assert_eq!(var2.unwrap(), 2);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn setting_variable_replaces_value_test() {
+        with_db(|db| async move {
+            db.set_user_variable("myuser", "myroom", "myvariable", 1)
+                .await
+                .expect("Could not set variable");
+
+            db.set_user_variable("myuser", "myroom", "myvariable", 2)
+                .await
+                .expect("Could not set variable");
+
+            let count = db.get_variable_count("myuser", "myroom").await.unwrap();
+            let value = db.get_user_variable("myuser", "myroom", "myvariable").await;
+
+            assert_eq!(count, 1);
+            assert_eq!(value.unwrap(), 2);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn update_variable_reports_old_value_test() {
+        with_db(|db| async move {
+            let set = ValueChange::Set(10);
+            let keep = BoundChange::Keep;
+            let updated = db
+                .update_user_variable("myuser", "myroom", "hp", set, keep, keep)
+                .await
+                .expect("Could not update variable");
+
+            assert_eq!(updated.old_value, None);
+            assert_eq!(updated.value, 10);
+
+            let add = ValueChange::Add(-3);
+            let updated = db
+                .update_user_variable("myuser", "myroom", "hp", add, keep, keep)
+                .await
+                .expect("Could not update variable");
+
+            assert_eq!(updated.old_value, Some(10));
+            assert_eq!(updated.value, 7);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn update_variable_respects_bounds_test() {
+        with_db(|db| async move {
+            let keep = BoundChange::Keep;
+            db.update_user_variable(
+                "myuser",
+                "myroom",
+                "hp",
+                ValueChange::Set(5),
+                BoundChange::Set(0),
+                BoundChange::Set(8),
+            )
+            .await
+            .expect("Could not update variable");
+
+            // Bounds are stored with the variable.
+            let updated = db
+                .update_user_variable("myuser", "myroom", "hp", ValueChange::Add(-9), keep, keep)
+                .await
+                .expect("Could not update variable");
+
+            assert_eq!(updated.value, 0);
+            assert_eq!((updated.min, updated.max), (Some(0), Some(8)));
+
+            let updated = db
+                .update_user_variable("myuser", "myroom", "hp", ValueChange::Set(20), keep, keep)
+                .await
+                .expect("Could not update variable");
+
+            assert_eq!(updated.value, 8);
+
+            let cleared = BoundChange::Clear;
+            let updated = db
+                .update_user_variable(
+                    "myuser",
+                    "myroom",
+                    "hp",
+                    ValueChange::Set(20),
+                    keep,
+                    cleared,
+                )
+                .await
+                .expect("Could not update variable");
+
+            assert_eq!(updated.value, 20);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn update_variable_errors_test() {
+        with_db(|db| async move {
+            let keep = BoundChange::Keep;
+            let missing = db
+                .update_user_variable("myuser", "myroom", "hp", ValueChange::Add(1), keep, keep)
+                .await;
+
+            assert!(matches!(missing, Err(DataError::KeyDoesNotExist(_))));
+
+            let inverted = db
+                .update_user_variable(
+                    "myuser",
+                    "myroom",
+                    "hp",
+                    ValueChange::Set(1),
+                    BoundChange::Set(5),
+                    BoundChange::Set(2),
+                )
+                .await;
+
+            assert!(matches!(inverted, Err(DataError::InvalidBounds(5, 2))));
+
+            // Nothing is stored when the update fails.
+            let count = db.get_variable_count("myuser", "myroom").await.unwrap();
+            assert_eq!(count, 0);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn variable_names_are_case_insensitive_test() {
+        with_db(|db| async move {
+            db.set_user_variable("myuser", "myroom", "Stärke", 3)
+                .await
+                .expect("Could not set variable");
+
+            let value = db.get_user_variable("myuser", "myroom", "STÄRKE").await;
+            assert_eq!(value.unwrap(), 3);
+
+            let variables = db.get_user_variables("myuser", "myroom").await.unwrap();
+            assert_eq!(variables.get("stärke"), Some(&3));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_and_get_typed_variable_test() {
+        with_db(|db| async move {
+            let sword = VariableValue::Dice("1d8+str".to_string());
+            let title = VariableValue::Text("Lord of Nowhere".to_string());
+
+            db.set_variable_value("myuser", "myroom", "sword", &sword)
+                .await
+                .expect("Could not set variable");
+
+            db.set_variable_value("myuser", "myroom", "title", &title)
+                .await
+                .expect("Could not set variable");
+
+            db.set_user_variable("myuser", "myroom", "str", 3)
+                .await
+                .expect("Could not set variable");
+
+            let value = db.get_variable_value("myuser", "myroom", "SWORD").await;
+            assert_eq!(value.unwrap(), sword);
+
+            let values = db.get_variable_values("myuser", "myroom").await.unwrap();
+            assert_eq!(values.len(), 3);
+            assert_eq!(values.get("title"), Some(&title));
+            assert_eq!(values.get("str"), Some(&VariableValue::Integer(3)));
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn integer_functions_ignore_typed_variables_test() {
+        with_db(|db| async move {
+            let sword = VariableValue::Dice("1d8".to_string());
+            db.set_variable_value("myuser", "myroom", "sword", &sword)
+                .await
+                .expect("Could not set variable");
+
+            let value = db.get_user_variable("myuser", "myroom", "sword").await;
+            let variables = db.get_user_variables("myuser", "myroom").await.unwrap();
+
+            assert!(matches!(value, Err(DataError::KeyDoesNotExist(_))));
+            assert!(variables.is_empty());
+
+            // Storing a number again makes it an integer variable.
+            db.set_user_variable("myuser", "myroom", "sword", 4)
+                .await
+                .expect("Could not set variable");
+
+            let value = db.get_variable_value("myuser", "myroom", "sword").await;
+            assert_eq!(value.unwrap(), VariableValue::Integer(4));
+        })
+        .await;
+    }
 }
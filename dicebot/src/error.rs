@@ -64,6 +64,12 @@ pub enum BotError {
     #[error("variable parsing error: {0}")]
     VariableParsingError(#[from] crate::parser::variables::VariableParsingError),
 
+    #[error("character sheet error: {0}")]
+    SheetError(#[from] crate::sheets::SheetError),
+
+    #[error("roll template error: {0}")]
+    TemplateError(#[from] crate::templates::TemplateError),
+
     #[error("legacy parsing error")]
     NomParserError(nom::error::ErrorKind),
 
@@ -76,6 +82,12 @@ pub enum BotError {
     #[error("too many commands or message was too large")]
     MessageTooLarge,
 
+    #[error("too many commands, slow down and try again later")]
+    RateLimited,
+
+    #[error("fair rolls are not turned on")]
+    FairRollsOff,
+
     #[error("could not convert to proper integer type")]
     TryFromIntError(#[from] std::num::TryFromIntError),
 
@@ -105,6 +117,55 @@ pub enum BotError {
 
     #[error("invalid metadata value: {0}")]
     TonicInvalidMetadata(#[from] InvalidMetadataValue),
+
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+impl BotError {
+    /// Name of the kind of error, like "DataError", for output that
+    /// programs read.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BotError::ConfigurationError(_) => "ConfigurationError",
+            BotError::SyncTokenRequired => "SyncTokenRequired",
+            BotError::NoDeviceIdFound => "NoDeviceIdFound",
+            BotError::CommandError(_) => "CommandError",
+            BotError::DataError(_) => "DataError",
+            BotError::ShouldNotProcessError => "ShouldNotProcessError",
+            BotError::NoCacheDirectoryError => "NoCacheDirectoryError",
+            BotError::UrlParseError(_) => "UrlParseError",
+            BotError::MatrixStateStoreError(_) => "MatrixStateStoreError",
+            BotError::MatrixError(_) => "MatrixError",
+            BotError::MatrixBaseError(_) => "MatrixBaseError",
+            BotError::FutureCanceledError => "FutureCanceledError",
+            BotError::TomlParsingError(_) => "TomlParsingError",
+            BotError::IoError(_) => "IoError",
+            BotError::DiceParsingError(_) => "DiceParsingError",
+            BotError::CommandParsingError(_) => "CommandParsingError",
+            BotError::DiceRollingError(_) => "DiceRollingError",
+            BotError::VariableParsingError(_) => "VariableParsingError",
+            BotError::SheetError(_) => "SheetError",
+            BotError::TemplateError(_) => "TemplateError",
+            BotError::NomParserError(_) => "NomParserError",
+            BotError::NomParserIncomplete => "NomParserIncomplete",
+            BotError::VariablesNotSupported => "VariablesNotSupported",
+            BotError::MessageTooLarge => "MessageTooLarge",
+            BotError::RateLimited => "RateLimited",
+            BotError::FairRollsOff => "FairRollsOff",
+            BotError::TryFromIntError(_) => "TryFromIntError",
+            BotError::IdentifierError(_) => "IdentifierError",
+            BotError::PasswordCreationError(_) => "PasswordCreationError",
+            BotError::AuthenticationError => "AuthenticationError",
+            BotError::AccountDoesNotExist => "AccountDoesNotExist",
+            BotError::AccountAlreadyExists => "AccountAlreadyExists",
+            BotError::RoomDoesNotExist => "RoomDoesNotExist",
+            BotError::TonicTransportError(_) => "TonicTransportError",
+            BotError::AddressParseError(_) => "AddressParseError",
+            BotError::TonicInvalidMetadata(_) => "TonicInvalidMetadata",
+            BotError::JsonError(_) => "JsonError",
+        }
+    }
 }
 
 #[derive(Error, Debug)]
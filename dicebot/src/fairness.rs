@@ -0,0 +1,72 @@
+use crate::rng::DiceRng;
+use hmac::{Hmac, Mac, NewMac};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// How many random bytes a secret is made of. Secrets are written as
+/// hex, twice as many characters.
+const SECRET_BYTES: usize = 32;
+
+/// Make a new secret for a fair mode session.
+pub fn new_secret() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; SECRET_BYTES]>())
+}
+
+/// The commitment to a secret: the SHA-256 hash of the secret, in
+/// hex. It is published while the secret is kept, and anyone can
+/// check it against the secret once it is revealed, for example with
+/// `echo -n <secret> | sha256sum`.
+pub fn commitment(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Whether a revealed secret is the one a commitment was made to.
+pub fn matches_commitment(secret: &str, commitment: &str) -> bool {
+    self::commitment(secret).eq_ignore_ascii_case(commitment.trim())
+}
+
+/// The generator that the rolls of an event are made with in fair
+/// mode, seeded with the HMAC-SHA256 of the event ID, keyed with the
+/// secret. Nobody can predict the rolls without the secret, and the
+/// bot cannot pick rolls once the secret is committed to.
+pub fn event_rng(secret: &str, event_id: &str) -> DiceRng {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can be keyed with any secret");
+
+    mac.update(event_id.as_bytes());
+    DiceRng::from_seed(mac.finalize().into_bytes().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_test() {
+        let secret = new_secret();
+        assert_eq!(secret.len(), SECRET_BYTES * 2);
+
+        let commitment = commitment(&secret);
+        assert!(matches_commitment(&secret, &commitment));
+        assert!(matches_commitment(&secret, &commitment.to_uppercase()));
+        assert!(!matches_commitment(&new_secret(), &commitment));
+
+        assert_eq!(
+            self::commitment("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn event_rng_repeats_rolls_of_event_test() {
+        let roll = |rng: DiceRng| -> Vec<u32> {
+            rng.with(|rng| (0..10).map(|_| rng.gen_range(1..=1000)).collect())
+        };
+
+        let secret = new_secret();
+        let rolls = roll(event_rng(&secret, "$event:example.com"));
+        assert_eq!(rolls, roll(event_rng(&secret, "$event:example.com")));
+        assert_ne!(rolls, roll(event_rng(&secret, "$other:example.com")));
+        assert_ne!(rolls, roll(event_rng(&new_secret(), "$event:example.com")));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::basic::roll::{ElementExpressionRoll, Rolled};
+use crate::cofd::dice::{DicePoolRoll, RolledDicePool};
+use crate::cthulhu::dice::{
+    DiceRollModifier, ExecutedAdvancementRoll, ExecutedDiceRoll, RolledAdvancement, RolledDice,
+};
+use crate::models::Verbosity;
+use itertools::Itertools;
+
+/// The successes of a dice pool roll, or the failure.
+fn pool_outcome(roll: &DicePoolRoll) -> String {
+    match roll.successes() {
+        0 => roll.outcome(),
+        successes if roll.is_exceptional() => format!("{} successes (exceptional!)", successes),
+        successes => format!("{} successes", successes),
+    }
+}
+
+/// The dice rolled for a dice pool, cut off after the given amount of
+/// dice so that huge pools do not spam the room.
+fn pool_dice(roll: &DicePoolRoll, max_displayed: usize) -> String {
+    let rolls = roll.rolls();
+    if rolls.len() > max_displayed {
+        let shown = rolls.iter().take(max_displayed).join(", ");
+        format!("{}, and {} more", shown, rolls.len() - max_displayed)
+    } else {
+        rolls.iter().join(", ")
+    }
+}
+
+/// The successes or failure of a dice pool roll, with the dice rolled.
+/// At most the given amount of dice are shown.
+pub fn pool_result(roll: &DicePoolRoll, max_displayed: usize) -> String {
+    format!(
+        "{} ({})",
+        pool_outcome(roll),
+        pool_dice(roll, max_displayed)
+    )
+}
+
+/// The size and modifiers of a rolled dice pool, like "5 dice
+/// (10-again, exceptional on 5 successes)".
+pub fn pool_description(pool: &RolledDicePool) -> String {
+    let dice_plural = if pool.num_dice == 1 { "die" } else { "dice" };
+    format!(
+        "{} {} ({}, exceptional on {} successes)",
+        pool.num_dice, dice_plural, pool.modifiers.quality, pool.modifiers.exceptional_on
+    )
+}
+
+/// The target of a Call of Cthulhu roll and its bonus or penalty dice.
+pub fn cthulhu_target(roll: &ExecutedDiceRoll) -> String {
+    format!("target: {}, with {}", roll.target, roll.modifier)
+}
+
+fn cthulhu_result(roll: &RolledDice) -> String {
+    format!(
+        "{} against {}: {}",
+        roll.num_rolled(),
+        roll.target(),
+        roll.result()
+    )
+}
+
+/// The target of a Call of Cthulhu skill advancement roll.
+pub fn advancement_target(roll: &ExecutedAdvancementRoll) -> String {
+    format!("target: {}", roll.target)
+}
+
+fn advancement_result(roll: &RolledAdvancement) -> String {
+    let outcome = match roll.successful() {
+        true => format!(
+            "success! new skill is {} (advanced by {}).",
+            roll.new_skill_amount(),
+            roll.advancement()
+        ),
+        false => format!("failure! skill remains at {}", roll.existing_skill()),
+    };
+
+    format!(
+        "rolled {} against {}: {}",
+        roll.num_rolled(),
+        roll.existing_skill(),
+        outcome
+    )
+}
+
+/// Every die rolled for a dice pool. The dice a die exploded into, or
+/// the re-roll of a rote action, follow the die with an arrow.
+fn pool_chains(pool: &RolledDicePool) -> String {
+    pool.chains
+        .iter()
+        .map(|chain| chain.iter().join(" → "))
+        .join(", ")
+}
+
+/// Format a regular dice roll. The description is shown in place of
+/// the expression. Normal replies already show every die rolled, so
+/// detailed replies are the same.
+pub fn basic_roll(description: &str, roll: &ElementExpressionRoll, verbosity: Verbosity) -> String {
+    match verbosity {
+        Verbosity::Compact => format!("<strong>{}:</strong> {}", description, roll.rolled_value()),
+        Verbosity::Normal | Verbosity::Detailed => format!(
+            "<strong>Dice:</strong> {}</p><p><strong>Result</strong>: {}",
+            description, roll
+        ),
+    }
+}
+
+/// Format a dice pool roll. Normal replies show at most the given
+/// amount of dice, and detailed replies show every die with the dice
+/// it exploded into.
+pub fn pool_roll(pool: &RolledDicePool, verbosity: Verbosity, max_displayed: usize) -> String {
+    match verbosity {
+        Verbosity::Compact => {
+            let dice_plural = if pool.num_dice == 1 { "die" } else { "dice" };
+            format!(
+                "<strong>Pool of {} {}:</strong> {}",
+                pool.num_dice,
+                dice_plural,
+                pool_outcome(&pool.roll)
+            )
+        }
+        Verbosity::Normal => format!(
+            "<strong>Pool:</strong> {}</p><p><strong>Result</strong>: {}",
+            pool_description(pool),
+            pool_result(&pool.roll, max_displayed)
+        ),
+        Verbosity::Detailed => format!(
+            "<strong>Pool:</strong> {}</p><p><strong>Result</strong>: {}</p>\
+             <p><strong>Dice:</strong> {}",
+            pool_description(pool),
+            pool_outcome(&pool.roll),
+            pool_chains(pool)
+        ),
+    }
+}
+
+/// Format a Call of Cthulhu roll. Detailed replies show every
+/// percentile result rolled for bonus or penalty dice.
+pub fn cthulhu_roll(roll: &ExecutedDiceRoll, verbosity: Verbosity) -> String {
+    let normal = format!(
+        "<strong>Roll:</strong> {}</p><p><strong>Result</strong>: {}",
+        cthulhu_target(roll),
+        cthulhu_result(&roll.roll)
+    );
+
+    match verbosity {
+        Verbosity::Compact => format!("<strong>Roll:</strong> {}", cthulhu_result(&roll.roll)),
+        Verbosity::Normal => normal,
+        Verbosity::Detailed => {
+            use DiceRollModifier::*;
+            let kept = match roll.modifier {
+                Normal => "",
+                OneBonus | TwoBonus => " (lowest kept)",
+                OnePenalty | TwoPenalty => " (highest kept)",
+            };
+
+            format!(
+                "{}</p><p><strong>Dice:</strong> {}{}",
+                normal,
+                roll.roll.rolls().iter().join(", "),
+                kept
+            )
+        }
+    }
+}
+
+/// Format a Call of Cthulhu skill advancement roll. Normal replies
+/// already show both the d100 roll and how much the skill advanced, so
+/// detailed replies are the same.
+pub fn advancement_roll(roll: &ExecutedAdvancementRoll, verbosity: Verbosity) -> String {
+    let result = advancement_result(&roll.roll);
+
+    match verbosity {
+        Verbosity::Compact => format!("<strong>Advancement:</strong> {}", result),
+        Verbosity::Normal | Verbosity::Detailed => format!(
+            "<strong>Roll:</strong> {}</p><p><strong>Result</strong>: {}",
+            advancement_target(roll),
+            result
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cofd::dice::{DicePool, DicePoolQuality, DEFAULT_MAX_DISPLAYED_ROLLS};
+
+    fn rolled_pool() -> RolledDicePool {
+        let pool = DicePool::easy_pool(3, DicePoolQuality::TenAgain);
+        RolledDicePool::from_chains(&pool, 3, vec![vec![10, 10, 4], vec![8], vec![2]])
+    }
+
+    fn pool_of(rolls: Vec<i32>) -> RolledDicePool {
+        let num_dice = rolls.len() as i32;
+        let pool = DicePool::easy_pool(num_dice, DicePoolQuality::TenAgain);
+        let chains = rolls.into_iter().map(|roll| vec![roll]).collect();
+        RolledDicePool::from_chains(&pool, num_dice, chains)
+    }
+
+    #[test]
+    fn pool_description_test() {
+        assert!(pool_description(&pool_of(vec![1])).starts_with("1 die"));
+        assert!(pool_description(&pool_of(vec![1, 2])).starts_with("2 dice"));
+        assert!(pool_description(&pool_of(vec![])).starts_with("0 dice"));
+    }
+
+    #[test]
+    fn pool_dice_cuts_off_lots_of_dice_test() {
+        let rolls = |amount: i32| pool_of((1..=amount).collect()).roll;
+
+        assert_eq!(
+            pool_dice(&rolls(19), DEFAULT_MAX_DISPLAYED_ROLLS),
+            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, and 4 more"
+        );
+        assert_eq!(
+            pool_dice(&rolls(15), DEFAULT_MAX_DISPLAYED_ROLLS),
+            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15"
+        );
+    }
+
+    #[test]
+    fn pool_result_shows_given_amount_of_dice_test() {
+        let roll = pool_of(vec![8, 2, 3, 4]).roll;
+        assert_eq!(pool_result(&roll, 2), "1 successes (8, 2, and 2 more)");
+    }
+
+    #[test]
+    fn compact_pool_roll_test() {
+        assert_eq!(
+            pool_roll(&rolled_pool(), Verbosity::Compact, 15),
+            "<strong>Pool of 3 dice:</strong> 3 successes"
+        );
+    }
+
+    #[test]
+    fn normal_pool_roll_cuts_off_dice_test() {
+        let html = pool_roll(&rolled_pool(), Verbosity::Normal, 2);
+        assert!(html.ends_with("3 successes (10, 10, and 3 more)"));
+    }
+
+    #[test]
+    fn detailed_pool_roll_shows_explosions_test() {
+        let html = pool_roll(&rolled_pool(), Verbosity::Detailed, 2);
+        assert!(html.ends_with("<strong>Dice:</strong> 10 → 10 → 4, 8, 2"));
+    }
+}
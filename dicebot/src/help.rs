@@ -6,6 +6,17 @@ pub fn parse_help_topic(input: &str) -> Option<HelpTopic> {
         "dicepool" => Some(HelpTopic::DicePool),
         "dice" => Some(HelpTopic::RollingDice),
         "cthulhu" => Some(HelpTopic::Cthulhu),
+        "history" => Some(HelpTopic::History),
+        "prob" => Some(HelpTopic::Probability),
+        "variables" => Some(HelpTopic::Variables),
+        "roomvars" => Some(HelpTopic::RoomVariables),
+        "characters" => Some(HelpTopic::Characters),
+        "sheets" => Some(HelpTopic::Sheets),
+        "macros" => Some(HelpTopic::Macros),
+        "admin" => Some(HelpTopic::Admin),
+        "config" => Some(HelpTopic::RoomSettings),
+        "templates" => Some(HelpTopic::Templates),
+        "fairness" => Some(HelpTopic::Fairness),
         "" => Some(HelpTopic::General),
         _ => None,
     }
@@ -16,6 +27,17 @@ pub enum HelpTopic {
     DicePool,
     Cthulhu,
     RollingDice,
+    History,
+    Probability,
+    Variables,
+    RoomVariables,
+    Characters,
+    Sheets,
+    Macros,
+    Admin,
+    RoomSettings,
+    Templates,
+    Fairness,
     General,
 }
 
@@ -46,6 +68,10 @@ Examples:
  !roll 1d4+5
  !roll 2d6+8
  !roll 2d8 + 4d6 - 3
+
+Inline rolls: if a room moderator turns them on with !inline on,
+expressions like [[1d20+5]] in ordinary messages are rolled too.
+Use !inline off to turn them off, and !inline to see the setting.
 "};
 
 const DICEPOOL_HELP: &'static str = indoc! {"
@@ -101,6 +127,266 @@ Note: If !cthadv is given a variable, and the roll is successful, it will
 update the variable with the new skill.
 "};
 
+const HISTORY_HELP: &'static str = indoc! {"
+Roll history
+
+Command: !history
+
+Every roll made with !roll, !pool, !chance, !cthroll and !cthadv
+is recorded.
+
+Syntax:
+ !history [amount] (your last rolls in this room, default 10)
+ !history room [amount] (last rolls of everyone in this room)
+ !history @user:server [amount] (last rolls of another user here)
+ !history export csv|json [hours] (rolls in this room from the last
+   few hours, default 6, at most a week; the last 100 rolls at most)
+ !threads on|off (moderators: keep the history of each thread apart)
+
+Examples:
+  !history
+  !history room 20
+  !history @someone:example.com 5
+  !history export csv 3
+"};
+
+const PROBABILITY_HELP: &'static str = indoc! {"
+Roll probabilities
+
+Command: !prob
+
+Calculates the odds of a roll without rolling it: the chance of
+success, the expected result, and a histogram of the outcomes. Small
+rolls are calculated exactly; very large ones are estimated by
+simulating many rolls.
+
+Syntax:
+ !prob dice-expression [>= target] (regular dice)
+ !prob pool dice-pool (Chronicles of Darkness dice pool)
+ !prob chance (Chronicles of Darkness chance die)
+ !prob cth target (Call of Cthulhu roll)
+
+Examples:
+  !prob 2d6+3
+  !prob 1d20+5 >= 15
+  !prob pool r:5
+  !prob cth bb:50
+"};
+
+const VARIABLES_HELP: &'static str = indoc! {"
+Variables
+
+Variables hold numbers for you in this room. A variable can be set to
+a number, a dice roll, or other variables, or changed relative to its
+current value. Optional min and max bounds are stored with the
+variable and limit every later change. Use none to remove a bound.
+
+Names can use letters, digits and underscores, but cannot start with
+a digit. Upper and lower case are the same.
+
+A variable can also hold text, or a dice expression that is rolled
+with !roll followed by its name. Only numbers work in arithmetic.
+
+Commands: !set, !inc, !dec, !get, !variables, !del
+
+Syntax:
+ !set name value [min number] [max number]
+ !set name = expression
+ !set name += expression
+ !set name -= expression
+ !inc name [expression] (adds 1 by default)
+ !dec name [expression] (subtracts 1 by default)
+ !set name text some text
+ !set name dice expression
+
+Examples:
+  !set hp 12 min 0 max 12
+  !set hp -= 1d6
+  !set hp = maxhp
+  !inc xp 3
+  !set hp max none
+  !set sword dice 1d8+str
+  !r sword
+"};
+
+const ROOM_VARIABLES_HELP: &'static str = indoc! {"
+Room variables
+
+Room variables are shared by everyone in the room, like a Momentum
+pool or a clock. Anyone can read them, but only room moderators can
+change them.
+
+Commands:
+ !roomvars (list all room variables)
+ !roomget name (show one room variable)
+ !roomset name value (moderators only)
+ !roomdel name (moderators only)
+
+Use a # in front of the name to use a room variable in a roll.
+
+Examples:
+  !roomset doom 3
+  !pool 5 + #momentum
+  !cthroll #luck
+"};
+
+const CHARACTERS_HELP: &'static str = indoc! {"
+Characters
+
+Each character has its own variables. While a character is active,
+!set, !get, !variables, !del and every roll use the variables of
+that character. Characters are separate for every room.
+
+Command: !character, !char
+
+Syntax:
+ !char [list] (list your characters in this room)
+ !char new name (create a character and make it active)
+ !char switch name (make a character active)
+ !char off (go back to your own variables)
+ !char delete name (delete a character and its variables)
+
+Examples:
+  !char new Old Man Henderson
+  !char switch Alice
+"};
+
+const SHEETS_HELP: &'static str = indoc! {"
+Character Sheets
+
+Import all of your variables in this room at once from a JSON or
+TOML document, or export them in either format. Every value must be
+a whole number. If any variable is invalid, nothing is imported and
+every problem is listed.
+
+Put the sheet in a code block on the line after !import. If a room
+moderator turned on uploads with !config uploads on, uploading a .json
+or .toml file also imports it.
+
+Commands: !import, !export
+
+Syntax:
+ !import (followed by a code block)
+ !export [json|toml] (json is the default)
+
+Example:
+  !import
+  ```toml
+  strength = 3
+  wits = 2
+  ```
+"};
+
+const MACROS_HELP: &'static str = indoc! {"
+Macros
+
+A macro stores a command you use often, and runs it by name. In the
+command, $1 to $9 are replaced by the arguments given when running
+the macro, and $* by all of them. Room macros are shared by everyone
+in the room, but only room moderators can change them. Your own
+macro wins over a room macro with the same name.
+
+Commands: !macro, !m
+
+Syntax:
+ !macro (list your macros and the room's macros)
+ !macro set name command
+ !macro del name
+ !macro roomset name command (moderators only)
+ !macro roomdel name (moderators only)
+ !m name [arguments]
+
+Examples:
+  !macro set shoot !pool dexterity + firearms + $1
+  !m shoot 2
+"};
+
+const ADMIN_HELP: &'static str = indoc! {"
+Bot administration
+
+Command: !admin
+
+Only bot admins from the config can use these, in an encrypted
+direct message with the bot.
+
+Syntax:
+ !admin rooms (list the rooms the bot is in)
+ !admin leave <room id> (make the bot leave a room)
+ !admin users (list user accounts)
+ !admin resetvars <user> [room id] (delete the variables of a user)
+ !admin status (uptime, version and database size)
+ !admin broadcast <message> (send a notice to every room)
+"};
+
+const ROOM_SETTINGS_HELP: &'static str = indoc! {"
+Room settings
+
+Command: !config
+
+Anyone can show the settings of the room with !config. Room
+moderators can change them, or go back to the default with
+!config <setting> default.
+
+Settings:
+ system basic|cofd|coc (what !r rolls, !roll is always basic)
+ verbosity compact|normal|detailed (detail of roll replies)
+ reply notice|message (kind of message the bot replies with)
+ inline on|off (roll [[expressions]] in messages)
+ threads on|off (separate roll history per thread)
+ maxdice <1-100> (dice of a pool shown, default 15)
+ uploads on|off (import uploaded .json and .toml files as sheets)
+
+Examples:
+ !config system cofd
+ !config maxdice 30
+"};
+
+const TEMPLATES_HELP: &'static str = indoc! {"
+Roll templates
+
+Command: !template
+
+Anyone can show the roll templates of the room with !template. Room
+moderators can change how replies to the rolls of a game system
+(basic, cofd or coc) look with !template <system> <template>, or go
+back to the built-in replies with !template <system> default.
+
+Templates can use HTML, and these placeholders:
+ {user} (who rolled)
+ {system} (game system of the roll)
+ {expression} (what was rolled)
+ {dice} (every die rolled)
+ {total} (sum, successes or percentile result)
+ {outcome} (success or failure, if any)
+
+Examples:
+ !template basic {user} rolled <b>{total}</b> ({dice})
+ !template cofd {expression}: {outcome}
+ !template coc default
+"};
+
+const FAIRNESS_HELP: &'static str = indoc! {"
+Fair rolls
+
+Command: !fairness
+
+If the bot admins turned on fair rolls, every roll is seeded from a
+secret and the ID of the message (or reaction) that made the roll.
+Edits roll the dice of the message they edit. The SHA-256 hash of the
+secret is published before any roll is made with it, and the secret
+is revealed when the bot restarts, so anyone can check later that a
+roll was not picked by the bot. Fair rolls name their session.
+
+Syntax:
+ !fairness (show the hash of the current secret, and revealed secrets)
+ !fairness reveal (bot admins: reveal the secret and start a new one)
+
+Check a roll with the revealed secret and the event ID, and the
+prefix and game system of the room if it has its own:
+ dicebot-cmd verify --secret 5f1c... --commitment 9a2e... \\
+   --event '$abc123:example.org' [--prefix .] [--system cofd] '!r 1d20'
+"};
+
 const GENERAL_HELP: &'static str = indoc! {"
 General Help
 
@@ -108,6 +394,27 @@ Try these help commands:
   !help cofd
   !help dice
   !help cthulhu
+  !help history
+  !help prob
+  !help variables
+  !help roomvars
+  !help characters
+  !help sheets
+  !help macros
+  !help config
+  !help templates
+  !help fairness
+  !help admin
+
+Commands start with ! unless a room moderator changed the prefix of
+the room with !prefix (e.g. !prefix . or !prefix default). You can
+also address the bot by name, like DiceBot: r 1d20.
+
+Add --compact or --detailed right after a roll command for a shorter
+or longer reply, like !r --compact 1d20.
+
+React to a roll reply with 🔁 to roll again, with 🎲 to make the same
+roll yourself, or with ❌ to remove the reply to your own command.
 "};
 
 impl HelpTopic {
@@ -117,6 +424,17 @@ impl HelpTopic {
             HelpTopic::DicePool => DICEPOOL_HELP,
             HelpTopic::Cthulhu => CTHULHU_HELP,
             HelpTopic::RollingDice => DICE_HELP,
+            HelpTopic::History => HISTORY_HELP,
+            HelpTopic::Probability => PROBABILITY_HELP,
+            HelpTopic::Variables => VARIABLES_HELP,
+            HelpTopic::RoomVariables => ROOM_VARIABLES_HELP,
+            HelpTopic::Characters => CHARACTERS_HELP,
+            HelpTopic::Sheets => SHEETS_HELP,
+            HelpTopic::Macros => MACROS_HELP,
+            HelpTopic::Admin => ADMIN_HELP,
+            HelpTopic::RoomSettings => ROOM_SETTINGS_HELP,
+            HelpTopic::Templates => TEMPLATES_HELP,
+            HelpTopic::Fairness => FAIRNESS_HELP,
             HelpTopic::General => GENERAL_HELP,
         }
     }
@@ -7,10 +7,16 @@ pub mod context;
 pub mod cthulhu;
 pub mod db;
 pub mod error;
+pub mod fairness;
+pub mod formatting;
 mod help;
 pub mod logic;
 pub mod matrix;
 pub mod models;
 mod parser;
+pub mod probability;
+pub mod rng;
 pub mod rpc;
+pub mod sheets;
 pub mod state;
+pub mod templates;
@@ -1,13 +1,19 @@
+use crate::basic::dice::{Element as DiceElement, ElementExpression, SignedElement};
+use crate::basic::roll::{Roll, Rolled};
+use crate::cofd::dice::DEFAULT_MAX_DISPLAYED_ROLLS;
 use crate::error::{BotError, DiceRollingError};
-use crate::parser::dice::{Amount, Element};
+use crate::parser::dice::{Amount, Element, Operator};
+use crate::parser::variables::{normalize_variable_name, SignedTerm, Term};
 use crate::{context::Context, models::Account};
 use crate::{
-    db::{sqlite::Database, Users, Variables},
-    models::TransientUser,
+    db::{sqlite::Database, FairnessSessions, RoomSettings, RoomVariables, Users, Variables},
+    models::{FairnessSession, GameSystem, ReplyType, RoomSetting, TransientUser, Verbosity},
 };
+use crate::{fairness, rng::DiceRng};
 use argon2::{self, Config, Error as ArgonError};
 use futures::stream::{self, StreamExt, TryStreamExt};
 use rand::Rng;
+use std::collections::HashMap;
 use std::slice;
 
 /// Calculate the amount of dice to roll by consulting the database
@@ -25,10 +31,18 @@ pub async fn calculate_single_die_amount(
 /// it cannot find a variable defined, or if the database errors.
 pub async fn calculate_dice_amount(amounts: &[Amount], ctx: &Context<'_>) -> Result<i32, BotError> {
     let stream = stream::iter(amounts);
-    let variables = &ctx
-        .db
-        .get_user_variables(&ctx.username, ctx.active_room_id().as_str())
-        .await?;
+    let room_id = ctx.active_room_id().as_str();
+    let variables = &ctx.db.get_user_variables(ctx.username, room_id).await?;
+
+    // Room variables are only looked up if the expression uses them.
+    let uses_room_variables = amounts
+        .iter()
+        .any(|amount| matches!(amount.element, Element::RoomVariable(_)));
+
+    let room_variables = &match uses_room_variables {
+        true => ctx.db.get_room_variables(room_id).await?,
+        false => HashMap::new(),
+    };
 
     use DiceRollingError::VariableNotFound;
     let dice_amount: i32 = stream
@@ -36,9 +50,13 @@ pub async fn calculate_dice_amount(amounts: &[Amount], ctx: &Context<'_>) -> Res
             match &amount.element {
                 Element::Number(num_dice) => Ok(num_dice * amount.operator.mult()),
                 Element::Variable(variable) => variables
-                    .get(variable)
+                    .get(&normalize_variable_name(variable))
                     .ok_or_else(|| VariableNotFound(variable.clone()))
                     .map(|i| *i),
+                Element::RoomVariable(variable) => room_variables
+                    .get(&normalize_variable_name(variable))
+                    .ok_or_else(|| VariableNotFound(format!("#{}", variable)))
+                    .map(|i| *i),
             }
         })
         .try_fold(0, |total, num_dice| async move { Ok(total + num_dice) })
@@ -47,6 +65,48 @@ pub async fn calculate_dice_amount(amounts: &[Amount], ctx: &Context<'_>) -> Res
     Ok(dice_amount)
 }
 
+/// Turn an expression of dice, numbers and variables into a regular
+/// dice expression by looking up the variables.
+pub async fn resolve_expression(
+    terms: &[SignedTerm],
+    ctx: &Context<'_>,
+) -> Result<ElementExpression, BotError> {
+    let mut elements = vec![];
+
+    for signed in terms {
+        let element = match &signed.term {
+            Term::Dice(dice) => match signed.operator {
+                Operator::Plus => SignedElement::Positive(DiceElement::Dice(*dice)),
+                Operator::Minus => SignedElement::Negative(DiceElement::Dice(*dice)),
+            },
+            Term::Amount(element) => {
+                let amount = Amount {
+                    operator: signed.operator,
+                    element: element.clone(),
+                };
+
+                match calculate_single_die_amount(&amount, ctx).await? {
+                    value if value < 0 => {
+                        SignedElement::Negative(DiceElement::Bonus(value.unsigned_abs()))
+                    }
+                    value => SignedElement::Positive(DiceElement::Bonus(value as u32)),
+                }
+            }
+        };
+
+        elements.push(element);
+    }
+
+    Ok(ElementExpression(elements))
+}
+
+/// Evaluate a variable update expression: roll its dice and add up
+/// the dice, numbers and variables.
+pub async fn evaluate_terms(terms: &[SignedTerm], ctx: &Context<'_>) -> Result<i32, BotError> {
+    let expression = resolve_expression(terms, ctx).await?;
+    Ok(ctx.rng.with(|rng| expression.roll(rng)).rolled_value())
+}
+
 /// Hash a password using the argon2 algorithm with a 16 byte salt.
 pub(crate) fn hash_password(raw_password: &str) -> Result<String, ArgonError> {
     let salt = rand::thread_rng().gen::<[u8; 16]>();
@@ -54,7 +114,7 @@ pub(crate) fn hash_password(raw_password: &str) -> Result<String, ArgonError> {
     argon2::hash_encoded(raw_password.as_bytes(), &salt, &config)
 }
 
-pub(crate) async fn get_account(db: &Database, username: &str) -> Result<Account, BotError> {
+pub async fn get_account(db: &Database, username: &str) -> Result<Account, BotError> {
     Ok(db
         .get_user(username)
         .await?
@@ -66,6 +126,120 @@ pub(crate) async fn get_account(db: &Database, username: &str) -> Result<Account
         }))
 }
 
+/// Whether a setting that is turned on or off, like [[expression]]
+/// inline rolls, is on in a room. Such settings are off until a
+/// moderator turns them on.
+pub(crate) async fn room_setting_enabled(
+    db: &Database,
+    room_id: &str,
+    setting: RoomSetting,
+) -> Result<bool, BotError> {
+    let value = db.get_room_setting(room_id, setting).await?;
+    Ok(value.as_deref() == Some("on"))
+}
+
+/// The value of a room setting, or the default if the room never
+/// changed it. Values are checked when they are set, so a value that
+/// cannot be parsed also falls back to the default.
+async fn room_setting_or_default<T: Default>(
+    db: &Database,
+    room_id: &str,
+    setting: RoomSetting,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, BotError> {
+    let value = db.get_room_setting(room_id, setting).await?;
+    Ok(value.as_deref().and_then(parse).unwrap_or_default())
+}
+
+/// The game system that !r rolls dice for in a room.
+pub(crate) async fn room_game_system(db: &Database, room_id: &str) -> Result<GameSystem, BotError> {
+    room_setting_or_default(
+        db,
+        room_id,
+        RoomSetting::DefaultSystem,
+        GameSystem::from_key,
+    )
+    .await
+}
+
+/// How much detail replies to rolls have in a room.
+pub(crate) async fn room_verbosity(db: &Database, room_id: &str) -> Result<Verbosity, BotError> {
+    room_setting_or_default(db, room_id, RoomSetting::Verbosity, Verbosity::from_key).await
+}
+
+/// Whether the bot replies with notices or ordinary messages in a
+/// room.
+pub(crate) async fn room_reply_type(db: &Database, room_id: &str) -> Result<ReplyType, BotError> {
+    room_setting_or_default(db, room_id, RoomSetting::ReplyType, ReplyType::from_key).await
+}
+
+/// How many dice of a dice pool are shown in a room.
+pub(crate) async fn room_max_displayed_dice(
+    db: &Database,
+    room_id: &str,
+) -> Result<usize, BotError> {
+    let setting = RoomSetting::MaxDisplayedDice;
+    let max = room_setting_or_default(db, room_id, setting, |value| value.parse().ok()).await?;
+
+    Ok(match max {
+        0 => DEFAULT_MAX_DISPLAYED_ROLLS,
+        max => max,
+    })
+}
+
+/// The command prefix of a room, if the room has its own instead of
+/// the configured one.
+pub async fn room_command_prefix(db: &Database, room_id: &str) -> Result<Option<String>, BotError> {
+    Ok(db
+        .get_room_setting(room_id, RoomSetting::CommandPrefix)
+        .await?)
+}
+
+/// Change a setting of a room, or go back to the default if no value
+/// is given. Values are stored as given, so they must be valid.
+pub async fn set_room_setting(
+    db: &Database,
+    room_id: &str,
+    setting: RoomSetting,
+    value: Option<&str>,
+) -> Result<(), BotError> {
+    Ok(db.set_room_setting(room_id, setting, value).await?)
+}
+
+/// Start a new fair mode session with a new secret, revealing the
+/// secret of the previous session, if there was one.
+pub(crate) async fn start_fairness_session(db: &Database) -> Result<FairnessSession, BotError> {
+    let secret = fairness::new_secret();
+    let commitment = fairness::commitment(&secret);
+    Ok(db.start_fairness_session(&secret, &commitment).await?)
+}
+
+/// Turn fair mode on or off when the bot starts. On starts a new
+/// session, which is returned, so its commitment can be published.
+/// Either way, the secret of the previous session is revealed.
+pub async fn restart_fair_mode(
+    db: &Database,
+    enabled: bool,
+) -> Result<Option<FairnessSession>, BotError> {
+    match enabled {
+        true => Ok(Some(start_fairness_session(db).await?)),
+        false => {
+            db.reveal_fairness_session().await?;
+            Ok(None)
+        }
+    }
+}
+
+/// The generator that the dice of an event are rolled with: seeded
+/// from the event in fair mode, and random otherwise. A fair mode
+/// generator knows its session, so rolls can name it.
+pub async fn event_rng(db: &Database, event_id: &str) -> Result<DiceRng, BotError> {
+    let session = db.current_fairness_session().await?;
+    Ok(session
+        .map(|session| fairness::event_rng(&session.secret, event_id).in_fair_session(session.id))
+        .unwrap_or_default())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
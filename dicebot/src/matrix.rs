@@ -1,19 +1,25 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use futures::stream::{self, StreamExt, TryStreamExt};
 use log::error;
-use matrix_sdk::{events::room::message::NoticeMessageEventContent, room::Joined, ClientConfig};
 use matrix_sdk::{
-    events::room::message::{InReplyTo, Relation},
+    events::room::message::{InReplyTo, Relation, Replacement},
     events::room::message::{MessageEventContent, MessageType},
-    events::AnyMessageEventContent,
+    events::{custom::CustomEventContent, AnyMessageEventContent},
     identifiers::EventId,
     Error as MatrixError,
 };
+use matrix_sdk::{
+    events::room::message::{NoticeMessageEventContent, TextMessageEventContent},
+    room::Joined,
+    ClientConfig,
+};
 use matrix_sdk::{identifiers::RoomId, identifiers::UserId, Client};
+use serde_json::{json, Value as JsonValue};
 use url::Url;
 
-use crate::{config::Config, error::BotError};
+use crate::{config::Config, error::BotError, models::ReplyType};
 
 fn cache_dir() -> Result<PathBuf, BotError> {
     let mut dir = dirs::cache_dir().ok_or(BotError::NoCacheDirectoryError)?;
@@ -57,6 +63,21 @@ pub async fn get_users_in_room(
     }
 }
 
+/// Retrieve the power level of a user in a room. Users who are not
+/// members of the room, or rooms the bot is not in, have power level 0.
+pub async fn get_power_level(
+    client: &Client,
+    room_id: &RoomId,
+    user: &UserId,
+) -> Result<i64, MatrixError> {
+    if let Some(joined_room) = client.get_joined_room(room_id) {
+        let member = joined_room.get_member(user).await?;
+        Ok(member.map(|member| member.power_level()).unwrap_or(0))
+    } else {
+        Ok(0)
+    }
+}
+
 pub async fn get_rooms_for_user(
     client: &Client,
     user: &UserId,
@@ -81,34 +102,123 @@ pub async fn get_rooms_for_user(
     Ok(rooms_for_user)
 }
 
+/// Message content with HTML and plain text, as a notice or an
+/// ordinary message.
+fn message_content(plain: String, html: String, reply_type: ReplyType) -> MessageEventContent {
+    let msgtype = match reply_type {
+        ReplyType::Notice => MessageType::Notice(NoticeMessageEventContent::html(plain, html)),
+        ReplyType::Message => MessageType::Text(TextMessageEventContent::html(plain, html)),
+    };
+
+    MessageEventContent::new(msgtype)
+}
+
+/// Put message content in a thread. The SDK does not know about
+/// threads yet, so the content is sent as a custom event of the
+/// message type. Clients without thread support show it as a reply
+/// to the given event, or to the root of the thread.
+fn thread_content(
+    content: MessageEventContent,
+    thread: &EventId,
+    reply_to: Option<EventId>,
+) -> Result<AnyMessageEventContent, serde_json::Error> {
+    let mut data: BTreeMap<String, JsonValue> = match serde_json::to_value(content)? {
+        JsonValue::Object(map) => map.into_iter().collect(),
+        _ => BTreeMap::new(),
+    };
+
+    let in_reply_to = reply_to.as_ref().unwrap_or(thread);
+    data.insert(
+        "m.relates_to".to_owned(),
+        json!({
+            "rel_type": "m.thread",
+            "event_id": thread,
+            "is_falling_back": reply_to.is_none(),
+            "m.in_reply_to": { "event_id": in_reply_to },
+        }),
+    );
+
+    Ok(AnyMessageEventContent::_Custom(CustomEventContent {
+        event_type: "m.room.message".to_owned(),
+        data,
+    }))
+}
+
+/// Send message content to a room, returning the ID of the sent
+/// event. Failures are logged.
+async fn send_content(
+    client: &Client,
+    room_id: &RoomId,
+    content: AnyMessageEventContent,
+) -> Option<EventId> {
+    let room = client.get_joined_room(room_id)?;
+
+    match room.send(content, None).await {
+        Ok(response) => Some(response.event_id),
+        Err(e) => {
+            let html = extract_error_message(e);
+            error!("Error sending html: {}", html);
+            None
+        }
+    }
+}
+
 /// Send a message. The message is a tuple of HTML and plain text
-/// responses.
+/// responses. If a thread is given, the message is sent in that
+/// thread. Returns the ID of the sent message, if it was sent.
 pub async fn send_message(
     client: &Client,
     room_id: &RoomId,
     message: (&str, &str),
     reply_to: Option<EventId>,
-) {
+    thread: Option<&EventId>,
+    reply_type: ReplyType,
+) -> Option<EventId> {
     let (html, plain) = message;
-    let room = match client.get_joined_room(room_id) {
-        Some(room) => room,
-        _ => return,
+    let mut content = message_content(plain.trim().to_owned(), html.to_owned(), reply_type);
+
+    let content = match thread {
+        Some(thread) => match thread_content(content, thread, reply_to) {
+            Ok(content) => content,
+            Err(e) => {
+                error!("Error creating thread message: {}", e);
+                return None;
+            }
+        },
+        None => {
+            content.relates_to = reply_to.map(|event_id| Relation::Reply {
+                in_reply_to: InReplyTo::new(event_id),
+            });
+
+            AnyMessageEventContent::RoomMessage(content)
+        }
     };
 
-    let mut content = MessageEventContent::new(MessageType::Notice(
-        NoticeMessageEventContent::html(plain.trim(), html),
-    ));
+    send_content(client, room_id, content).await
+}
 
-    content.relates_to = reply_to.map(|event_id| Relation::Reply {
-        in_reply_to: InReplyTo::new(event_id),
-    });
+/// Replace the content of a message sent earlier. Clients that do not
+/// support edits show the new message with a "*" in front of it.
+pub async fn edit_message(
+    client: &Client,
+    room_id: &RoomId,
+    event_id: EventId,
+    message: (&str, &str),
+    reply_type: ReplyType,
+) -> Option<EventId> {
+    let (html, plain) = message;
+    let new_content = message_content(plain.trim().to_owned(), html.to_owned(), reply_type);
+    let mut content = message_content(
+        format!("* {}", plain.trim()),
+        format!("* {}", html),
+        reply_type,
+    );
+
+    content.relates_to = Some(Relation::Replacement(Replacement::new(
+        event_id,
+        Box::new(new_content),
+    )));
 
     let content = AnyMessageEventContent::RoomMessage(content);
-
-    let result = room.send(content, None).await;
-
-    if let Err(e) = result {
-        let html = extract_error_message(e);
-        error!("Error sending html: {}", html);
-    };
+    send_content(client, room_id, content).await
 }
@@ -7,6 +7,346 @@ pub struct RoomInfo {
     pub room_name: String,
 }
 
+/// Structured summary of a completed roll, independent of the game
+/// system that produced it. Stored in the roll history.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct RollSummary {
+    /// What was rolled, after variables were resolved (e.g. "1d20 +
+    /// 5" or "8 dice (ten-again, exceptional on 5 successes)").
+    pub expression: String,
+
+    /// Every individual die result, in the order rolled.
+    pub dice: Vec<i32>,
+
+    /// The headline number of the roll: the sum for regular dice,
+    /// the number of successes for dice pools, or the percentile
+    /// result for Call of Cthulhu.
+    pub total: i32,
+
+    /// Success or failure description, if the system has one.
+    pub outcome: Option<String>,
+}
+
+/// The result of a roll made by a command, along with the game
+/// system it was rolled for. Replies to rolls are rendered from it,
+/// by the built-in formatting or by the roll template of the room.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollResult {
+    pub system: GameSystem,
+    pub summary: RollSummary,
+}
+
+/// A single entry in the roll history.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RollRecord {
+    pub room_id: String,
+    pub user_id: String,
+
+    /// The full command text that produced the roll.
+    pub command: String,
+
+    pub result: RollSummary,
+
+    /// Seconds since the UNIX epoch.
+    pub timestamp: i64,
+
+    /// The fair mode session whose secret seeded the dice, if the
+    /// roll was made in fair mode.
+    pub fair_session: Option<i64>,
+}
+
+/// A named character played by a user in a room. Each character has
+/// its own set of variables.
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct Character {
+    pub name: String,
+
+    /// Whether variables currently resolve against this character.
+    pub active: bool,
+}
+
+/// A setting changed by the moderators of a room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomSetting {
+    /// Whether [[expression]] in ordinary messages is rolled.
+    InlineRolls,
+
+    /// What commands start with in the room, instead of the
+    /// configured default.
+    CommandPrefix,
+
+    /// Whether each thread keeps its own roll history.
+    ThreadScope,
+
+    /// The game system that !r rolls dice for.
+    DefaultSystem,
+
+    /// How much detail replies to rolls have.
+    Verbosity,
+
+    /// Whether the bot replies with notices or ordinary messages.
+    ReplyType,
+
+    /// How many dice of a dice pool are shown before the rest are cut
+    /// off.
+    MaxDisplayedDice,
+
+    /// The template replies to rolls of a game system are rendered
+    /// with, instead of the built-in formatting.
+    RollTemplate(GameSystem),
+
+    /// Whether uploaded .json and .toml files are imported as
+    /// character sheets.
+    SheetUploads,
+}
+
+impl RoomSetting {
+    /// Key of the setting in the database.
+    pub fn key(&self) -> &'static str {
+        match self {
+            RoomSetting::InlineRolls => "inline_rolls",
+            RoomSetting::CommandPrefix => "command_prefix",
+            RoomSetting::ThreadScope => "thread_scope",
+            RoomSetting::DefaultSystem => "default_system",
+            RoomSetting::Verbosity => "verbosity",
+            RoomSetting::ReplyType => "reply_type",
+            RoomSetting::MaxDisplayedDice => "max_displayed_dice",
+            RoomSetting::RollTemplate(GameSystem::Basic) => "roll_template_basic",
+            RoomSetting::RollTemplate(GameSystem::ChroniclesOfDarkness) => "roll_template_cofd",
+            RoomSetting::RollTemplate(GameSystem::CallOfCthulhu) => "roll_template_coc",
+            RoomSetting::SheetUploads => "sheet_uploads",
+        }
+    }
+}
+
+/// A game system that the bot can roll dice for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum GameSystem {
+    /// Regular dice expressions, like 1d20+5.
+    #[default]
+    Basic,
+
+    /// Dice pools of the Chronicles of Darkness.
+    ChroniclesOfDarkness,
+
+    /// Percentile rolls of Call of Cthulhu.
+    CallOfCthulhu,
+}
+
+impl GameSystem {
+    /// Every game system, in the order they are listed to users.
+    pub const ALL: [GameSystem; 3] = [
+        GameSystem::Basic,
+        GameSystem::ChroniclesOfDarkness,
+        GameSystem::CallOfCthulhu,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            GameSystem::Basic => "basic",
+            GameSystem::ChroniclesOfDarkness => "cofd",
+            GameSystem::CallOfCthulhu => "coc",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<GameSystem> {
+        match key {
+            "basic" => Some(GameSystem::Basic),
+            "cofd" => Some(GameSystem::ChroniclesOfDarkness),
+            "coc" => Some(GameSystem::CallOfCthulhu),
+            _ => None,
+        }
+    }
+}
+
+/// How much detail replies to rolls have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    Compact,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl Verbosity {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Verbosity::Compact => "compact",
+            Verbosity::Normal => "normal",
+            Verbosity::Detailed => "detailed",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Verbosity> {
+        match key {
+            "compact" => Some(Verbosity::Compact),
+            "normal" => Some(Verbosity::Normal),
+            "detailed" => Some(Verbosity::Detailed),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of Matrix message the bot replies with. Clients usually
+/// show notices less prominently, and other bots ignore them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ReplyType {
+    #[default]
+    Notice,
+    Message,
+}
+
+impl ReplyType {
+    pub fn key(&self) -> &'static str {
+        match self {
+            ReplyType::Notice => "notice",
+            ReplyType::Message => "message",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<ReplyType> {
+        match key {
+            "notice" => Some(ReplyType::Notice),
+            "message" => Some(ReplyType::Message),
+            _ => None,
+        }
+    }
+}
+
+/// The reply the bot sent to a command message, so that the reply can
+/// be edited when the command message is edited.
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct MessageReply {
+    pub reply_event_id: String,
+
+    /// Sender of the command message. Replies recorded before senders
+    /// were kept have none.
+    pub sender: Option<String>,
+
+    /// Root of the thread the command message was sent in, if any.
+    pub thread_id: Option<String>,
+}
+
+/// The command message answered by a roll reply of the bot, so that
+/// the roll can be made again by reacting to the reply.
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct CommandReply {
+    /// ID of the command message.
+    pub event_id: String,
+    pub sender: String,
+    pub message: String,
+
+    /// Root of the thread the command message was sent in, if any.
+    pub thread_id: Option<String>,
+}
+
+/// A fair mode session: the secret that rolls were seeded from while
+/// it lasted, and the commitment to the secret published during it.
+/// The secret must not be shown until the session is revealed.
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct FairnessSession {
+    pub id: i64,
+    pub secret: String,
+    pub commitment: String,
+    pub started_at: i64,
+    pub revealed_at: Option<i64>,
+}
+
+/// A stored command, run by name with its arguments filled in.
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct Macro {
+    pub name: String,
+    pub body: String,
+
+    /// Whether the macro is shared by everyone in the room.
+    pub room: bool,
+}
+
+/// The value of a user variable. Only integer variables can be used
+/// in dice pools and other arithmetic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariableValue {
+    Integer(i32),
+    Text(String),
+
+    /// A dice expression like "1d8+str", rolled by name.
+    Dice(String),
+}
+
+impl VariableValue {
+    /// Name of the type of value, as stored in the database.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VariableValue::Integer(_) => "integer",
+            VariableValue::Text(_) => "text",
+            VariableValue::Dice(_) => "dice",
+        }
+    }
+}
+
+impl std::fmt::Display for VariableValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableValue::Integer(value) => write!(f, "{}", value),
+            VariableValue::Text(text) => write!(f, "\"{}\"", text),
+            VariableValue::Dice(expression) => write!(f, "{} (dice)", expression),
+        }
+    }
+}
+
+/// How an update changes the value of a variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueChange {
+    /// Replace the value.
+    Set(i32),
+
+    /// Add to the existing value. The variable must already be set.
+    Add(i32),
+
+    /// Leave the value alone, for example when only changing bounds.
+    Keep,
+}
+
+impl ValueChange {
+    /// Calculate the new value from the old one, if there is one.
+    pub fn apply(&self, old: Option<i32>) -> Option<i32> {
+        match (self, old) {
+            (ValueChange::Set(value), _) => Some(*value),
+            (ValueChange::Add(amount), Some(old)) => Some(old.saturating_add(*amount)),
+            (ValueChange::Keep, Some(old)) => Some(old),
+            (_, None) => None,
+        }
+    }
+}
+
+/// How an update changes one of the bounds of a variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundChange {
+    Keep,
+    Set(i32),
+    Clear,
+}
+
+impl BoundChange {
+    pub fn apply(&self, old: Option<i32>) -> Option<i32> {
+        match self {
+            BoundChange::Keep => old,
+            BoundChange::Set(bound) => Some(*bound),
+            BoundChange::Clear => None,
+        }
+    }
+}
+
+/// A variable after an update, along with the value it had before.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UpdatedVariable {
+    /// None if the variable was not set before.
+    pub old_value: Option<i32>,
+    pub value: i32,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
 #[derive(Eq, PartialEq, Clone, Copy, Debug, sqlx::Type)]
 #[sqlx(rename_all = "snake_case")]
 pub enum AccountStatus {
@@ -1,5 +1,6 @@
+use super::variables::variable_name;
 use combine::error::ParseError;
-use combine::parser::char::{digit, letter, spaces};
+use combine::parser::char::{char, digit, spaces};
 use combine::stream::Stream;
 use combine::{many, many1, one_of, Parser};
 use thiserror::Error;
@@ -61,6 +62,10 @@ pub enum Element {
     /// resolved to a number by consulting the dtaabase.
     Variable(String),
 
+    /// This element in the expression is a room variable, shared by
+    /// everyone in the room. Written with a '#' in front of the name.
+    RoomVariable(String),
+
     /// This element is a simple number, and will be added or
     /// subtracted from the total dice amount depending on its
     /// corresponding Operator.
@@ -78,6 +83,16 @@ pub struct Amount {
     pub element: Element,
 }
 
+/// Parser for the name of a room variable, which is a variable name
+/// prefixed with '#'.
+fn room_variable_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    char('#').with(variable_name())
+}
+
 /// Parser that attempt to convert the text at the start of the dice
 /// parsing into an Amount instance.
 fn first_amount_parser<Input>() -> impl Parser<Input, Output = ParseResult<Amount>>
@@ -100,10 +115,16 @@ where
         }
     };
 
-    many1(letter())
-        .or(many1(digit()))
+    let room_variable = room_variable_parser().map(|name| {
+        Ok(Amount {
+            operator: Operator::Plus,
+            element: Element::RoomVariable(name),
+        })
+    });
+
+    room_variable
+        .or(variable_name().or(many1(digit())).map(map_first_amount))
         .skip(spaces().silent()) //Consume any space after first amount
-        .map(map_first_amount)
 }
 
 /// Attempt to convert some text in the middle or end of the dice roll
@@ -130,7 +151,9 @@ where
         }
     };
 
-    let parse_element = many1(letter()).or(many1(digit())).map(map_element);
+    let parse_element = room_variable_parser()
+        .map(|name| Ok(Element::RoomVariable(name)))
+        .or(variable_name().or(many1(digit())).map(map_element));
 
     let element_parser = parse_operator
         .skip(spaces().silent())
@@ -227,6 +250,19 @@ mod parse_single_amount_tests {
     //     )
     // }
 
+    #[test]
+    fn parse_single_room_variable_test() {
+        let result = parse_single_amount("#doom");
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            Amount {
+                operator: Operator::Plus,
+                element: Element::RoomVariable("doom".to_string())
+            }
+        )
+    }
+
     #[test]
     fn parse_single_number_test() {
         let result = parse_single_amount("1");
@@ -316,4 +352,55 @@ mod parse_many_amounts_tests {
     fn parse_complex_amount_expression() {
         assert!(parse_amounts("1 + myvariable - 2").is_ok());
     }
+
+    #[test]
+    fn parse_broad_variable_names_test() {
+        let result = parse_amounts("str_mod + skill2 - Überleben");
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                Amount {
+                    operator: Operator::Plus,
+                    element: Element::Variable("str_mod".to_string())
+                },
+                Amount {
+                    operator: Operator::Plus,
+                    element: Element::Variable("skill2".to_string())
+                },
+                Amount {
+                    operator: Operator::Minus,
+                    element: Element::Variable("Überleben".to_string())
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_room_variable_amounts_test() {
+        let result = parse_amounts("#momentum + 2 - #doom");
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                Amount {
+                    operator: Operator::Plus,
+                    element: Element::RoomVariable("momentum".to_string())
+                },
+                Amount {
+                    operator: Operator::Plus,
+                    element: Element::Number(2)
+                },
+                Amount {
+                    operator: Operator::Minus,
+                    element: Element::RoomVariable("doom".to_string())
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_room_variable_without_name_should_error() {
+        assert!(parse_amounts("#").is_err());
+        assert!(parse_amounts("1 + #").is_err());
+    }
 }
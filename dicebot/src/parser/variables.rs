@@ -1,7 +1,15 @@
-use combine::parser::char::{char, digit, letter, spaces};
-use combine::{many1, optional, Parser};
+use crate::basic::dice::Dice;
+use crate::models::{BoundChange, VariableValue};
+use crate::parser::dice::{parse_single_amount, Element, Operator};
+use combine::error::ParseError;
+use combine::parser::char::{char, digit, spaces};
+use combine::stream::Stream;
+use combine::{many, many1, optional, satisfy, Parser};
 use thiserror::Error;
 
+/// Most dice that can be rolled in a variable update.
+const MAX_DICE: u32 = 1000;
+
 enum ParsedValue {
     Valid(i32),
     Invalid,
@@ -15,12 +23,65 @@ pub enum VariableParsingError {
     #[error("unconsumed input")]
     UnconsumedInput,
 
+    #[error("invalid variable name")]
+    InvalidName,
+
+    #[error("invalid expression: {0}")]
+    InvalidExpression(String),
+
+    #[error("invalid bound, must be a number or 'none': {0}")]
+    InvalidBound(String),
+
     #[error("parser error: {0}")]
     InternalParseError(#[from] combine::error::StringStreamError),
 }
 
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Parser for variable names: letters (in any language), digits and
+/// underscores, not starting with a digit so that names can't be
+/// confused with numbers. Used by every parser that accepts
+/// variables.
+pub fn variable_name<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    satisfy(is_name_start)
+        .and(many(satisfy(is_name_char)))
+        .map(|(first, rest): (char, String)| format!("{}{}", first, rest))
+}
+
+/// Whether a string can be used as a variable name. Agrees with the
+/// variable_name parser.
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map_or(false, is_name_start) && chars.all(is_name_char)
+}
+
+/// Variable names are case-insensitive. They are stored and looked
+/// up in this form.
+pub fn normalize_variable_name(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Parse the input of commands that take only a variable name.
+pub fn parse_variable_name(input: &str) -> Result<String, VariableParsingError> {
+    let name = input.trim();
+    match is_valid_variable_name(name) {
+        true => Ok(name.to_owned()),
+        false => Err(VariableParsingError::InvalidName),
+    }
+}
+
 pub fn parse_set_variable(input: &str) -> Result<(String, i32), VariableParsingError> {
-    let name = many1(letter()).map(|value: String| value);
+    let name = variable_name();
 
     let maybe_minus = optional(char('-')).map(|value: Option<char>| match value {
         Some(minus_sign) => String::from(minus_sign),
@@ -50,6 +111,224 @@ pub fn parse_set_variable(input: &str) -> Result<(String, i32), VariableParsingE
     }
 }
 
+/// How an update combines the expression with the current value of
+/// the variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOperator {
+    /// `=`, or just a value after the name.
+    Assign,
+
+    /// `+=`
+    Add,
+
+    /// `-=`
+    Subtract,
+}
+
+/// One part of a variable update expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Dice(Dice),
+    Amount(Element),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTerm {
+    pub operator: Operator,
+    pub term: Term,
+}
+
+/// A parsed variable update, like "hp -= 1d6" or "hp = maxhp min 0".
+/// The expression is empty if only the bounds are changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableUpdate {
+    pub name: String,
+    pub operator: UpdateOperator,
+    pub expression: Vec<SignedTerm>,
+    pub min: BoundChange,
+    pub max: BoundChange,
+}
+
+fn parse_term(operator: Operator, token: &str) -> Result<SignedTerm, VariableParsingError> {
+    let invalid = || VariableParsingError::InvalidExpression(token.to_owned());
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+    let dice = token
+        .split_once('d')
+        .filter(|(count, sides)| (count.is_empty() || is_number(count)) && is_number(sides));
+
+    let term = match dice {
+        Some((count, sides)) => {
+            let count = match count {
+                "" => 1,
+                count => count.parse::<u32>().map_err(|_| invalid())?,
+            };
+
+            let sides = sides.parse::<u32>().map_err(|_| invalid())?;
+
+            if count == 0 || count > MAX_DICE || sides == 0 {
+                return Err(invalid());
+            }
+
+            Term::Dice(Dice::new(count, sides))
+        }
+        None => {
+            let amount = parse_single_amount(token).map_err(|_| invalid())?;
+            Term::Amount(amount.element)
+        }
+    };
+
+    Ok(SignedTerm { operator, term })
+}
+
+/// Parse an expression of dice, numbers and variables joined with "+"
+/// and "-", like "1d6 + strength - 1".
+pub fn parse_expression(input: &str) -> Result<Vec<SignedTerm>, VariableParsingError> {
+    let mut terms = vec![];
+    let mut rest = input.trim();
+    let mut operator = Operator::Plus;
+
+    if let Some(negated) = rest.strip_prefix('-') {
+        operator = Operator::Minus;
+        rest = negated;
+    }
+
+    loop {
+        let end = rest.find(|c| c == '+' || c == '-').unwrap_or(rest.len());
+        terms.push(parse_term(operator, rest[..end].trim())?);
+
+        operator = match rest[end..].chars().next() {
+            Some('+') => Operator::Plus,
+            Some(_) => Operator::Minus,
+            None => return Ok(terms),
+        };
+
+        rest = &rest[end + 1..];
+    }
+}
+
+fn parse_bound(input: Option<&str>) -> Result<BoundChange, VariableParsingError> {
+    match input {
+        Some(bound) if bound.eq_ignore_ascii_case("none") => Ok(BoundChange::Clear),
+        Some(bound) => bound
+            .parse::<i32>()
+            .map(BoundChange::Set)
+            .map_err(|_| VariableParsingError::InvalidBound(bound.to_owned())),
+        None => Err(VariableParsingError::InvalidBound(String::new())),
+    }
+}
+
+/// Split the name of the variable off the front of the input.
+fn split_variable_name(input: &str) -> Result<(&str, &str), VariableParsingError> {
+    let input = input.trim();
+    let end = input
+        .find(|c: char| !is_name_char(c))
+        .unwrap_or(input.len());
+
+    let (name, rest) = input.split_at(end);
+    match is_valid_variable_name(name) {
+        true => Ok((name, rest.trim())),
+        false => Err(VariableParsingError::InvalidName),
+    }
+}
+
+/// Parse the input of the set command: a variable name, an optional
+/// operator (`=`, `+=` or `-=`), an expression, and optionally new
+/// bounds for the variable, written as "min 0" and "max 10".
+pub fn parse_variable_update(input: &str) -> Result<VariableUpdate, VariableParsingError> {
+    let (name, rest) = split_variable_name(input)?;
+
+    let (operator, rest) = if let Some(rest) = rest.strip_prefix("+=") {
+        (UpdateOperator::Add, rest)
+    } else if let Some(rest) = rest.strip_prefix("-=") {
+        (UpdateOperator::Subtract, rest)
+    } else {
+        (
+            UpdateOperator::Assign,
+            rest.strip_prefix('=').unwrap_or(rest),
+        )
+    };
+
+    let mut words = rest.split_whitespace();
+    let mut expression = vec![];
+    let mut min = BoundChange::Keep;
+    let mut max = BoundChange::Keep;
+
+    while let Some(word) = words.next() {
+        match word.to_lowercase().as_ref() {
+            "min" => min = parse_bound(words.next())?,
+            "max" => max = parse_bound(words.next())?,
+            _ => expression.push(word),
+        }
+    }
+
+    let expression = match expression.is_empty() {
+        true => vec![],
+        false => parse_expression(&expression.join(" "))?,
+    };
+
+    // Only an assignment can leave out the value, to change bounds.
+    let changes_bounds = min != BoundChange::Keep || max != BoundChange::Keep;
+    if expression.is_empty() && (operator != UpdateOperator::Assign || !changes_bounds) {
+        return Err(VariableParsingError::InvalidValue);
+    }
+
+    Ok(VariableUpdate {
+        name: name.to_owned(),
+        operator,
+        expression,
+        min,
+        max,
+    })
+}
+
+/// Parse the input of the set command when it stores text or a dice
+/// expression instead of a number: "name text some words" or "name
+/// dice 1d8+str". Returns None for any other input. Dice expressions
+/// are checked here, but only rolled when used.
+pub fn parse_typed_variable(
+    input: &str,
+) -> Result<Option<(String, VariableValue)>, VariableParsingError> {
+    let (name, rest) = split_variable_name(input)?;
+    let (kind, value) = match rest.split_once(char::is_whitespace) {
+        Some((kind, value)) => (kind.to_lowercase(), value.trim()),
+        None => return Ok(None),
+    };
+
+    let value = match kind.as_ref() {
+        "text" => VariableValue::Text(value.to_owned()),
+        "dice" => {
+            parse_expression(value)?;
+            VariableValue::Dice(value.to_owned())
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some((name.to_owned(), value)))
+}
+
+/// Parse the input of the increment and decrement commands: a
+/// variable name, followed by an optional expression that defaults
+/// to 1.
+pub fn parse_increment(
+    input: &str,
+    operator: UpdateOperator,
+) -> Result<VariableUpdate, VariableParsingError> {
+    let (name, rest) = split_variable_name(input)?;
+    let expression = match rest {
+        "" => parse_expression("1")?,
+        rest => parse_expression(rest)?,
+    };
+
+    Ok(VariableUpdate {
+        name: name.to_owned(),
+        operator,
+        expression,
+        min: BoundChange::Keep,
+        max: BoundChange::Keep,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,10 +340,131 @@ mod tests {
         assert_eq!(("myvar".to_string(), 5), result.unwrap());
     }
 
+    #[test]
+    fn parse_name_with_digits_and_underscores() {
+        let result = parse_set_variable("str_mod2 3");
+        assert_eq!(("str_mod2".to_string(), 3), result.unwrap());
+    }
+
+    #[test]
+    fn parse_unicode_name() {
+        let result = parse_set_variable("Körperkraft 4");
+        assert_eq!(("Körperkraft".to_string(), 4), result.unwrap());
+    }
+
+    #[test]
+    fn valid_variable_names_test() {
+        assert!(is_valid_variable_name("_hidden"));
+        assert!(is_valid_variable_name("Überleben"));
+        assert!(is_valid_variable_name("skill2"));
+        assert!(!is_valid_variable_name("2skill"));
+        assert!(!is_valid_variable_name("two words"));
+        assert!(!is_valid_variable_name(""));
+    }
+
     #[test]
     fn parse_negative_number() {
         let result = parse_set_variable("myvar -5");
         assert!(result.is_ok());
         assert_eq!(("myvar".to_string(), -5), result.unwrap());
     }
+
+    fn number(operator: Operator, value: i32) -> SignedTerm {
+        SignedTerm {
+            operator,
+            term: Term::Amount(Element::Number(value)),
+        }
+    }
+
+    #[test]
+    fn parse_relative_update_test() {
+        let update = parse_variable_update("hp -= 1d6 + con").unwrap();
+        assert_eq!(update.name, "hp");
+        assert_eq!(update.operator, UpdateOperator::Subtract);
+        assert_eq!(
+            update.expression,
+            vec![
+                SignedTerm {
+                    operator: Operator::Plus,
+                    term: Term::Dice(Dice::new(1, 6))
+                },
+                SignedTerm {
+                    operator: Operator::Plus,
+                    term: Term::Amount(Element::Variable("con".to_string()))
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_assignment_test() {
+        let update = parse_variable_update("hp = maxhp").unwrap();
+        assert_eq!(update.operator, UpdateOperator::Assign);
+        assert_eq!(
+            update.expression,
+            vec![SignedTerm {
+                operator: Operator::Plus,
+                term: Term::Amount(Element::Variable("maxhp".to_string()))
+            }]
+        );
+
+        // The original "name value" form still works.
+        let update = parse_variable_update("myvar -5").unwrap();
+        assert_eq!(update.operator, UpdateOperator::Assign);
+        assert_eq!(update.expression, vec![number(Operator::Minus, 5)]);
+    }
+
+    #[test]
+    fn parse_bounds_test() {
+        let update = parse_variable_update("hp = 10 min 0 max 12").unwrap();
+        assert_eq!(update.expression, vec![number(Operator::Plus, 10)]);
+        assert_eq!(update.min, BoundChange::Set(0));
+        assert_eq!(update.max, BoundChange::Set(12));
+
+        let update = parse_variable_update("hp max none").unwrap();
+        assert!(update.expression.is_empty());
+        assert_eq!(update.min, BoundChange::Keep);
+        assert_eq!(update.max, BoundChange::Clear);
+    }
+
+    #[test]
+    fn parse_invalid_update_test() {
+        assert!(parse_variable_update("hp").is_err());
+        assert!(parse_variable_update("hp += min 0").is_err());
+        assert!(parse_variable_update("hp = 1d0").is_err());
+        assert!(parse_variable_update("hp = 1 + + 2").is_err());
+        assert!(parse_variable_update("hp = 5 min low").is_err());
+        assert!(parse_variable_update("= 5").is_err());
+    }
+
+    #[test]
+    fn parse_increment_test() {
+        let update = parse_increment("xp", UpdateOperator::Add).unwrap();
+        assert_eq!(update.expression, vec![number(Operator::Plus, 1)]);
+
+        let update = parse_increment("xp 3", UpdateOperator::Add).unwrap();
+        assert_eq!(update.expression, vec![number(Operator::Plus, 3)]);
+    }
+
+    #[test]
+    fn parse_typed_variable_test() {
+        assert_eq!(
+            parse_typed_variable("name text Old Man Henderson").unwrap(),
+            Some((
+                "name".to_string(),
+                VariableValue::Text("Old Man Henderson".to_string())
+            ))
+        );
+
+        assert_eq!(
+            parse_typed_variable("sword dice 1d8+str").unwrap(),
+            Some((
+                "sword".to_string(),
+                VariableValue::Dice("1d8+str".to_string())
+            ))
+        );
+
+        assert_eq!(parse_typed_variable("hp = 5").unwrap(), None);
+        assert!(parse_typed_variable("sword dice 1d8 +").is_err());
+    }
 }
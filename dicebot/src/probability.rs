@@ -0,0 +1,475 @@
+//! Common code for calculating the odds of dice rolls. Each game
+//! system builds a Distribution of its outcomes, either exactly or by
+//! simulating a bounded number of rolls, and reports it as Odds.
+use itertools::Itertools;
+use std::convert::TryFrom;
+
+/// Sequences of rolls less likely than this are not explored any
+/// further when enumerating rolls. This keeps exploding dice finite.
+const MIN_PROBABILITY: f64 = 1e-12;
+
+/// Width of the longest histogram bar, in characters.
+const HISTOGRAM_WIDTH: usize = 30;
+
+/// Histograms with more rows than this have their outcomes grouped
+/// into ranges.
+const MAX_HISTOGRAM_ROWS: usize = 20;
+
+/// Outcomes at either end of a distribution less likely than this
+/// are left out of histograms.
+const HISTOGRAM_CUTOFF: f64 = 0.0005;
+
+/// How many individual dice a simulation may roll in total. The
+/// number of simulated rolls is derived from this.
+pub(crate) const SIMULATION_BUDGET: u64 = 2_000_000;
+
+/// The fewest simulated rolls that still give a useful estimate. If
+/// the budget does not allow this many rolls, the expression is too
+/// large to simulate.
+pub(crate) const MIN_SIMULATED_ROLLS: u64 = 200;
+
+/// The most simulated rolls that will be made, no matter how small
+/// the expression is.
+pub(crate) const MAX_SIMULATED_ROLLS: u64 = 20_000;
+
+/// Amount of rolls to simulate for an expression that rolls the
+/// given amount of dice per roll, or None if the expression is too
+/// large to simulate.
+pub(crate) fn simulated_rolls(dice_per_roll: u64) -> Option<u64> {
+    let rolls = (SIMULATION_BUDGET / dice_per_roll.max(1)).min(MAX_SIMULATED_ROLLS);
+    Some(rolls).filter(|&rolls| rolls >= MIN_SIMULATED_ROLLS)
+}
+
+/// Probability distribution over whole-number outcomes, such as the
+/// total of a roll or an amount of successes. Stored densely from the
+/// lowest possible outcome upwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// The lowest outcome, which the first probability belongs to.
+    offset: i32,
+    probabilities: Vec<f64>,
+}
+
+impl Distribution {
+    /// A single outcome that always happens.
+    pub fn constant(value: i32) -> Distribution {
+        Distribution {
+            offset: value,
+            probabilities: vec![1.0],
+        }
+    }
+
+    /// Every outcome from low to high (inclusive) is equally likely.
+    pub fn uniform(low: i32, high: i32) -> Distribution {
+        let count = (high - low + 1).max(1) as usize;
+        Distribution {
+            offset: low,
+            probabilities: vec![1.0 / count as f64; count],
+        }
+    }
+
+    /// Build a distribution out of (outcome, probability) pairs.
+    /// Outcomes may be repeated; their probabilities are summed.
+    pub fn from_outcomes<I: IntoIterator<Item = (i32, f64)>>(outcomes: I) -> Distribution {
+        let outcomes: Vec<(i32, f64)> = outcomes.into_iter().collect();
+        let low = outcomes.iter().map(|&(value, _)| value).min().unwrap_or(0);
+        let high = outcomes.iter().map(|&(value, _)| value).max().unwrap_or(0);
+
+        let mut probabilities = vec![0.0; (high - low + 1) as usize];
+        for (value, probability) in outcomes {
+            probabilities[(value - low) as usize] += probability;
+        }
+
+        Distribution {
+            offset: low,
+            probabilities,
+        }
+    }
+
+    /// Estimate a distribution from simulated outcomes.
+    pub fn from_samples<I: IntoIterator<Item = i32>>(samples: I) -> Distribution {
+        let samples: Vec<i32> = samples.into_iter().collect();
+        let weight = 1.0 / samples.len().max(1) as f64;
+        Self::from_outcomes(samples.into_iter().map(|value| (value, weight)))
+    }
+
+    /// Distribution of the sum of two independent outcomes.
+    pub fn add(&self, other: &Distribution) -> Distribution {
+        let mut probabilities = vec![0.0; self.probabilities.len() + other.probabilities.len() - 1];
+
+        for (i, p1) in self.probabilities.iter().enumerate() {
+            for (j, p2) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += p1 * p2;
+            }
+        }
+
+        Distribution {
+            offset: self.offset + other.offset,
+            probabilities,
+        }
+    }
+
+    /// Distribution of the negated outcome.
+    pub fn negate(&self) -> Distribution {
+        Distribution {
+            offset: -self.max(),
+            probabilities: self.probabilities.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Distribution of the outcome plus a fixed amount.
+    pub fn shift(&self, amount: i32) -> Distribution {
+        Distribution {
+            offset: self.offset + amount,
+            probabilities: self.probabilities.clone(),
+        }
+    }
+
+    /// Remove vanishingly unlikely outcomes from both ends of the
+    /// distribution. Keeps repeated additions of exploding dice small.
+    pub fn trimmed(mut self) -> Distribution {
+        while self.probabilities.len() > 1 && self.probabilities[0] < MIN_PROBABILITY {
+            self.probabilities.remove(0);
+            self.offset += 1;
+        }
+
+        while self.probabilities.len() > 1
+            && self.probabilities.last().copied().unwrap_or(0.0) < MIN_PROBABILITY
+        {
+            self.probabilities.pop();
+        }
+
+        self
+    }
+
+    /// The lowest possible outcome.
+    pub fn min(&self) -> i32 {
+        self.offset
+    }
+
+    /// The highest possible outcome.
+    pub fn max(&self) -> i32 {
+        self.offset + self.probabilities.len() as i32 - 1
+    }
+
+    /// Chance of exactly this outcome.
+    pub fn probability(&self, value: i32) -> f64 {
+        usize::try_from(value - self.offset)
+            .ok()
+            .and_then(|index| self.probabilities.get(index))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Chance of this outcome or any higher one.
+    pub fn at_least(&self, value: i32) -> f64 {
+        self.outcomes()
+            .filter(|&(outcome, _)| outcome >= value)
+            .map(|(_, probability)| probability)
+            .sum()
+    }
+
+    /// The average outcome.
+    pub fn expected_value(&self) -> f64 {
+        self.outcomes()
+            .map(|(outcome, probability)| outcome as f64 * probability)
+            .sum()
+    }
+
+    /// Every outcome from lowest to highest, with its probability.
+    pub fn outcomes(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        let offset = self.offset;
+        self.probabilities
+            .iter()
+            .enumerate()
+            .map(move |(index, &probability)| (offset + index as i32, probability))
+    }
+
+    /// Histogram rows of the distribution. Very unlikely outcomes at
+    /// either end are left out, and large distributions have their
+    /// outcomes grouped into ranges so the histogram stays readable.
+    pub fn histogram_rows(&self) -> Vec<(String, f64)> {
+        let likely = |&(_, probability): &(i32, f64)| probability >= HISTOGRAM_CUTOFF;
+        let low = self.outcomes().find(likely).map(|(outcome, _)| outcome);
+        let high = self
+            .outcomes()
+            .filter(likely)
+            .last()
+            .map(|(outcome, _)| outcome);
+        let (low, high) = (low.unwrap_or(self.min()), high.unwrap_or(self.max()));
+
+        let len = (high - low + 1) as usize;
+        let group_size = (len + MAX_HISTOGRAM_ROWS - 1) / MAX_HISTOGRAM_ROWS;
+
+        self.outcomes()
+            .filter(|&(outcome, _)| outcome >= low && outcome <= high)
+            .chunks(group_size.max(1))
+            .into_iter()
+            .map(|group| {
+                let group: Vec<(i32, f64)> = group.collect();
+                let (first, last) = (group[0].0, group[group.len() - 1].0);
+                let label = match first == last {
+                    true => first.to_string(),
+                    false => format!("{}-{}", first, last),
+                };
+
+                (label, group.iter().map(|&(_, p)| p).sum())
+            })
+            .collect()
+    }
+}
+
+/// One die result chosen while enumerating rolls.
+struct Choice {
+    result: i32,
+    sides: i32,
+
+    /// The sequence was too unlikely to explore any further, so no
+    /// other results are tried for this die.
+    truncated: bool,
+}
+
+/// Stands in for a random number generator, and walks through every
+/// possible sequence of die results instead. Each execution of a
+/// rolling function receives the next sequence. This allows the
+/// odds of a roll to be calculated exactly, using the same rules that
+/// actually roll the dice.
+pub(crate) struct RollEnumerator {
+    path: Vec<Choice>,
+    position: usize,
+    probability: f64,
+}
+
+impl RollEnumerator {
+    fn new() -> RollEnumerator {
+        RollEnumerator {
+            path: vec![],
+            position: 0,
+            probability: 1.0,
+        }
+    }
+
+    /// The result (1 to sides) of the next die in the current
+    /// sequence of rolls.
+    pub(crate) fn next_die(&mut self, sides: i32) -> i32 {
+        let sides = sides.max(1);
+
+        if self.position == self.path.len() {
+            let probability = self.probability / sides as f64;
+            self.path.push(Choice {
+                result: 1,
+                sides,
+                truncated: probability < MIN_PROBABILITY,
+            });
+        }
+
+        let choice = &self.path[self.position];
+        self.position += 1;
+        self.probability /= choice.sides as f64;
+        choice.result
+    }
+
+    /// Move on to the next sequence of die results. Returns false if
+    /// every sequence has been visited.
+    fn advance(&mut self) -> bool {
+        self.path.truncate(self.position);
+
+        while let Some(choice) = self.path.last_mut() {
+            if choice.result < choice.sides && !choice.truncated {
+                choice.result += 1;
+                self.position = 0;
+                self.probability = 1.0;
+                return true;
+            }
+
+            self.path.pop();
+        }
+
+        false
+    }
+}
+
+/// Execute a rolling function once for every possible sequence of
+/// die results, returning each outcome with its probability.
+pub(crate) fn enumerate_rolls<T, F>(mut roll: F) -> Vec<(T, f64)>
+where
+    F: FnMut(&mut RollEnumerator) -> T,
+{
+    let mut enumerator = RollEnumerator::new();
+    let mut outcomes = vec![];
+
+    loop {
+        let outcome = roll(&mut enumerator);
+        outcomes.push((outcome, enumerator.probability));
+
+        if !enumerator.advance() {
+            break;
+        }
+    }
+
+    outcomes
+}
+
+/// The calculated odds of a roll, ready to be shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Odds {
+    /// What the odds were calculated for.
+    pub description: String,
+
+    /// Named statistics, like success chance or expected value.
+    pub statistics: Vec<(String, String)>,
+
+    /// Labeled histogram rows with their probabilities.
+    pub histogram: Vec<(String, f64)>,
+
+    /// If the odds were estimated by simulation instead of calculated
+    /// exactly, how many rolls were simulated.
+    pub simulated_rolls: Option<u64>,
+}
+
+/// Format a probability as a percentage.
+pub fn percent(probability: f64) -> String {
+    format!("{:.2}%", probability * 100.0)
+}
+
+/// Draw an ASCII histogram, with bars scaled to the most likely row.
+pub fn histogram(rows: &[(String, f64)]) -> String {
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let highest = rows.iter().map(|&(_, p)| p).fold(0.0, f64::max);
+
+    rows.iter()
+        .map(|(label, probability)| {
+            let width = match highest > 0.0 {
+                true => (probability / highest * HISTOGRAM_WIDTH as f64).round() as usize,
+                false => 0,
+            };
+
+            format!(
+                "{:>label_width$} | {:<bar_width$} {}",
+                label,
+                "#".repeat(width),
+                percent(*probability),
+                label_width = label_width,
+                bar_width = HISTOGRAM_WIDTH
+            )
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn two_dice_sum_test() {
+        let d6 = Distribution::uniform(1, 6);
+        let two_d6 = d6.add(&d6);
+
+        assert_eq!(2, two_d6.min());
+        assert_eq!(12, two_d6.max());
+        assert_close(6.0 / 36.0, two_d6.probability(7));
+        assert_close(1.0 / 36.0, two_d6.probability(12));
+        assert_close(7.0, two_d6.expected_value());
+    }
+
+    #[test]
+    fn negate_and_shift_test() {
+        let d4 = Distribution::uniform(1, 4).negate().shift(10);
+        assert_eq!(6, d4.min());
+        assert_eq!(9, d4.max());
+        assert_close(7.5, d4.expected_value());
+    }
+
+    #[test]
+    fn at_least_test() {
+        let d20 = Distribution::uniform(1, 20);
+        assert_close(0.3, d20.at_least(15));
+        assert_close(1.0, d20.at_least(-5));
+        assert_close(0.0, d20.at_least(21));
+    }
+
+    #[test]
+    fn from_samples_test() {
+        let dist = Distribution::from_samples(vec![1, 1, 3, 3]);
+        assert_close(0.5, dist.probability(1));
+        assert_close(0.0, dist.probability(2));
+        assert_close(0.5, dist.probability(3));
+    }
+
+    #[test]
+    fn enumerate_rolls_visits_every_sequence_test() {
+        // Two d6 rolls, summed.
+        let outcomes = enumerate_rolls(|dice| dice.next_die(6) + dice.next_die(6));
+        assert_eq!(36, outcomes.len());
+
+        let dist = Distribution::from_outcomes(outcomes);
+        assert_close(6.0 / 36.0, dist.probability(7));
+    }
+
+    #[test]
+    fn enumerate_rolls_handles_varying_sequence_lengths_test() {
+        // Roll a d2, and roll another d2 only if the first was a 2.
+        let outcomes = enumerate_rolls(|dice| match dice.next_die(2) {
+            2 => 2 + dice.next_die(2),
+            first => first,
+        });
+
+        let dist = Distribution::from_outcomes(outcomes);
+        assert_close(0.5, dist.probability(1));
+        assert_close(0.25, dist.probability(3));
+        assert_close(0.25, dist.probability(4));
+    }
+
+    #[test]
+    fn enumerate_rolls_terminates_on_endless_explosions_test() {
+        // Keep rolling as long as the die comes up 2.
+        let outcomes = enumerate_rolls(|dice| {
+            let mut count = 0;
+            while dice.next_die(2) == 2 {
+                count += 1;
+            }
+            count
+        });
+
+        let dist = Distribution::from_outcomes(outcomes);
+        assert_close(0.5, dist.probability(0));
+        assert_close(0.25, dist.probability(1));
+        assert_close(1.0, dist.outcomes().map(|(_, p)| p).sum());
+    }
+
+    #[test]
+    fn histogram_groups_large_distributions_test() {
+        let rows = Distribution::uniform(1, 100).histogram_rows();
+        assert_eq!(20, rows.len());
+        assert_eq!("1-5", rows[0].0);
+        assert_close(0.05, rows[0].1);
+    }
+
+    #[test]
+    fn histogram_leaves_out_unlikely_outcomes_test() {
+        let dist = Distribution::from_outcomes(vec![(0, 0.0001), (1, 0.5), (2, 0.4999)]);
+        let labels: Vec<String> = dist.histogram_rows().into_iter().map(|(l, _)| l).collect();
+        assert_eq!(vec!["1", "2"], labels);
+    }
+
+    #[test]
+    fn histogram_scales_bars_test() {
+        let rows = vec![("1".to_string(), 0.5), ("2".to_string(), 0.25)];
+        let drawn = histogram(&rows);
+        let lines: Vec<&str> = drawn.lines().collect();
+
+        assert_eq!(2, lines.len());
+        assert_eq!(HISTOGRAM_WIDTH, lines[0].matches('#').count());
+        assert_eq!(HISTOGRAM_WIDTH / 2, lines[1].matches('#').count());
+        assert!(lines[0].ends_with("50.00%"));
+    }
+}
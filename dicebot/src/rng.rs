@@ -0,0 +1,103 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::{Arc, Mutex};
+
+/// The source of randomness for every dice roll. Rolls are random by
+/// default, and the same every time if the generator is seeded,
+/// which is how tests and dicebot-cmd get repeatable rolls. Clones
+/// share one generator.
+#[derive(Clone, Debug)]
+pub struct DiceRng {
+    rng: Arc<Mutex<StdRng>>,
+
+    /// The fair mode session whose secret seeded the generator.
+    fair_session: Option<i64>,
+}
+
+impl DiceRng {
+    fn new(rng: StdRng) -> DiceRng {
+        DiceRng {
+            rng: Arc::new(Mutex::new(rng)),
+            fair_session: None,
+        }
+    }
+
+    pub fn from_entropy() -> DiceRng {
+        DiceRng::new(StdRng::from_entropy())
+    }
+
+    pub fn seeded(seed: u64) -> DiceRng {
+        DiceRng::new(StdRng::seed_from_u64(seed))
+    }
+
+    /// A generator seeded with a full 32 byte seed, like the seeds of
+    /// fair mode rolls.
+    pub fn from_seed(seed: [u8; 32]) -> DiceRng {
+        DiceRng::new(StdRng::from_seed(seed))
+    }
+
+    /// Mark the generator as seeded by the secret of a fair mode
+    /// session, so rolls made with it can name the session.
+    pub fn in_fair_session(self, session_id: i64) -> DiceRng {
+        DiceRng {
+            fair_session: Some(session_id),
+            ..self
+        }
+    }
+
+    /// The fair mode session the generator was seeded for, if any.
+    pub fn fair_session(&self) -> Option<i64> {
+        self.fair_session
+    }
+
+    /// Roll dice with the generator. The generator is locked until
+    /// the rolling is done, so the function must not block.
+    pub fn with<T>(&self, roll: impl FnOnce(&mut StdRng) -> T) -> T {
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        roll(&mut rng)
+    }
+
+    /// A generator of its own, seeded from this one, to roll lots of
+    /// dice with without keeping this one locked. Its rolls still
+    /// follow from the seed of this generator.
+    pub fn split(&self) -> StdRng {
+        self.with(|rng| StdRng::from_rng(rng).expect("Could not seed from the dice generator"))
+    }
+}
+
+impl Default for DiceRng {
+    fn default() -> DiceRng {
+        DiceRng::from_entropy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_rng_repeats_rolls_test() {
+        let roll = |rng: &DiceRng| rng.with(|rng| rng.gen_range(1..=1000));
+
+        let first = DiceRng::seeded(42);
+        let second = DiceRng::seeded(42);
+        let rolls: Vec<u32> = (0..10).map(|_| roll(&first)).collect();
+        assert_eq!(rolls, (0..10).map(|_| roll(&second)).collect::<Vec<_>>());
+
+        // Clones continue the same sequence instead of repeating it.
+        let clone = first.clone();
+        assert_ne!(roll(&clone), roll(&DiceRng::seeded(42)));
+    }
+
+    #[test]
+    fn split_rng_follows_seed_test() {
+        let first = DiceRng::seeded(42).split().gen_range(1..=1000);
+        assert_eq!(first, DiceRng::seeded(42).split().gen_range(1..=1000));
+
+        // Splitting moves the shared generator on.
+        let rng = DiceRng::seeded(42);
+        let split = rng.split().gen_range(1..=1000);
+        assert_ne!(rng.split().gen_range(1..=1000), split);
+    }
+}
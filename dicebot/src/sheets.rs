@@ -0,0 +1,304 @@
+//! Character sheets: all of a user's variables in a room as a single
+//! JSON or TOML document, so they can be imported and exported in one
+//! go instead of one `!set` command at a time.
+use crate::parser::variables::is_valid_variable_name;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use thiserror::Error;
+
+/// The most variables a single sheet can import.
+pub const MAX_SHEET_VARIABLES: usize = 500;
+
+/// Largest uploaded sheet file, in bytes, that will be downloaded.
+pub const MAX_SHEET_FILE_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetFormat {
+    Json,
+    Toml,
+}
+
+impl SheetFormat {
+    /// Look up a format by name or file extension.
+    pub fn from_name(name: &str) -> Option<SheetFormat> {
+        match name.trim().to_lowercase().as_ref() {
+            "json" => Some(SheetFormat::Json),
+            "toml" => Some(SheetFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SheetFormat::Json => "json",
+            SheetFormat::Toml => "toml",
+        }
+    }
+
+    /// Guess the format of an unlabeled document. A sheet in JSON is
+    /// always an object, while TOML cannot start with a brace.
+    fn detect(document: &str) -> SheetFormat {
+        match document.trim_start().starts_with('{') {
+            true => SheetFormat::Json,
+            false => SheetFormat::Toml,
+        }
+    }
+}
+
+impl fmt::Display for SheetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Why a single key of a sheet could not be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyError {
+    pub key: String,
+    pub reason: String,
+}
+
+impl KeyError {
+    fn new(key: &str, reason: &str) -> KeyError {
+        KeyError {
+            key: key.to_owned(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SheetError {
+    #[error("sheet is not valid {0}: {1}")]
+    InvalidDocument(SheetFormat, String),
+
+    #[error("sheet file is larger than {} bytes", MAX_SHEET_FILE_SIZE)]
+    FileTooLarge,
+
+    #[error("sheet must be a table of variable names and values")]
+    NotATable,
+
+    #[error(
+        "sheet has {0} variables, but at most {} can be imported",
+        MAX_SHEET_VARIABLES
+    )]
+    TooManyVariables(usize),
+
+    #[error("sheet has {} invalid variables", .0.len())]
+    InvalidVariables(Vec<KeyError>),
+}
+
+/// A sheet as received from the user: an optional format (from a code
+/// block language or a file extension) and the document itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetInput {
+    pub format: Option<SheetFormat>,
+    pub document: String,
+}
+
+impl SheetInput {
+    /// Extract the document from command input. The document may be
+    /// wrapped in a Markdown code block, in which case the language
+    /// of the block selects the format.
+    pub fn from_message(input: &str) -> SheetInput {
+        let input = input.trim();
+        let fenced = input
+            .strip_prefix("```")
+            .map(|rest| rest.strip_suffix("```").unwrap_or(rest));
+
+        match fenced {
+            Some(block) => {
+                let (language, document) = block.split_once('\n').unwrap_or(("", block));
+                SheetInput {
+                    format: SheetFormat::from_name(language),
+                    document: document.to_owned(),
+                }
+            }
+            None => SheetInput {
+                format: None,
+                document: input.to_owned(),
+            },
+        }
+    }
+
+    pub fn format(&self) -> SheetFormat {
+        self.format
+            .unwrap_or_else(|| SheetFormat::detect(&self.document))
+    }
+}
+
+/// Check a single value of the sheet. Only whole numbers that fit in
+/// a variable are accepted.
+fn json_value(value: &serde_json::Value) -> Result<i32, &'static str> {
+    match value {
+        serde_json::Value::Number(number) => number
+            .as_i64()
+            .ok_or("not a whole number")
+            .and_then(|n| i32::try_from(n).map_err(|_| "number is too large")),
+        _ => Err("not a number"),
+    }
+}
+
+fn toml_value(value: &toml::Value) -> Result<i32, &'static str> {
+    match value {
+        toml::Value::Integer(n) => i32::try_from(*n).map_err(|_| "number is too large"),
+        toml::Value::Float(_) => Err("not a whole number"),
+        _ => Err("not a number"),
+    }
+}
+
+/// Validate every key and value of the sheet, collecting the errors
+/// of all invalid keys rather than stopping at the first one.
+fn validate<V>(
+    table: impl Iterator<Item = (String, V)>,
+    value_of: impl Fn(&V) -> Result<i32, &'static str>,
+) -> Result<Vec<(String, i32)>, SheetError> {
+    let mut variables = vec![];
+    let mut errors = vec![];
+
+    for (key, value) in table {
+        if !is_valid_variable_name(&key) {
+            errors.push(KeyError::new(&key, "not a valid variable name"));
+            continue;
+        }
+
+        match value_of(&value) {
+            Ok(value) => variables.push((key, value)),
+            Err(reason) => errors.push(KeyError::new(&key, reason)),
+        }
+    }
+
+    if !errors.is_empty() {
+        Err(SheetError::InvalidVariables(errors))
+    } else if variables.len() > MAX_SHEET_VARIABLES {
+        Err(SheetError::TooManyVariables(variables.len()))
+    } else {
+        Ok(variables)
+    }
+}
+
+/// Parse a sheet into variables. Nothing is returned unless every
+/// key in the sheet is valid.
+pub fn parse_sheet(sheet: &SheetInput) -> Result<Vec<(String, i32)>, SheetError> {
+    let format = sheet.format();
+    let invalid = |e: &dyn fmt::Display| SheetError::InvalidDocument(format, e.to_string());
+
+    match format {
+        SheetFormat::Json => {
+            let document: serde_json::Value =
+                serde_json::from_str(&sheet.document).map_err(|e| invalid(&e))?;
+
+            match document {
+                serde_json::Value::Object(table) => validate(table.into_iter(), json_value),
+                _ => Err(SheetError::NotATable),
+            }
+        }
+        SheetFormat::Toml => {
+            let table: toml::value::Table =
+                toml::from_str(&sheet.document).map_err(|e| invalid(&e))?;
+            validate(table.into_iter(), toml_value)
+        }
+    }
+}
+
+/// Write variables out as a sheet, sorted by name.
+pub fn export_sheet<'a>(
+    variables: impl IntoIterator<Item = (&'a String, &'a i32)>,
+    format: SheetFormat,
+) -> String {
+    let variables: BTreeMap<&String, &i32> = variables.into_iter().collect();
+
+    // Serializing a map of strings to numbers cannot fail.
+    match format {
+        SheetFormat::Json => serde_json::to_string_pretty(&variables).unwrap_or_default(),
+        SheetFormat::Toml => toml::to_string(&variables).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn sheet(document: &str) -> SheetInput {
+        SheetInput::from_message(document)
+    }
+
+    #[test]
+    fn code_block_language_selects_format_test() {
+        let input = sheet("```toml\nstrength = 3\n```");
+        assert_eq!(input.format, Some(SheetFormat::Toml));
+        assert_eq!(input.document, "strength = 3\n");
+
+        let input = sheet("```\n{\"strength\": 3}\n```");
+        assert_eq!(input.format, None);
+        assert_eq!(input.format(), SheetFormat::Json);
+    }
+
+    #[test]
+    fn parse_json_sheet_test() {
+        let mut variables = parse_sheet(&sheet(r#"{"strength": 3, "dexterity": -1}"#)).unwrap();
+        variables.sort();
+        assert_eq!(
+            variables,
+            vec![("dexterity".to_string(), -1), ("strength".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn parse_toml_sheet_test() {
+        let variables = parse_sheet(&sheet("```toml\nstrength = 3\nwits = 2\n```"));
+        assert_eq!(
+            variables,
+            Ok(vec![("strength".to_string(), 3), ("wits".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn invalid_keys_are_all_reported_test() {
+        let result = parse_sheet(&sheet(
+            r#"{"strength": 3, "wits": "high", "luck": 1.5, "bad name": 1}"#,
+        ));
+
+        assert_eq!(
+            result,
+            Err(SheetError::InvalidVariables(vec![
+                KeyError::new("wits", "not a number"),
+                KeyError::new("luck", "not a whole number"),
+                KeyError::new("bad name", "not a valid variable name"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn sheet_must_be_a_table_test() {
+        assert_eq!(
+            parse_sheet(&sheet("```json\n[1, 2, 3]\n```")),
+            Err(SheetError::NotATable)
+        );
+        assert!(matches!(
+            parse_sheet(&sheet("{strength: 3")),
+            Err(SheetError::InvalidDocument(SheetFormat::Json, _))
+        ));
+    }
+
+    #[test]
+    fn exported_sheet_can_be_imported_test() {
+        let mut variables = HashMap::new();
+        variables.insert("strength".to_string(), 3);
+        variables.insert("composure".to_string(), 2);
+
+        for format in [SheetFormat::Json, SheetFormat::Toml] {
+            let exported = export_sheet(&variables, format);
+            let input = SheetInput {
+                format: Some(format),
+                document: exported,
+            };
+
+            let imported: HashMap<String, i32> = parse_sheet(&input).unwrap().into_iter().collect();
+
+            assert_eq!(imported, variables);
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use crate::config::*;
 use log::info;
+use once_cell::sync::Lazy;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// When the bot started running, for reporting its uptime.
+pub static STARTED: Lazy<Instant> = Lazy::new(Instant::now);
 
 /// Holds state of the dice bot, for anything requiring mutable
 /// transitions. This is a simple mutable trait whose values represent
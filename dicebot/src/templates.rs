@@ -0,0 +1,163 @@
+use crate::commands::escape_html;
+use crate::models::RollResult;
+use itertools::Itertools;
+use thiserror::Error;
+
+/// Longest roll template a room can set.
+pub const MAX_TEMPLATE_LENGTH: usize = 500;
+
+/// The placeholders a roll template can use, and what they show.
+pub const PLACEHOLDERS: [(&str, &str); 6] = [
+    ("user", "the user who rolled"),
+    ("system", "the game system of the roll"),
+    (
+        "expression",
+        "what was rolled, after variables were resolved",
+    ),
+    ("dice", "every die rolled"),
+    ("total", "the sum, successes or percentile result"),
+    ("outcome", "success or failure, if the system has one"),
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("unknown placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+
+    #[error("placeholder is not closed with }}")]
+    UnclosedPlaceholder,
+
+    #[error("template is empty")]
+    EmptyTemplate,
+
+    #[error("template can be at most {0} characters")]
+    TemplateTooLong(usize),
+}
+
+/// A piece of a roll template.
+#[derive(Debug, PartialEq)]
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Split a template into text and {placeholders}.
+fn parse(template: &str) -> Result<Vec<Part>, TemplateError> {
+    let mut parts = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let (text, placeholder) = rest.split_at(start);
+        let end = placeholder
+            .find('}')
+            .ok_or(TemplateError::UnclosedPlaceholder)?;
+
+        let name = &placeholder[1..end];
+        if !PLACEHOLDERS.iter().any(|(known, _)| *known == name) {
+            return Err(TemplateError::UnknownPlaceholder(name.to_owned()));
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        parts.push(Part::Placeholder(name));
+        rest = &placeholder[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+
+    Ok(parts)
+}
+
+/// Check that a template can be used for replies: it is not too long
+/// and only uses known placeholders.
+pub fn validate(template: &str) -> Result<(), TemplateError> {
+    match template.chars().count() {
+        0 => Err(TemplateError::EmptyTemplate),
+        len if len > MAX_TEMPLATE_LENGTH => {
+            Err(TemplateError::TemplateTooLong(MAX_TEMPLATE_LENGTH))
+        }
+        _ => parse(template).map(|_| ()),
+    }
+}
+
+/// The value a placeholder has for a roll.
+fn placeholder_value(name: &str, user: &str, roll: &RollResult) -> String {
+    let summary = &roll.summary;
+    match name {
+        "user" => user.to_owned(),
+        "system" => roll.system.key().to_owned(),
+        "expression" => summary.expression.clone(),
+        "dice" => summary.dice.iter().join(", "),
+        "total" => summary.total.to_string(),
+        "outcome" => summary.outcome.clone().unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// Render the reply to a roll with a template. Templates may use HTML
+/// for formatting, while placeholder values are escaped. The plain
+/// text of the reply is made from the HTML, like every other reply.
+pub fn render(template: &str, user: &str, roll: &RollResult) -> Result<String, TemplateError> {
+    let html = parse(template)?
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => text.to_owned(),
+            Part::Placeholder(name) => escape_html(&placeholder_value(name, user, roll)),
+        })
+        .collect();
+
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GameSystem, RollSummary};
+
+    fn pool_roll() -> RollResult {
+        RollResult {
+            system: GameSystem::ChroniclesOfDarkness,
+            summary: RollSummary {
+                expression: "3 dice".to_owned(),
+                dice: vec![10, 8, 2],
+                total: 2,
+                outcome: Some("<2> successes".to_owned()),
+            },
+        }
+    }
+
+    #[test]
+    fn render_template_test() {
+        let html = render(
+            "<em>{user}</em> rolled {expression} [{dice}]: {total}, {outcome} ({system})",
+            "@user:example.com",
+            &pool_roll(),
+        );
+
+        assert_eq!(
+            html,
+            Ok("<em>@user:example.com</em> rolled 3 dice [10, 8, 2]: 2, \
+                &lt;2&gt; successes (cofd)"
+                .to_owned())
+        );
+    }
+
+    #[test]
+    fn validate_template_test() {
+        assert_eq!(validate("{total} } {outcome}"), Ok(()));
+        assert_eq!(validate(""), Err(TemplateError::EmptyTemplate));
+        assert_eq!(
+            validate("{total} {dmg}"),
+            Err(TemplateError::UnknownPlaceholder("dmg".to_owned()))
+        );
+        assert_eq!(validate("{total"), Err(TemplateError::UnclosedPlaceholder));
+        assert_eq!(
+            validate(&"a".repeat(MAX_TEMPLATE_LENGTH + 1)),
+            Err(TemplateError::TemplateTooLong(MAX_TEMPLATE_LENGTH))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Golden-output tests: every command is executed the way the bot
+//! executes it, with seeded dice, and the replies are compared to the
+//! transcripts in tests/golden. Run with UPDATE_GOLDEN=1 to write the
+//! transcripts from the current replies instead.
+use matrix_sdk::identifiers::room_id;
+use matrix_sdk::Client;
+use std::path::PathBuf;
+use tenebrous_dicebot::commands::{execute_command, ResponseExtractor};
+use tenebrous_dicebot::context::{Context, RoomContext};
+use tenebrous_dicebot::db::sqlite::Database;
+use tenebrous_dicebot::logic;
+use tenebrous_dicebot::models::Verbosity;
+use tenebrous_dicebot::rng::DiceRng;
+use url::Url;
+
+/// Every transcript rolls the same dice.
+const SEED: u64 = 1;
+
+const USER: &str = "@user:example.com";
+
+/// The messages of a transcript. A message starts with "> ", and lines
+/// starting with ">> " continue it, for commands like !import.
+fn messages(transcript: &str) -> Vec<String> {
+    let mut messages: Vec<String> = vec![];
+
+    for line in transcript.lines() {
+        if let Some(continued) = line.strip_prefix(">> ") {
+            let message = messages.last_mut().expect("Continued line without message");
+            message.push('\n');
+            message.push_str(continued);
+        } else if let Some(message) = line.strip_prefix("> ") {
+            messages.push(message.to_owned());
+        }
+    }
+
+    messages
+}
+
+/// Execute the messages of a transcript in a fresh database, and
+/// write them down with their replies.
+async fn run_transcript(messages: &[String]) -> String {
+    let db_path = tempfile::NamedTempFile::new_in(".").unwrap();
+    let db = Database::new(db_path.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let homeserver = Url::parse("http://example.com").unwrap();
+    let matrix_client = Client::new(homeserver).unwrap();
+    let rng = DiceRng::seeded(SEED);
+
+    // The room is an encrypted direct message, and the user a bot
+    // admin, so that every command may be executed.
+    let room_id = room_id!("!golden:example.com");
+    let room = RoomContext {
+        id: &room_id,
+        display_name: "golden room".to_owned(),
+        secure: true,
+    };
+
+    let mut transcript = String::new();
+    for message in messages {
+        let ctx = Context {
+            account: logic::get_account(&db, USER).await.unwrap(),
+            db: db.clone(),
+            matrix_client: matrix_client.clone(),
+            origin_room: room.clone(),
+            active_room: room.clone(),
+            username: USER,
+            message_body: message,
+            thread_id: None,
+            admin: true,
+            verbosity: Verbosity::default(),
+            rng: rng.clone(),
+        };
+
+        let reply = execute_command(&ctx).await.message_plain(USER);
+        let message = message.replace('\n', "\n>> ");
+        transcript.push_str(&format!("> {}\n{}\n\n", message, reply.trim_end()));
+    }
+
+    transcript
+}
+
+async fn check_golden(name: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect();
+
+    let expected = std::fs::read_to_string(&path).expect("Could not read transcript");
+    let actual = run_transcript(&messages(&expected)).await;
+
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        std::fs::write(&path, &actual).expect("Could not write transcript");
+    } else {
+        assert_eq!(expected, actual, "replies differ from {}", path.display());
+    }
+}
+
+macro_rules! golden {
+    ($name:ident, $file:expr) => {
+        #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+        async fn $name() {
+            check_golden($file).await;
+        }
+    };
+}
+
+golden!(basic_rolls, "basic_rolls.txt");
+golden!(dice_pools, "dice_pools.txt");
+golden!(cthulhu_rolls, "cthulhu_rolls.txt");
+golden!(probability, "probability.txt");
+golden!(variables, "variables.txt");
+golden!(room_variables, "room_variables.txt");
+golden!(characters, "characters.txt");
+golden!(sheets, "sheets.txt");
+golden!(macros, "macros.txt");
+golden!(room_settings, "room_settings.txt");
+golden!(history, "history.txt");
+golden!(accounts, "accounts.txt");
+// !admin status (uptime) and !admin broadcast (sends to Matrix) are
+// left out, since their replies depend on more than the commands.
+golden!(admin, "admin.txt");
+golden!(help, "help.txt");